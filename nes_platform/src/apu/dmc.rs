@@ -0,0 +1,201 @@
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
+
+// NTSC 下 DMC 的输出速率, 单位是 CPU 周期
+const DMC_RATE_TABLE: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+
+/**
+DMC (增量调制) 通道, 直接从 CPU 内存中读取 1 位增量编码的采样播放
+寄存器 0x4010-0x4013:
+ 0 IL-- RRRR  IRQ 打开, 循环, 速率
+ 1 -DDD DDDD  直接写入输出值
+ 2 AAAA AAAA  采样地址 = 0xC000 + A * 64
+ 3 LLLL LLLL  采样长度 = L * 16 + 1
+https://www.nesdev.org/wiki/APU_DMC
+ */
+#[derive(Debug, Default, Clone)]
+pub struct Dmc {
+    irq_enabled: bool,
+    looping: bool,
+    timer_period: u16,
+    timer: u16,
+    output_level: u8,
+
+    sample_address: u16,
+    sample_length: u16,
+    current_address: u16,
+    pub bytes_remaining: u16,
+    sample_buffer: Option<u8>,
+
+    shift_register: u8,
+    bits_remaining: u8,
+    // 输出单元中有没有正在播放的采样字节
+    playing: bool,
+
+    pub irq: bool,
+}
+
+impl Dmc {
+    pub fn write_register(&mut self, register: u16, data: u8) {
+        match register {
+            0 => {
+                self.irq_enabled = data & 0b1000_0000 != 0;
+                if !self.irq_enabled {
+                    self.irq = false;
+                }
+                self.looping = data & 0b0100_0000 != 0;
+                self.timer_period = DMC_RATE_TABLE[(data & 0b1111) as usize];
+            }
+            1 => self.output_level = data & 0b0111_1111,
+            2 => self.sample_address = 0xC000 + data as u16 * 64,
+            3 => self.sample_length = data as u16 * 16 + 1,
+            _ => {}
+        }
+    }
+
+    // 写 0x4015 的第 4 位
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.irq = false;
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    /*
+    采样缓冲区空了并且还有剩余字节时, 需要从 CPU 内存读下一个字节, 返回它的地址
+    读取由总线完成, 读到的数据通过 fill_sample_buffer 送回来
+     */
+    pub fn pending_read(&self) -> Option<u16> {
+        if self.sample_buffer.is_none() && self.bytes_remaining > 0 {
+            Some(self.current_address)
+        } else {
+            None
+        }
+    }
+
+    pub fn fill_sample_buffer(&mut self, data: u8) {
+        self.sample_buffer = Some(data);
+        // 地址到 0xFFFF 之后回到 0x8000
+        self.current_address = if self.current_address == 0xFFFF { 0x8000 } else { self.current_address + 1 };
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.timer_period.saturating_sub(1);
+
+        if self.playing {
+            // 1 表示输出加 2, 0 表示减 2, 超出 0-127 时不变
+            if self.shift_register & 1 == 1 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+
+        if self.bits_remaining > 0 {
+            self.bits_remaining -= 1;
+        }
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(data) => {
+                    self.playing = true;
+                    self.shift_register = data;
+                }
+                None => self.playing = false,
+            }
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        self.output_level
+    }
+}
+
+impl SaveState for Dmc {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.irq_enabled);
+        state.write_bool(self.looping);
+        state.write_u16(self.timer_period);
+        state.write_u16(self.timer);
+        state.write_u8(self.output_level);
+        state.write_u16(self.sample_address);
+        state.write_u16(self.sample_length);
+        state.write_u16(self.current_address);
+        state.write_u16(self.bytes_remaining);
+        state.write_bool(self.sample_buffer.is_some());
+        state.write_u8(self.sample_buffer.unwrap_or(0));
+        state.write_u8(self.shift_register);
+        state.write_u8(self.bits_remaining);
+        state.write_bool(self.playing);
+        state.write_bool(self.irq);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.irq_enabled = state.read_bool()?;
+        self.looping = state.read_bool()?;
+        self.timer_period = state.read_u16()?;
+        self.timer = state.read_u16()?;
+        self.output_level = state.read_u8()?;
+        self.sample_address = state.read_u16()?;
+        self.sample_length = state.read_u16()?;
+        self.current_address = state.read_u16()?;
+        self.bytes_remaining = state.read_u16()?;
+        let has_sample = state.read_bool()?;
+        let sample = state.read_u8()?;
+        self.sample_buffer = has_sample.then_some(sample);
+        self.shift_register = state.read_u8()?;
+        self.bits_remaining = state.read_u8()?;
+        self.playing = state.read_bool()?;
+        self.irq = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dmc_reads_sample_and_raises_irq() {
+        let mut dmc = Dmc::default();
+        // IRQ 打开, 最快速率, 采样地址 0xC040, 长度 1
+        dmc.write_register(0, 0b1000_1111);
+        dmc.write_register(1, 0x40);
+        dmc.write_register(2, 0x01);
+        dmc.write_register(3, 0x00);
+        dmc.set_enabled(true);
+        assert_eq!(dmc.pending_read(), Some(0xC040));
+        dmc.fill_sample_buffer(0xff);
+        assert_eq!(dmc.pending_read(), None);
+        assert!(dmc.irq);
+
+        // 取出采样之后每个输出周期加 2
+        for _ in 0..54 * 3 {
+            dmc.clock_timer();
+        }
+        assert_eq!(dmc.output(), 0x44);
+    }
+}
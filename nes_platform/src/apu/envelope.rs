@@ -0,0 +1,171 @@
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
+
+/*
+长度计数器的装载值, 写通道的第 4 个寄存器时用高 5 位查表
+https://www.nesdev.org/wiki/APU_Length_Counter
+ */
+const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14,
+    12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+/**
+长度计数器: 减到 0 时通道静音, 由帧计数器的半帧信号驱动
+ */
+#[derive(Debug, Default, Clone)]
+pub struct LengthCounter {
+    pub counter: u8,
+    pub enabled: bool,
+    // 暂停计数 (和包络的循环标志是同一位)
+    pub halt: bool,
+}
+
+impl LengthCounter {
+    pub fn load(&mut self, index: u8) {
+        if self.enabled {
+            self.counter = LENGTH_TABLE[(index & 0x1F) as usize];
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.counter = 0;
+        }
+    }
+
+    pub fn clock(&mut self) {
+        if !self.halt && self.counter > 0 {
+            self.counter -= 1;
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.counter > 0
+    }
+}
+
+/**
+包络发生器: 输出固定音量, 或者从 15 开始逐渐衰减的音量, 由帧计数器的四分之一帧信号驱动
+https://www.nesdev.org/wiki/APU_Envelope
+ */
+#[derive(Debug, Default, Clone)]
+pub struct Envelope {
+    pub start: bool,
+    pub looping: bool,
+    pub constant_volume: bool,
+    // 固定音量, 或者衰减的周期
+    pub volume: u8,
+    divider: u8,
+    decay: u8,
+}
+
+impl Envelope {
+    // 写通道的第 1 个寄存器: --LC VVVV
+    pub fn write(&mut self, data: u8) {
+        self.looping = data & 0b0010_0000 != 0;
+        self.constant_volume = data & 0b0001_0000 != 0;
+        self.volume = data & 0b1111;
+    }
+
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.looping {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.constant_volume {
+            self.volume
+        } else {
+            self.decay
+        }
+    }
+}
+
+impl SaveState for LengthCounter {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.counter);
+        state.write_bool(self.enabled);
+        state.write_bool(self.halt);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.counter = state.read_u8()?;
+        self.enabled = state.read_bool()?;
+        self.halt = state.read_bool()?;
+        Ok(())
+    }
+}
+
+impl SaveState for Envelope {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.start);
+        state.write_bool(self.looping);
+        state.write_bool(self.constant_volume);
+        state.write_u8(self.volume);
+        state.write_u8(self.divider);
+        state.write_u8(self.decay);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.start = state.read_bool()?;
+        self.looping = state.read_bool()?;
+        self.constant_volume = state.read_bool()?;
+        self.volume = state.read_u8()?;
+        self.divider = state.read_u8()?;
+        self.decay = state.read_u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_length_counter() {
+        let mut length = LengthCounter::default();
+        // 没有打开的通道不装载
+        length.load(1);
+        assert_eq!(length.counter, 0);
+        length.set_enabled(true);
+        length.load(1);
+        assert_eq!(length.counter, 254);
+        length.clock();
+        assert_eq!(length.counter, 253);
+        length.halt = true;
+        length.clock();
+        assert_eq!(length.counter, 253);
+        length.set_enabled(false);
+        assert!(!length.active());
+    }
+
+    #[test]
+    fn test_envelope_decay() {
+        let mut envelope = Envelope::default();
+        envelope.write(0b0000_0001);
+        envelope.start = true;
+        envelope.clock();
+        assert_eq!(envelope.output(), 15);
+        // 周期为 1 时每 2 次时钟衰减 1
+        envelope.clock();
+        assert_eq!(envelope.output(), 15);
+        envelope.clock();
+        assert_eq!(envelope.output(), 14);
+
+        envelope.write(0b0001_0111);
+        assert_eq!(envelope.output(), 7);
+    }
+}
@@ -0,0 +1,420 @@
+use crate::apu::dmc::Dmc;
+use crate::apu::noise::Noise;
+use crate::apu::pulse::Pulse;
+use crate::apu::triangle::Triangle;
+use crate::mapper::Mapper;
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
+
+pub mod dmc;
+pub mod envelope;
+pub mod noise;
+pub mod pulse;
+pub mod triangle;
+pub mod wav;
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+
+/*
+帧计数器的时序, 单位是 CPU 周期
+https://www.nesdev.org/wiki/APU_Frame_Counter
+ 4 步模式: 每一步都有四分之一帧信号, 第 2, 4 步还有半帧信号, 第 4 步产生 IRQ
+ 5 步模式: 第 4 步什么都不做, 第 2, 5 步有半帧信号, 不产生 IRQ
+ */
+const FRAME_STEP_1: u32 = 7457;
+const FRAME_STEP_2: u32 = 14913;
+const FRAME_STEP_3: u32 = 22371;
+const FRAME_STEP_4: u32 = 29829;
+const FRAME_STEP_5: u32 = 37281;
+
+// 输出的高通滤波, 去掉混音结果中的直流分量
+const HIGH_PASS_FACTOR: f32 = 0.996;
+
+/**
+NES 的音频处理单元 (2A03 中的 APU)
+CPU 通过 0x4000-0x4013, 0x4015 和 0x4017 访问它:
+ 0x4000-0x4007 两个方波通道
+ 0x4008-0x400B 三角波通道
+ 0x400C-0x400F 噪声通道
+ 0x4010-0x4013 DMC 通道
+ 0x4015        通道开关 (写) / 通道状态 (读)
+ 0x4017        帧计数器
+ */
+pub struct Apu {
+    pub pulse1: Pulse,
+    pub pulse2: Pulse,
+    pub triangle: Triangle,
+    pub noise: Noise,
+    pub dmc: Dmc,
+
+    five_step_mode: bool,
+    frame_irq_inhibit: bool,
+    pub frame_irq: bool,
+    frame_cycle: u32,
+    // 方波通道的定时器每 2 个 CPU 周期计数一次
+    odd_cycle: bool,
+
+    cpu_clock_hz: f64,
+    sample_rate: u32,
+    // 每个采样之间的 CPU 周期数, 一般不是整数
+    cycles_per_sample: f64,
+    sample_timer: f64,
+    samples: Vec<f32>,
+    filter_input: f32,
+    filter_output: f32,
+
+    // DMC 从内存读取采样时 CPU 需要暂停的周期数
+    stall_cycles: u16,
+}
+
+impl Apu {
+    pub fn new(cpu_clock_hz: f64, sample_rate: u32) -> Self {
+        Apu {
+            pulse1: Pulse::new(true),
+            pulse2: Pulse::new(false),
+            triangle: Triangle::default(),
+            noise: Noise::default(),
+            dmc: Dmc::default(),
+            five_step_mode: false,
+            frame_irq_inhibit: false,
+            frame_irq: false,
+            frame_cycle: 0,
+            odd_cycle: false,
+            cpu_clock_hz,
+            sample_rate,
+            cycles_per_sample: cpu_clock_hz / sample_rate as f64,
+            sample_timer: 0.0,
+            samples: Vec::new(),
+            filter_input: 0.0,
+            filter_output: 0.0,
+            stall_cycles: 0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.cycles_per_sample = self.cpu_clock_hz / sample_rate as f64;
+    }
+
+    pub fn write_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4000..=0x4003 => self.pulse1.write_register(addr - 0x4000, data),
+            0x4004..=0x4007 => self.pulse2.write_register(addr - 0x4004, data),
+            0x4008..=0x400B => self.triangle.write_register(addr - 0x4008, data),
+            0x400C..=0x400F => self.noise.write_register(addr - 0x400C, data),
+            0x4010..=0x4013 => self.dmc.write_register(addr - 0x4010, data),
+            0x4015 => {
+                self.pulse1.length.set_enabled(data & 0b0000_0001 != 0);
+                self.pulse2.length.set_enabled(data & 0b0000_0010 != 0);
+                self.triangle.length.set_enabled(data & 0b0000_0100 != 0);
+                self.noise.length.set_enabled(data & 0b0000_1000 != 0);
+                self.dmc.set_enabled(data & 0b0001_0000 != 0);
+            }
+            0x4017 => {
+                self.five_step_mode = data & 0b1000_0000 != 0;
+                self.frame_irq_inhibit = data & 0b0100_0000 != 0;
+                if self.frame_irq_inhibit {
+                    self.frame_irq = false;
+                }
+                self.frame_cycle = 0;
+                // 切换到 5 步模式时立即产生一次四分之一帧和半帧信号
+                if self.five_step_mode {
+                    self.clock_quarter_frame();
+                    self.clock_half_frame();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /*
+    读 0x4015: IF-D NT21  DMC 中断, 帧中断, DMC 还有剩余字节, 各通道的长度计数器不为 0
+    读取之后清除帧中断
+     */
+    pub fn read_status(&mut self) -> u8 {
+        let mut data = 0;
+        if self.pulse1.length.active() {
+            data |= 0b0000_0001;
+        }
+        if self.pulse2.length.active() {
+            data |= 0b0000_0010;
+        }
+        if self.triangle.length.active() {
+            data |= 0b0000_0100;
+        }
+        if self.noise.length.active() {
+            data |= 0b0000_1000;
+        }
+        if self.dmc.bytes_remaining > 0 {
+            data |= 0b0001_0000;
+        }
+        if self.frame_irq {
+            data |= 0b0100_0000;
+        }
+        if self.dmc.irq {
+            data |= 0b1000_0000;
+        }
+        self.frame_irq = false;
+        data
+    }
+
+    pub fn irq_pending(&self) -> bool {
+        self.frame_irq || self.dmc.irq
+    }
+
+    /*
+    APU 前进 cycles 个 CPU 周期, DMC 需要通过 mapper 读取卡带上的采样
+     */
+    pub fn tick(&mut self, cycles: u16, mapper: &dyn Mapper) {
+        for _ in 0..cycles {
+            self.step_cycle(mapper);
+        }
+    }
+
+    fn step_cycle(&mut self, mapper: &dyn Mapper) {
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+        if self.odd_cycle {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+        self.odd_cycle = !self.odd_cycle;
+
+        // 采样地址在 0xC000-0xFFFF (回绕到 0x8000 之后), 总是在卡带上
+        if let Some(addr) = self.dmc.pending_read() {
+            self.dmc.fill_sample_buffer(mapper.cpu_read(addr));
+            self.stall_cycles += 4;
+        }
+
+        self.step_frame_counter();
+
+        self.sample_timer += 1.0;
+        if self.sample_timer >= self.cycles_per_sample {
+            self.sample_timer -= self.cycles_per_sample;
+            let sample = self.high_pass(self.mix());
+            self.samples.push(sample);
+        }
+    }
+
+    fn step_frame_counter(&mut self) {
+        self.frame_cycle += 1;
+        match self.frame_cycle {
+            FRAME_STEP_1 | FRAME_STEP_3 => self.clock_quarter_frame(),
+            FRAME_STEP_2 => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            }
+            FRAME_STEP_4 if !self.five_step_mode => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+                if !self.frame_irq_inhibit {
+                    self.frame_irq = true;
+                }
+            }
+            FRAME_STEP_5 if self.five_step_mode => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            }
+            _ => {}
+        }
+        let period = if self.five_step_mode { FRAME_STEP_5 + 1 } else { FRAME_STEP_4 + 1 };
+        if self.frame_cycle >= period {
+            self.frame_cycle = 0;
+        }
+    }
+
+    // 包络和三角波的线性计数器
+    fn clock_quarter_frame(&mut self) {
+        self.pulse1.envelope.clock();
+        self.pulse2.envelope.clock();
+        self.noise.envelope.clock();
+        self.triangle.clock_linear_counter();
+    }
+
+    // 长度计数器和扫频单元
+    fn clock_half_frame(&mut self) {
+        self.pulse1.length.clock();
+        self.pulse2.length.clock();
+        self.triangle.length.clock();
+        self.noise.length.clock();
+        self.pulse1.clock_sweep();
+        self.pulse2.clock_sweep();
+    }
+
+    /*
+    非线性混音, 输出范围 0.0-1.0
+    https://www.nesdev.org/wiki/APU_Mixer
+     */
+    fn mix(&self) -> f32 {
+        let pulse = (self.pulse1.output() + self.pulse2.output()) as f32;
+        let pulse_out = if pulse == 0.0 { 0.0 } else { 95.88 / (8128.0 / pulse + 100.0) };
+
+        let tnd = self.triangle.output() as f32 / 8227.0
+            + self.noise.output() as f32 / 12241.0
+            + self.dmc.output() as f32 / 22638.0;
+        let tnd_out = if tnd == 0.0 { 0.0 } else { 159.79 / (1.0 / tnd + 100.0) };
+
+        pulse_out + tnd_out
+    }
+
+    fn high_pass(&mut self, input: f32) -> f32 {
+        let output = HIGH_PASS_FACTOR * self.filter_output + input - self.filter_input;
+        self.filter_input = input;
+        self.filter_output = output;
+        output
+    }
+
+    // 取出到目前为止生成的采样
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+
+    pub fn take_stall_cycles(&mut self) -> u16 {
+        let stall = self.stall_cycles;
+        self.stall_cycles = 0;
+        stall
+    }
+}
+
+/*
+采样率之类的配置不属于机器的状态, 恢复之后丢弃还没有取走的采样
+ */
+impl SaveState for Apu {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_tag(b"APU ");
+        self.pulse1.save_state(state);
+        self.pulse2.save_state(state);
+        self.triangle.save_state(state);
+        self.noise.save_state(state);
+        self.dmc.save_state(state);
+        state.write_bool(self.five_step_mode);
+        state.write_bool(self.frame_irq_inhibit);
+        state.write_bool(self.frame_irq);
+        state.write_u32(self.frame_cycle);
+        state.write_bool(self.odd_cycle);
+        state.write_f64(self.sample_timer);
+        state.write_f32(self.filter_input);
+        state.write_f32(self.filter_output);
+        state.write_u16(self.stall_cycles);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.read_tag(b"APU ")?;
+        self.pulse1.load_state(state)?;
+        self.pulse2.load_state(state)?;
+        self.triangle.load_state(state)?;
+        self.noise.load_state(state)?;
+        self.dmc.load_state(state)?;
+        self.five_step_mode = state.read_bool()?;
+        self.frame_irq_inhibit = state.read_bool()?;
+        self.frame_irq = state.read_bool()?;
+        self.frame_cycle = state.read_u32()?;
+        self.odd_cycle = state.read_bool()?;
+        self.sample_timer = state.read_f64()?;
+        self.filter_input = state.read_f32()?;
+        self.filter_output = state.read_f32()?;
+        self.stall_cycles = state.read_u16()?;
+        self.samples.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::apu::wav;
+    use crate::cartridge::Cartridge;
+    use crate::mapper::nrom::Nrom;
+    use crate::timing::Region;
+
+    fn ntsc_apu() -> Apu {
+        Apu::new(Region::Ntsc.cpu_clock_hz(), DEFAULT_SAMPLE_RATE)
+    }
+
+    #[test]
+    fn test_status_and_length_counters() {
+        let mut apu = ntsc_apu();
+        apu.write_register(0x4015, 0b0000_0011);
+        apu.write_register(0x4003, 0b0000_1000);
+        apu.write_register(0x4007, 0b0000_1000);
+        // 三角波没有打开, 长度计数器不装载
+        apu.write_register(0x400B, 0b0000_1000);
+        assert_eq!(apu.read_status(), 0b0000_0011);
+        apu.write_register(0x4015, 0b0000_0001);
+        assert_eq!(apu.read_status(), 0b0000_0001);
+    }
+
+    #[test]
+    fn test_frame_irq() {
+        let mapper = Nrom::new(Cartridge::default());
+        let mut apu = ntsc_apu();
+        apu.tick(FRAME_STEP_4 as u16 - 1, &mapper);
+        assert!(!apu.irq_pending());
+        apu.tick(1, &mapper);
+        assert!(apu.irq_pending());
+        assert_eq!(apu.read_status() & 0b0100_0000, 0b0100_0000);
+        assert!(!apu.irq_pending());
+
+        // 屏蔽帧中断
+        apu.write_register(0x4017, 0b0100_0000);
+        apu.tick(FRAME_STEP_4 as u16, &mapper);
+        assert!(!apu.irq_pending());
+
+        // 5 步模式不产生帧中断
+        apu.write_register(0x4017, 0b1000_0000);
+        apu.tick(FRAME_STEP_5 as u16 + 1, &mapper);
+        assert!(!apu.irq_pending());
+    }
+
+    #[test]
+    fn test_dmc_reads_through_mapper() {
+        let mapper = Nrom::new(Cartridge { prg_rom: vec![0xAA; 0x4000], ..Cartridge::default() });
+        let mut apu = ntsc_apu();
+        apu.write_register(0x4010, 0b1000_1111);
+        apu.write_register(0x4013, 0x00);
+        apu.write_register(0x4015, 0b0001_0000);
+        apu.tick(1, &mapper);
+        assert_eq!(apu.take_stall_cycles(), 4);
+        assert_eq!(apu.read_status() & 0b1001_0000, 0b1000_0000);
+        assert!(apu.irq_pending());
+        // 写 0x4015 清除 DMC 中断
+        apu.write_register(0x4015, 0);
+        assert!(!apu.irq_pending());
+    }
+
+    #[test]
+    fn test_sample_stream_and_wav_output() {
+        let mapper = Nrom::new(Cartridge::default());
+        let mut apu = ntsc_apu();
+        apu.set_sample_rate(22_050);
+        // 方波 1: 50% 占空比, 固定音量 15, 大约 440Hz
+        apu.write_register(0x4015, 0b0000_0001);
+        apu.write_register(0x4000, 0b1011_1111);
+        apu.write_register(0x4002, 0xFD);
+        apu.write_register(0x4003, 0x00);
+        // 0.1 秒
+        for _ in 0..179 {
+            apu.tick(1000, &mapper);
+        }
+        let samples = apu.take_samples();
+        assert!((2200..=2210).contains(&samples.len()));
+        assert!(samples.iter().any(|s| *s > 0.05));
+        assert!(samples.iter().any(|s| *s < -0.05));
+        assert!(apu.take_samples().is_empty());
+
+        let mut buffer = Vec::new();
+        wav::write_wav(&mut buffer, apu.sample_rate(), &samples).unwrap();
+        assert_eq!(&buffer[0..4], b"RIFF");
+        assert_eq!(&buffer[8..16], b"WAVEfmt ");
+        assert_eq!(buffer.len(), 44 + samples.len() * 2);
+
+        let path = std::env::temp_dir().join("nes_platform_apu_test.wav");
+        wav::save_wav(&path, apu.sample_rate(), &samples).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len() as usize, buffer.len());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
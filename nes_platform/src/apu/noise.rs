@@ -0,0 +1,126 @@
+use crate::apu::envelope::{Envelope, LengthCounter};
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
+
+// NTSC 下噪声通道定时器的周期, 单位是 CPU 周期
+const NOISE_PERIOD_TABLE: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+
+/**
+噪声通道, 用 15 位的线性反馈移位寄存器产生伪随机序列
+寄存器 0x400C-0x400F:
+ 0 --LC VVVV  长度计数器暂停/包络循环, 固定音量, 音量/包络周期
+ 2 M--- PPPP  模式 (短序列), 周期
+ 3 LLLL L---  长度计数器装载值
+https://www.nesdev.org/wiki/APU_Noise
+ */
+#[derive(Debug, Clone)]
+pub struct Noise {
+    mode: bool,
+    timer_period: u16,
+    timer: u16,
+    shift_register: u16,
+    pub envelope: Envelope,
+    pub length: LengthCounter,
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Noise {
+            mode: false,
+            timer_period: NOISE_PERIOD_TABLE[0],
+            timer: 0,
+            // 上电时移位寄存器为 1
+            shift_register: 1,
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+        }
+    }
+}
+
+impl Noise {
+    pub fn write_register(&mut self, register: u16, data: u8) {
+        match register {
+            0 => {
+                self.length.halt = data & 0b0010_0000 != 0;
+                self.envelope.write(data);
+            }
+            2 => {
+                self.mode = data & 0b1000_0000 != 0;
+                self.timer_period = NOISE_PERIOD_TABLE[(data & 0b1111) as usize];
+            }
+            3 => {
+                self.length.load(data >> 3);
+                self.envelope.start = true;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+            // 反馈位是第 0 位和第 1 位 (短序列模式下是第 6 位) 的异或
+            let other = if self.mode { 6 } else { 1 };
+            let feedback = (self.shift_register ^ (self.shift_register >> other)) & 1;
+            self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.length.active() || self.shift_register & 1 == 1 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+impl SaveState for Noise {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.mode);
+        state.write_u16(self.timer_period);
+        state.write_u16(self.timer);
+        state.write_u16(self.shift_register);
+        self.envelope.save_state(state);
+        self.length.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.mode = state.read_bool()?;
+        self.timer_period = state.read_u16()?;
+        self.timer = state.read_u16()?;
+        self.shift_register = state.read_u16()?;
+        self.envelope.load_state(state)?;
+        self.length.load_state(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_noise_shift_register() {
+        let mut noise = Noise::default();
+        noise.clock_timer();
+        // 1 的第 0 位和第 1 位异或为 1, 移入第 14 位
+        assert_eq!(noise.shift_register, 0x4000);
+
+        // 长序列模式的周期是 32767
+        let mut noise = Noise::default();
+        let mut period = 0;
+        loop {
+            for _ in 0..4 {
+                noise.clock_timer();
+            }
+            period += 1;
+            if noise.shift_register == 1 {
+                break;
+            }
+        }
+        assert_eq!(period, 32767);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::apu::envelope::{Envelope, LengthCounter};
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
+
+/*
+4 种占空比的波形, 每种 8 步
+https://www.nesdev.org/wiki/APU_Pulse
+ */
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+/**
+方波通道, 有两个, 区别只在于扫频单元取反时第 1 个用反码, 第 2 个用补码
+寄存器 (第 1 个通道 0x4000-0x4003, 第 2 个通道 0x4004-0x4007):
+ 0 DDLC VVVV  占空比, 长度计数器暂停/包络循环, 固定音量, 音量/包络周期
+ 1 EPPP NSSS  扫频: 打开, 周期, 取反, 移位
+ 2 TTTT TTTT  定时器低 8 位
+ 3 LLLL LTTT  长度计数器装载值, 定时器高 3 位
+ */
+#[derive(Debug, Default, Clone)]
+pub struct Pulse {
+    // 第 1 个方波通道扫频取反时多减 1
+    ones_complement: bool,
+    duty: u8,
+    sequence_step: u8,
+    timer_period: u16,
+    timer: u16,
+    pub envelope: Envelope,
+    pub length: LengthCounter,
+
+    sweep_enabled: bool,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_divider: u8,
+    sweep_reload: bool,
+}
+
+impl Pulse {
+    pub fn new(ones_complement: bool) -> Self {
+        Pulse { ones_complement, ..Pulse::default() }
+    }
+
+    pub fn write_register(&mut self, register: u16, data: u8) {
+        match register {
+            0 => {
+                self.duty = data >> 6;
+                self.length.halt = data & 0b0010_0000 != 0;
+                self.envelope.write(data);
+            }
+            1 => {
+                self.sweep_enabled = data & 0b1000_0000 != 0;
+                self.sweep_period = (data >> 4) & 0b111;
+                self.sweep_negate = data & 0b0000_1000 != 0;
+                self.sweep_shift = data & 0b111;
+                self.sweep_reload = true;
+            }
+            2 => self.timer_period = (self.timer_period & 0x0700) | data as u16,
+            3 => {
+                self.timer_period = (self.timer_period & 0x00FF) | ((data as u16 & 0b111) << 8);
+                self.length.load(data >> 3);
+                // 重新开始波形和包络
+                self.sequence_step = 0;
+                self.envelope.start = true;
+            }
+            _ => {}
+        }
+    }
+
+    // 定时器每 2 个 CPU 周期 (1 个 APU 周期) 计数一次
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.sequence_step = (self.sequence_step + 1) % 8;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn sweep_target(&self) -> u16 {
+        let change = self.timer_period >> self.sweep_shift;
+        if self.sweep_negate {
+            let change = if self.ones_complement { change + 1 } else { change };
+            self.timer_period.saturating_sub(change)
+        } else {
+            self.timer_period + change
+        }
+    }
+
+    // 周期太短或者扫频的目标周期超出 11 位时通道静音
+    fn muted(&self) -> bool {
+        self.timer_period < 8 || self.sweep_target() > 0x7FF
+    }
+
+    // 半帧信号
+    pub fn clock_sweep(&mut self) {
+        if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift > 0 && !self.muted() {
+            self.timer_period = self.sweep_target();
+        }
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.length.active() || self.muted() || DUTY_TABLE[self.duty as usize][self.sequence_step as usize] == 0 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+impl SaveState for Pulse {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.duty);
+        state.write_u8(self.sequence_step);
+        state.write_u16(self.timer_period);
+        state.write_u16(self.timer);
+        self.envelope.save_state(state);
+        self.length.save_state(state);
+        state.write_bool(self.sweep_enabled);
+        state.write_u8(self.sweep_period);
+        state.write_bool(self.sweep_negate);
+        state.write_u8(self.sweep_shift);
+        state.write_u8(self.sweep_divider);
+        state.write_bool(self.sweep_reload);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.duty = state.read_u8()?;
+        self.sequence_step = state.read_u8()?;
+        self.timer_period = state.read_u16()?;
+        self.timer = state.read_u16()?;
+        self.envelope.load_state(state)?;
+        self.length.load_state(state)?;
+        self.sweep_enabled = state.read_bool()?;
+        self.sweep_period = state.read_u8()?;
+        self.sweep_negate = state.read_bool()?;
+        self.sweep_shift = state.read_u8()?;
+        self.sweep_divider = state.read_u8()?;
+        self.sweep_reload = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pulse_sweep() {
+        let mut pulse = Pulse::new(true);
+        pulse.length.set_enabled(true);
+        pulse.write_register(2, 0x00);
+        pulse.write_register(3, 0x01);
+        // 打开扫频, 周期 0, 取反, 移位 1
+        pulse.write_register(1, 0b1000_1001);
+        pulse.clock_sweep();
+        // 0x100 - (0x80 + 1)
+        assert_eq!(pulse.timer_period, 0x7f);
+
+        let mut pulse = Pulse::new(false);
+        pulse.write_register(2, 0x00);
+        pulse.write_register(3, 0x01);
+        pulse.write_register(1, 0b1000_1001);
+        pulse.clock_sweep();
+        assert_eq!(pulse.timer_period, 0x80);
+    }
+
+    #[test]
+    fn test_pulse_output() {
+        let mut pulse = Pulse::new(false);
+        pulse.length.set_enabled(true);
+        // 占空比 50%, 固定音量 9
+        pulse.write_register(0, 0b1001_1001);
+        pulse.write_register(2, 0x10);
+        pulse.write_register(3, 0x08);
+        let mut outputs = Vec::new();
+        for _ in 0..8 {
+            outputs.push(pulse.output());
+            for _ in 0..=0x10 {
+                pulse.clock_timer();
+            }
+        }
+        assert_eq!(outputs, vec![0, 9, 9, 9, 9, 0, 0, 0]);
+
+        // 周期小于 8 时静音
+        pulse.write_register(2, 0x07);
+        pulse.write_register(3, 0x08);
+        assert_eq!(pulse.output(), 0);
+    }
+}
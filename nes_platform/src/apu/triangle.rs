@@ -0,0 +1,131 @@
+use crate::apu::envelope::LengthCounter;
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
+
+// 32 步的三角波 15..0, 0..15
+const TRIANGLE_SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+/**
+三角波通道, 没有音量控制, 除了长度计数器还有一个线性计数器
+寄存器 0x4008-0x400B:
+ 0 CRRR RRRR  控制 (长度计数器暂停/线性计数器控制), 线性计数器装载值
+ 2 TTTT TTTT  定时器低 8 位
+ 3 LLLL LTTT  长度计数器装载值, 定时器高 3 位
+https://www.nesdev.org/wiki/APU_Triangle
+ */
+#[derive(Debug, Default, Clone)]
+pub struct Triangle {
+    control: bool,
+    linear_reload_value: u8,
+    linear_counter: u8,
+    linear_reload: bool,
+    timer_period: u16,
+    timer: u16,
+    sequence_step: u8,
+    pub length: LengthCounter,
+}
+
+impl Triangle {
+    pub fn write_register(&mut self, register: u16, data: u8) {
+        match register {
+            0 => {
+                self.control = data & 0b1000_0000 != 0;
+                self.length.halt = self.control;
+                self.linear_reload_value = data & 0b0111_1111;
+            }
+            2 => self.timer_period = (self.timer_period & 0x0700) | data as u16,
+            3 => {
+                self.timer_period = (self.timer_period & 0x00FF) | ((data as u16 & 0b111) << 8);
+                self.length.load(data >> 3);
+                self.linear_reload = true;
+            }
+            _ => {}
+        }
+    }
+
+    // 定时器每个 CPU 周期计数一次, 两个计数器都不为 0 时才推进波形
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            if self.length.active() && self.linear_counter > 0 {
+                self.sequence_step = (self.sequence_step + 1) % 32;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    // 四分之一帧信号
+    pub fn clock_linear_counter(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        // 周期太短时频率超出人耳范围, 真实硬件上的输出混在一起接近 7.5, 这里直接输出中间值
+        if self.timer_period < 2 {
+            return 7;
+        }
+        TRIANGLE_SEQUENCE[self.sequence_step as usize]
+    }
+}
+
+impl SaveState for Triangle {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.control);
+        state.write_u8(self.linear_reload_value);
+        state.write_u8(self.linear_counter);
+        state.write_bool(self.linear_reload);
+        state.write_u16(self.timer_period);
+        state.write_u16(self.timer);
+        state.write_u8(self.sequence_step);
+        self.length.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.control = state.read_bool()?;
+        self.linear_reload_value = state.read_u8()?;
+        self.linear_counter = state.read_u8()?;
+        self.linear_reload = state.read_bool()?;
+        self.timer_period = state.read_u16()?;
+        self.timer = state.read_u16()?;
+        self.sequence_step = state.read_u8()?;
+        self.length.load_state(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_triangle_needs_both_counters() {
+        let mut triangle = Triangle::default();
+        triangle.length.set_enabled(true);
+        triangle.write_register(0, 0x00);
+        triangle.write_register(2, 0x10);
+        triangle.write_register(3, 0x08);
+        triangle.clock_linear_counter();
+        // 线性计数器为 0, 波形不动
+        for _ in 0..0x100 {
+            triangle.clock_timer();
+        }
+        assert_eq!(triangle.output(), 15);
+
+        triangle.write_register(0, 0x7f);
+        triangle.write_register(3, 0x08);
+        triangle.clock_linear_counter();
+        for _ in 0..=0x10 {
+            triangle.clock_timer();
+        }
+        assert_eq!(triangle.output(), 14);
+    }
+}
@@ -0,0 +1,560 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::instruction::addressing::AddressingMode;
+use crate::instruction::CPU_INSTRUCTION_BUILTIN;
+
+// 没有 .org 时程序从 0x0600 开始, 和 CPU::memory_load_program 的加载地址相同
+pub const DEFAULT_ORIGIN: u16 = 0x0600;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "asm line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+/**
+汇编的结果: 从 origin 开始的连续字节, .org 向后跳过的部分用 0 填充
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembled {
+    pub origin: u16,
+    pub bytes: Vec<u8>,
+}
+
+/*
+一个简单的两遍 6502 汇编器
+ ; 注释
+ label:              标签, 可以和指令写在同一行
+ .org $8000          设置当前地址
+ .byte 1, $02, %11   字节
+ .word label, $1234  小端的 16 位字
+ LDA #$05  LDA $10  LDA $10,X  LDX $10,Y  LDA $1234  LDA $1234,X  LDA $1234,Y
+ LDA ($10,X)  LDA ($10),Y  JMP ($1234)  ASL A  ASL  BNE label  INX
+数字可以写成 $十六进制, %二进制 或者十进制, 表达式支持 + - 以及取低字节 < 和高字节 >
+第一遍确定每条指令的长度和标签的地址, 第二遍生成字节
+ */
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    assemble_program(source).map(|program| program.bytes)
+}
+
+pub fn assemble_program(source: &str) -> Result<Assembled, AsmError> {
+    let statements = parse(source)?;
+
+    // 第一遍
+    let mut labels: HashMap<String, u16> = HashMap::new();
+    let mut address = DEFAULT_ORIGIN as u32;
+    let mut origin = None;
+    // 第一条 .org 可以把默认地址往回移, 之后地址只能向后走
+    let mut started = false;
+    let mut sized = Vec::with_capacity(statements.len());
+    for statement in statements {
+        let error = |message: String| AsmError { line: statement.line, message };
+        if let Some(label) = &statement.label {
+            if labels.insert(label.clone(), address as u16).is_some() {
+                return Err(error(format!("label `{}` defined twice", label)));
+            }
+        }
+        let size = match &statement.kind {
+            StatementKind::Org(expr) => {
+                let value = expr.eval(&labels).ok_or_else(|| error(".org needs a known address".to_string()))? as u32;
+                if started && value < address {
+                    return Err(error(format!(".org ${:04X} moves backwards", value)));
+                }
+                address = value;
+                started = true;
+                0
+            }
+            StatementKind::Bytes(values) => values.len() as u32,
+            StatementKind::Words(values) => values.len() as u32 * 2,
+            StatementKind::Instruction { mnemonic, operand } => {
+                let (code, mode) = select_opcode(mnemonic, operand, &labels).map_err(error)?;
+                sized.push(SizedInstruction { code, mode });
+                operand_size(mode) + 1
+            }
+            StatementKind::Empty => 0,
+        };
+        if size > 0 && origin.is_none() {
+            origin = Some(address as u16);
+            started = true;
+        }
+        address += size;
+        if address > 0x10000 {
+            return Err(error("program does not fit below $FFFF".to_string()));
+        }
+    }
+
+    // 第二遍
+    let statements = parse(source)?;
+    let origin = origin.unwrap_or(DEFAULT_ORIGIN);
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut address = DEFAULT_ORIGIN as u32;
+    let mut instructions = sized.into_iter();
+    for statement in statements {
+        let error = |message: String| AsmError { line: statement.line, message };
+        let eval = |expr: &Expr| expr.eval(&labels).ok_or_else(|| error(format!("undefined label in `{}`", expr.text)));
+        let mut emitted = Vec::new();
+        match &statement.kind {
+            StatementKind::Org(expr) => {
+                address = eval(expr)? as u32;
+                if address >= origin as u32 {
+                    bytes.resize(address as usize - origin as usize, 0);
+                }
+            }
+            StatementKind::Bytes(values) => {
+                for value in values {
+                    let value = eval(value)?;
+                    if value > 0xFF {
+                        return Err(error(format!("${:X} does not fit in a byte", value)));
+                    }
+                    emitted.push(value as u8);
+                }
+            }
+            StatementKind::Words(values) => {
+                for value in values {
+                    emitted.extend_from_slice(&eval(value)?.to_le_bytes());
+                }
+            }
+            StatementKind::Instruction { operand, .. } => {
+                let SizedInstruction { code, mode } = instructions.next().expect("instruction sized in the first pass");
+                emitted.push(code);
+                match (mode, operand) {
+                    (AddressingMode::NoneAddressing | AddressingMode::Accumulator, _) => {}
+                    (AddressingMode::Relative, Operand::Address(expr)) => {
+                        let target = eval(expr)? as i32;
+                        let offset = target - (address as i32 + 2);
+                        if !(-128..=127).contains(&offset) {
+                            return Err(error(format!("branch target `{}` is out of range ({})", expr.text, offset)));
+                        }
+                        emitted.push(offset as i8 as u8);
+                    }
+                    (_, Operand::Immediate(expr) | Operand::Address(expr) | Operand::IndexedX(expr) | Operand::IndexedY(expr)
+                        | Operand::Indirect(expr) | Operand::IndirectX(expr) | Operand::IndirectY(expr)) => {
+                        let value = eval(expr)?;
+                        if operand_size(mode) == 1 {
+                            if value > 0xFF {
+                                return Err(error(format!("${:X} does not fit in a byte", value)));
+                            }
+                            emitted.push(value as u8);
+                        } else {
+                            emitted.extend_from_slice(&value.to_le_bytes());
+                        }
+                    }
+                    (_, Operand::None | Operand::Accumulator) => {}
+                }
+            }
+            StatementKind::Empty => {}
+        }
+        address += emitted.len() as u32;
+        bytes.extend_from_slice(&emitted);
+    }
+    Ok(Assembled { origin, bytes })
+}
+
+struct SizedInstruction {
+    code: u8,
+    mode: AddressingMode,
+}
+
+fn operand_size(mode: AddressingMode) -> u32 {
+    match mode {
+        AddressingMode::NoneAddressing | AddressingMode::Accumulator => 0,
+        AddressingMode::Absolute | AddressingMode::Absolute_X | AddressingMode::Absolute_Y | AddressingMode::Indirect => 2,
+        _ => 1,
+    }
+}
+
+fn find_opcode(mnemonic: &str, mode: AddressingMode) -> Option<u8> {
+    CPU_INSTRUCTION_BUILTIN
+        .iter()
+        .find(|builtin| builtin.op.mnemonic == mnemonic && builtin.op.mode == mode)
+        .map(|builtin| builtin.op.code)
+}
+
+/*
+根据操作数的写法选择寻址方式, 地址能确定小于 0x100 并且有零页寻址的指令时用零页寻址
+向前引用的标签在第一遍还不知道地址, 按绝对寻址处理
+ */
+fn select_opcode(mnemonic: &str, operand: &Operand, labels: &HashMap<String, u16>) -> Result<(u8, AddressingMode), String> {
+    let zero_page = |expr: &Expr| expr.eval(labels).map(|value| value <= 0xFF).unwrap_or(false);
+    let candidates: Vec<AddressingMode> = match operand {
+        Operand::None => vec![AddressingMode::NoneAddressing, AddressingMode::Accumulator],
+        Operand::Accumulator => vec![AddressingMode::Accumulator],
+        Operand::Immediate(_) => vec![AddressingMode::Immediate],
+        Operand::Address(expr) if zero_page(expr) => {
+            vec![AddressingMode::Relative, AddressingMode::ZeroPage, AddressingMode::Absolute]
+        }
+        Operand::Address(_) => vec![AddressingMode::Relative, AddressingMode::Absolute],
+        Operand::IndexedX(expr) if zero_page(expr) => vec![AddressingMode::ZeroPage_X, AddressingMode::Absolute_X],
+        Operand::IndexedX(_) => vec![AddressingMode::Absolute_X],
+        Operand::IndexedY(expr) if zero_page(expr) => vec![AddressingMode::ZeroPage_Y, AddressingMode::Absolute_Y],
+        Operand::IndexedY(_) => vec![AddressingMode::Absolute_Y],
+        Operand::Indirect(_) => vec![AddressingMode::Indirect],
+        Operand::IndirectX(_) => vec![AddressingMode::Indirect_X],
+        Operand::IndirectY(_) => vec![AddressingMode::Indirect_Y],
+    };
+    candidates
+        .into_iter()
+        .find_map(|mode| find_opcode(mnemonic, mode).map(|code| (code, mode)))
+        .ok_or_else(|| {
+            if CPU_INSTRUCTION_BUILTIN.iter().any(|builtin| builtin.op.mnemonic == mnemonic) {
+                format!("{} does not support this addressing mode", mnemonic)
+            } else {
+                format!("unknown instruction `{}`", mnemonic)
+            }
+        })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Part {
+    Full,
+    Low,
+    High,
+}
+
+/*
+表达式: 数字和标签用 + - 连接, 前面可以加 < (低字节) 或者 > (高字节)
+ */
+#[derive(Debug, Clone)]
+struct Expr {
+    text: String,
+    part: Part,
+    terms: Vec<(i32, Term)>,
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    Number(u32),
+    Label(String),
+}
+
+impl Expr {
+    fn parse(text: &str) -> Result<Expr, String> {
+        let text = text.trim();
+        let (part, rest) = match text.strip_prefix('<') {
+            Some(rest) => (Part::Low, rest),
+            None => match text.strip_prefix('>') {
+                Some(rest) => (Part::High, rest),
+                None => (Part::Full, text),
+            },
+        };
+        let mut terms = Vec::new();
+        let mut sign = 1;
+        let mut current = String::new();
+        for c in rest.chars().chain(std::iter::once('+')) {
+            if (c == '+' || c == '-') && !current.trim().is_empty() {
+                terms.push((sign, parse_term(current.trim())?));
+                current.clear();
+                sign = if c == '-' { -1 } else { 1 };
+            } else if c == '-' && current.trim().is_empty() && terms.is_empty() {
+                return Err(format!("negative value `{}`", text));
+            } else {
+                current.push(c);
+            }
+        }
+        if terms.is_empty() {
+            return Err("missing value".to_string());
+        }
+        Ok(Expr { text: text.to_string(), part, terms })
+    }
+
+    fn eval(&self, labels: &HashMap<String, u16>) -> Option<u16> {
+        let mut value: i64 = 0;
+        for (sign, term) in &self.terms {
+            let term = match term {
+                Term::Number(n) => *n as i64,
+                Term::Label(name) => *labels.get(name)? as i64,
+            };
+            value += *sign as i64 * term;
+        }
+        let value = (value & 0xFFFF) as u16;
+        Some(match self.part {
+            Part::Full => value,
+            Part::Low => value & 0xFF,
+            Part::High => value >> 8,
+        })
+    }
+}
+
+fn parse_term(text: &str) -> Result<Term, String> {
+    let number = if let Some(hex) = text.strip_prefix('$') {
+        u32::from_str_radix(hex, 16)
+    } else if let Some(bin) = text.strip_prefix('%') {
+        u32::from_str_radix(bin, 2)
+    } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+        text.parse::<u32>()
+    } else if is_identifier(text) {
+        return Ok(Term::Label(text.to_string()));
+    } else {
+        return Err(format!("bad value `{}`", text));
+    };
+    match number {
+        Ok(n) if n <= 0xFFFF => Ok(Term::Number(n)),
+        Ok(n) => Err(format!("value `{}` ({}) is larger than $FFFF", text, n)),
+        Err(e) => Err(format!("bad number `{}`: {}", text, e)),
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    None,
+    Accumulator,
+    Immediate(Expr),
+    Address(Expr),
+    IndexedX(Expr),
+    IndexedY(Expr),
+    Indirect(Expr),
+    IndirectX(Expr),
+    IndirectY(Expr),
+}
+
+impl Operand {
+    fn parse(text: &str) -> Result<Operand, String> {
+        let text = text.trim();
+        let upper = text.to_ascii_uppercase().replace(' ', "");
+        if text.is_empty() {
+            return Ok(Operand::None);
+        }
+        if upper == "A" {
+            return Ok(Operand::Accumulator);
+        }
+        if let Some(rest) = text.strip_prefix('#') {
+            return Ok(Operand::Immediate(Expr::parse(rest)?));
+        }
+        if upper.starts_with('(') {
+            // upper 去掉了空格, 下标不能用来切 text, 在 text 本身里面找逗号
+            if upper.ends_with(",X)") {
+                let comma = text.rfind(',').unwrap_or(text.len());
+                return Ok(Operand::IndirectX(Expr::parse(&text[1..comma])?));
+            }
+            if upper.ends_with("),Y") {
+                let close = text.rfind(')').unwrap_or(text.len());
+                return Ok(Operand::IndirectY(Expr::parse(&text[1..close])?));
+            }
+            if upper.ends_with(')') {
+                return Ok(Operand::Indirect(Expr::parse(&text[1..text.len() - 1])?));
+            }
+            return Err(format!("bad indirect operand `{}`", text));
+        }
+        if let Some(comma) = text.rfind(',') {
+            let index = text[comma + 1..].trim().to_ascii_uppercase();
+            let expr = Expr::parse(&text[..comma])?;
+            return match index.as_str() {
+                "X" => Ok(Operand::IndexedX(expr)),
+                "Y" => Ok(Operand::IndexedY(expr)),
+                _ => Err(format!("bad index register `{}`", index)),
+            };
+        }
+        Ok(Operand::Address(Expr::parse(text)?))
+    }
+}
+
+#[derive(Debug, Clone)]
+enum StatementKind {
+    Empty,
+    Org(Expr),
+    Bytes(Vec<Expr>),
+    Words(Vec<Expr>),
+    Instruction { mnemonic: String, operand: Operand },
+}
+
+#[derive(Debug, Clone)]
+struct Statement {
+    line: usize,
+    label: Option<String>,
+    kind: StatementKind,
+}
+
+fn parse(source: &str) -> Result<Vec<Statement>, AsmError> {
+    let mut statements = Vec::new();
+    for (index, raw) in source.lines().enumerate() {
+        let line = index + 1;
+        let error = |message: String| AsmError { line, message };
+        let mut text = raw.split(';').next().unwrap_or("").trim();
+
+        let mut label = None;
+        if let Some(colon) = text.find(':') {
+            let name = text[..colon].trim();
+            if !is_identifier(name) {
+                return Err(error(format!("bad label `{}`", name)));
+            }
+            label = Some(name.to_string());
+            text = text[colon + 1..].trim();
+        }
+
+        let (word, rest) = match text.find(char::is_whitespace) {
+            Some(space) => (&text[..space], text[space..].trim()),
+            None => (text, ""),
+        };
+        let list = |rest: &str| -> Result<Vec<Expr>, AsmError> {
+            rest.split(',').map(|item| Expr::parse(item).map_err(error)).collect()
+        };
+        let kind = match word.to_ascii_lowercase().as_str() {
+            "" => StatementKind::Empty,
+            ".org" => StatementKind::Org(Expr::parse(rest).map_err(error)?),
+            ".byte" | ".db" => StatementKind::Bytes(list(rest)?),
+            ".word" | ".dw" => StatementKind::Words(list(rest)?),
+            directive if directive.starts_with('.') => return Err(error(format!("unknown directive `{}`", word))),
+            _ => StatementKind::Instruction {
+                mnemonic: word.to_ascii_uppercase(),
+                operand: Operand::parse(rest).map_err(error)?,
+            },
+        };
+        statements.push(Statement { line, label, kind });
+    }
+    Ok(statements)
+}
+
+/**
+在测试和演示程序中直接写汇编, 每个字符串是一行, 汇编失败时 panic
+ let program = asm!(
+     "    LDX #$08",
+     "loop:",
+     "    DEX",
+     "    BNE loop",
+     "    BRK",
+ );
+ */
+#[macro_export]
+macro_rules! asm {
+    ($($line:expr),* $(,)?) => {
+        $crate::asm::assemble(concat!($($line, "\n"),*)).unwrap_or_else(|e| panic!("{}", e))
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::CPU;
+    use crate::disasm::disassemble;
+
+    #[test]
+    fn test_all_addressing_modes() {
+        let bytes = assemble(
+            "
+            LDA #$05        ; Immediate
+            LDA $10         ; ZeroPage
+            LDA $10,X       ; ZeroPage_X
+            LDX $10,Y       ; ZeroPage_Y
+            LDA $1234       ; Absolute
+            LDA $1234,X     ; Absolute_X
+            LDA $1234,Y     ; Absolute_Y
+            LDA ($10,X)     ; Indirect_X
+            LDA ($10),Y     ; Indirect_Y
+            JMP ($1234)     ; Indirect
+            ASL A           ; Accumulator
+            LSR
+            INX             ; NoneAddressing
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            bytes,
+            vec![
+                0xa9, 0x05, 0xa5, 0x10, 0xb5, 0x10, 0xb6, 0x10, 0xad, 0x34, 0x12, 0xbd, 0x34, 0x12,
+                0xb9, 0x34, 0x12, 0xa1, 0x10, 0xb1, 0x10, 0x6c, 0x34, 0x12, 0x0a, 0x4a, 0xe8,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_indirect_operands_with_spaces() {
+        let bytes = assemble(
+            "
+            LDA ( $10 , X )
+            lda ( $20,x)
+            LDA ( $30 ) , y
+            Jmp ( $1234 )
+            ",
+        )
+        .unwrap();
+        assert_eq!(bytes, vec![0xa1, 0x10, 0xa1, 0x20, 0xb1, 0x30, 0x6c, 0x34, 0x12]);
+    }
+
+    #[test]
+    fn test_labels_and_directives() {
+        let program = assemble_program(
+            "
+            .org $8000
+            start:  LDX #<table
+                    LDY #>table
+            loop:   DEX
+                    BNE loop
+                    JSR sub
+                    JMP start
+            sub:    RTS
+            .org $8010
+            table:  .byte 1, $02, %11
+                    .word start, table+1
+            ",
+        )
+        .unwrap();
+        assert_eq!(program.origin, 0x8000);
+        assert_eq!(
+            program.bytes,
+            vec![
+                0xa2, 0x10, 0xa0, 0x80, 0xca, 0xd0, 0xfd, 0x20, 0x0d, 0x80, 0x4c, 0x00, 0x80, 0x60, 0x00, 0x00,
+                0x01, 0x02, 0x03, 0x00, 0x80, 0x11, 0x80,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_forward_reference_uses_absolute() {
+        // 向前引用的零页标签在第一遍不知道地址, 使用绝对寻址
+        let bytes = assemble(".org $0000\nLDA var\nvar: .byte 0").unwrap();
+        assert_eq!(bytes, vec![0xad, 0x03, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(assemble("NOP\nFOO #1").unwrap_err().line, 2);
+        assert!(assemble("LDX ($10),Y").is_err());
+        assert!(assemble("BNE nowhere").is_err());
+        assert!(assemble("a: NOP\na: NOP").is_err());
+        assert!(assemble("LDA #$100").is_err());
+        assert!(assemble(".org $8000\n.org $7000").is_err());
+        let far = format!("loop: {}\nBNE loop", "NOP\n".repeat(200));
+        assert!(assemble(&far).unwrap_err().message.contains("out of range"));
+    }
+
+    #[test]
+    fn test_round_trip_through_disassembler() {
+        let source = "LDA ($30),Y\nJMP ($1234)\nSTA $0200,X\nROR A\nBRK";
+        let lines = disassemble(&assemble(source).unwrap(), DEFAULT_ORIGIN);
+        let text: Vec<String> = lines.iter().map(|line| format!("{} {}", line.mnemonic, line.operand).trim().to_string()).collect();
+        assert_eq!(text, vec!["LDA ($30),Y", "JMP ($1234)", "STA $0200,X", "ROR A", "BRK"]);
+    }
+
+    #[test]
+    fn test_asm_macro_runs_on_cpu() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(crate::asm!(
+            "    LDX #$08",
+            "    LDA #$00",
+            "loop:",
+            "    CLC",
+            "    ADC #$03",
+            "    DEX",
+            "    BNE loop",
+            "    STA $10",
+            "    BRK",
+        )).unwrap();
+        assert_eq!(cpu.memory_read(0x10), 24);
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs;
+use std::process;
+
+use nes_platform::cartridge::{Cartridge, PRG_ROM_PAGE_SIZE};
+use nes_platform::cpu::CpuVariant;
+use nes_platform::disasm::{disassemble, disassemble_for, format_listing};
+use nes_platform::snake::{SNAKE_GAME_CODE, SNAKE_LOAD_ADDRESS};
+
+const USAGE: &str = "usage: disasm [options] <program.bin | rom.nes>
+       disasm --snake
+
+options:
+  --origin ADDR   address of the first byte (default 0x0600 for binaries;
+                  0x8000, or 0xC000 for the last 16KB PRG bank, for .nes files)
+  --bank N        16KB PRG bank of a .nes file to disassemble (default 0)
+  --cpu VARIANT   instruction set: 2a03 (default), 6502 or 65c02
+  --snake         disassemble the built-in snake game";
+
+fn parse_number(value: &str) -> Result<u32, String> {
+    let parsed = match value.strip_prefix("0x").or_else(|| value.strip_prefix('$')) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse::<u32>(),
+    };
+    parsed.map_err(|e| format!("bad number `{}`: {}", value, e))
+}
+
+fn run(args: Vec<String>) -> Result<String, String> {
+    let mut origin = None;
+    let mut bank = 0usize;
+    let mut variant = CpuVariant::default();
+    let mut path = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--origin" => origin = Some(u16::try_from(parse_number(&value()?)?).map_err(|e| e.to_string())?),
+            "--bank" => bank = parse_number(&value()?)? as usize,
+            "--cpu" => variant = value()?.parse()?,
+            "--snake" => return Ok(format_listing(&disassemble(&SNAKE_GAME_CODE, SNAKE_LOAD_ADDRESS))),
+            other if other.starts_with("--") => return Err(format!("unknown option {}", other)),
+            other => path = Some(other.to_string()),
+        }
+    }
+    let path = path.ok_or("missing program path")?;
+
+    let (bytes, origin) = if path.to_ascii_lowercase().ends_with(".nes") {
+        let cartridge = Cartridge::load(&path).map_err(|e| format!("{}: {}", path, e))?;
+        let banks = cartridge.prg_rom.len() / PRG_ROM_PAGE_SIZE;
+        if bank >= banks {
+            return Err(format!("{} has {} PRG banks, bank {} does not exist", path, banks, bank));
+        }
+        let start = bank * PRG_ROM_PAGE_SIZE;
+        let bytes = cartridge.prg_rom[start..start + PRG_ROM_PAGE_SIZE].to_vec();
+        // 最后一个 bank 通常固定在 0xC000, 包含中断向量
+        let default_origin = if bank == banks - 1 { 0xC000 } else { 0x8000 };
+        (bytes, origin.unwrap_or(default_origin))
+    } else {
+        let bytes = fs::read(&path).map_err(|e| format!("{}: {}", path, e))?;
+        (bytes, origin.unwrap_or(0x0600))
+    };
+    Ok(format_listing(&disassemble_for(&bytes, origin, variant)))
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    match run(args) {
+        Ok(listing) => print!("{}", listing),
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::process;
+
+use nes_platform::cartridge::Cartridge;
+use nes_platform::checksum::crc32;
+use nes_platform::cpu::CpuVariant;
+use nes_platform::headless::image::save_frame;
+use nes_platform::headless::{Headless, InputScript, RunLimit};
+use nes_platform::movie::Movie;
+
+const USAGE: &str = "usage: headless [options] <rom.nes | program.bin>
+
+options:
+  --frames N          run N frames (default 60)
+  --steps N           run N instructions instead of frames
+  --raw               treat the file as a raw 6502 program even if it ends in .nes
+  --load-addr ADDR    load address of a raw program (default 0x0600)
+  --cpu VARIANT       cpu variant: 2a03 (default), 6502 or 65c02 with its extra instructions
+  --input FILE        scripted input, one `<frame> <player> <buttons>` per line
+  --movie FILE        replay the input of a movie recorded with `--record`
+  --frame-out FILE    save the final frame (.png or .ppm)
+  --ram-out FILE      save CPU RAM (2KB for ROMs, 64KB for raw programs)
+  --trace FILE        write a nestest-style trace line before every instruction";
+
+struct Options {
+    path: String,
+    limit: RunLimit,
+    raw: bool,
+    load_addr: u16,
+    variant: CpuVariant,
+    input: Option<String>,
+    movie: Option<String>,
+    frame_out: Option<String>,
+    ram_out: Option<String>,
+    trace: Option<String>,
+}
+
+fn parse_number(value: &str) -> Result<u64, String> {
+    let parsed = match value.strip_prefix("0x").or_else(|| value.strip_prefix('$')) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse::<u64>(),
+    };
+    parsed.map_err(|e| format!("bad number `{}`: {}", value, e))
+}
+
+fn parse_args(args: Vec<String>) -> Result<Options, String> {
+    let mut options = Options {
+        path: String::new(),
+        limit: RunLimit::Frames(60),
+        raw: false,
+        load_addr: 0x0600,
+        variant: CpuVariant::default(),
+        input: None,
+        movie: None,
+        frame_out: None,
+        ram_out: None,
+        trace: None,
+    };
+    let mut path = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--frames" => options.limit = RunLimit::Frames(parse_number(&value()?)?),
+            "--steps" => options.limit = RunLimit::Steps(parse_number(&value()?)?),
+            "--raw" => options.raw = true,
+            "--load-addr" => {
+                options.load_addr = u16::try_from(parse_number(&value()?)?).map_err(|e| e.to_string())?;
+            }
+            "--cpu" => options.variant = value()?.parse()?,
+            "--input" => options.input = Some(value()?),
+            "--movie" => options.movie = Some(value()?),
+            "--frame-out" => options.frame_out = Some(value()?),
+            "--ram-out" => options.ram_out = Some(value()?),
+            "--trace" => options.trace = Some(value()?),
+            "-h" | "--help" => return Err(String::new()),
+            other if other.starts_with("--") => return Err(format!("unknown option {}", other)),
+            other => path = Some(other.to_string()),
+        }
+    }
+    options.path = path.ok_or_else(|| "missing ROM or program path".to_string())?;
+    if !options.path.to_ascii_lowercase().ends_with(".nes") {
+        options.raw = true;
+    }
+    if options.input.is_some() && options.movie.is_some() {
+        return Err("--input and --movie cannot be used together".to_string());
+    }
+    Ok(options)
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let script = match (&options.input, &options.movie) {
+        (Some(path), _) => {
+            let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            InputScript::parse(&text).map_err(|e| format!("{}: {}", path, e))?
+        }
+        (None, Some(path)) => {
+            let movie = Movie::load(path).map_err(|e| format!("{}: {}", path, e))?;
+            let rom = fs::read(&options.path).map_err(|e| format!("{}: {}", options.path, e))?;
+            if movie.rom_crc32.is_some_and(|crc| crc != crc32(&rom)) {
+                eprintln!("warning: {} was recorded with a different ROM", path);
+            }
+            InputScript::from_movie(&movie)
+        }
+        (None, None) => InputScript::default(),
+    };
+
+    let mut headless = if options.raw {
+        let program = fs::read(&options.path).map_err(|e| format!("{}: {}", options.path, e))?;
+        Headless::with_program(&program, options.load_addr).map_err(|e| format!("{}: {}", options.path, e))?
+    } else {
+        let cartridge = Cartridge::load(&options.path).map_err(|e| format!("{}: {}", options.path, e))?;
+        Headless::with_rom(cartridge).map_err(|e| format!("{}: {}", options.path, e))?
+    };
+
+    headless.cpu.variant = options.variant;
+    if let Some(path) = &options.trace {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        headless.set_trace(Box::new(BufWriter::new(file)));
+    }
+    headless.run(options.limit, &script).map_err(|e| e.to_string())?;
+    println!(
+        "frames: {} steps: {} cycles: {}{}",
+        headless.frames,
+        headless.steps,
+        headless.cpu.cycles,
+        if headless.halted { " (halted on BRK)" } else { "" }
+    );
+
+    if let Some(path) = &options.frame_out {
+        let frame = headless.frame().ok_or("raw programs have no PPU frame to save")?;
+        save_frame(path, frame).map_err(|e| format!("{}: {}", path, e))?;
+    }
+    if let Some(path) = &options.ram_out {
+        fs::write(path, headless.ram()).map_err(|e| format!("{}: {}", path, e))?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(options) => options,
+        // --help
+        Err(message) if message.is_empty() => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(message) = run(options) {
+        eprintln!("error: {}", message);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,423 @@
+use std::any::Any;
+
+use crate::cartridge::{Cartridge, CartridgeError};
+use crate::mapper::{self, Mapper};
+use crate::mapper::nrom::Nrom;
+use crate::ppu::NesPPU;
+use crate::apu::{Apu, DEFAULT_SAMPLE_RATE};
+use crate::timing::Region;
+use crate::joypad::Joypad;
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
+
+/**
+CPU 通过总线访问内存和外设, 总线负责把地址解码到具体的设备上
+https://www.nesdev.org/wiki/CPU_memory_map
+
+实现不同的 Bus 就可以让同一个 CPU 核心运行在不同的内存映射上:
+平坦的 64KB 内存见 Memory, NES 主机的内存映射见 NesBus
+总线要负责保存和恢复它上面所有设备的状态, 见 savestate
+ */
+pub trait Bus: SaveState {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, data: u8);
+
+    // 调试和跟踪用的读, 不能有副作用 (比如读 PPUSTATUS 会清除 vblank 标志)
+    fn peek(&mut self, addr: u16) -> u8 {
+        self.read(addr)
+    }
+
+    /*
+    CPU 每执行完一条指令, 把消耗的 CPU 周期数告诉总线, 总线上的设备 (PPU 等) 跟着前进
+     */
+    fn tick(&mut self, _cycles: u16) {}
+
+    // 是否有等待处理的 NMI, 读取之后清除
+    fn poll_nmi(&mut self) -> bool {
+        false
+    }
+
+    // 总线上的设备 (比如 mapper) 是否在拉低 IRQ 线
+    fn irq_pending(&self) -> bool {
+        false
+    }
+
+    // DMA 之类的操作会让 CPU 暂停, 返回需要额外消耗的 CPU 周期数, 读取之后清除
+    fn take_stall_cycles(&mut self) -> u16 {
+        0
+    }
+
+    // 前端需要拿到具体的总线类型 (比如从 NesBus 里取出画面)
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/*
+ _______________ $10000  _______________
+| PRG-ROM       |       |               |
+| Upper Bank    |       |               |
+|_ _ _ _ _ _ _ _| $C000 | PRG-ROM       |
+| PRG-ROM       |       |               |
+| Lower Bank    |       |               |
+|_______________| $8000 |_______________|
+| SRAM          |       | SRAM          |
+|_______________| $6000 |_______________|
+| Expansion ROM |       | Expansion ROM |
+|_______________| $4020 |_______________|
+| I/O Registers |       |               |
+|_ _ _ _ _ _ _ _| $4000 |               |
+| Mirrors       |       | I/O Registers |
+| $2000-$2007   |       |               |
+|_ _ _ _ _ _ _ _| $2008 |               |
+| I/O Registers |       |               |
+|_______________| $2000 |_______________|
+| Mirrors       |       |               |
+| $0000-$07FF   |       |               |
+|_ _ _ _ _ _ _ _| $0800 |               |
+| RAM           |       | RAM           |
+|_ _ _ _ _ _ _ _| $0200 |               |
+| Stack         |       |               |
+|_ _ _ _ _ _ _ _| $0100 |               |
+| Zero Page     |       |               |
+|_______________| $0000 |_______________|
+ */
+const RAM: u16 = 0x0000;
+const RAM_MIRRORS_END: u16 = 0x1FFF;
+const PPU_REGISTERS: u16 = 0x2000;
+const PPU_REGISTERS_MIRRORS_END: u16 = 0x3FFF;
+const APU_IO_REGISTERS: u16 = 0x4000;
+const APU_IO_REGISTERS_END: u16 = 0x401F;
+const APU_REGISTERS_END: u16 = 0x4013;
+const OAM_DMA: u16 = 0x4014;
+const APU_STATUS: u16 = 0x4015;
+const JOYPAD_1: u16 = 0x4016;
+// 读是手柄 2, 写是 APU 的帧计数器
+const JOYPAD_2: u16 = 0x4017;
+const APU_FRAME_COUNTER: u16 = 0x4017;
+const CARTRIDGE_SPACE: u16 = 0x4020;
+const PRG_RAM: u16 = 0x6000;
+
+/**
+NES 主机的 CPU 总线
+ */
+pub struct NesBus {
+    // 主机内部只有 2KB 内存, 0x0000-0x1FFF 是它的 4 份镜像
+    cpu_vram: [u8; 0x0800],
+    // PPU 的 8 个寄存器, 在 0x2000-0x3FFF 之间每 8 个字节镜像一次
+    pub ppu: NesPPU,
+    pub apu: Apu,
+    // 两个玩家的手柄
+    pub joypads: [Joypad; 2],
+    // 0x4018-0x401F 是 CPU 测试模式用的寄存器
+    apu_io_registers: [u8; 0x20],
+    // 卡带上的 mapper, 负责 0x6000-0xFFFF 的 PRG-RAM 和 PRG-ROM
+    mapper: Box<dyn Mapper>,
+    // 执行 OAM DMA 之后 CPU 需要暂停的周期数
+    stall_cycles: u16,
+    // 总线经过的 CPU 周期数, 用来判断 DMA 开始时是奇数周期还是偶数周期
+    cycles: u64,
+}
+
+impl NesBus {
+    pub fn new(cartridge: Cartridge) -> Result<Self, CartridgeError> {
+        Ok(NesBus::with_mapper(mapper::create(cartridge)?))
+    }
+
+    pub fn with_mapper(mapper: Box<dyn Mapper>) -> Self {
+        NesBus {
+            cpu_vram: [0; 0x0800],
+            ppu: NesPPU::new(),
+            apu: Apu::new(Region::Ntsc.cpu_clock_hz(), DEFAULT_SAMPLE_RATE),
+            joypads: [Joypad::new(), Joypad::new()],
+            apu_io_registers: [0; 0x20],
+            mapper,
+            stall_cycles: 0,
+            cycles: 0,
+        }
+    }
+
+    /*
+    写 0x4014 会把 CPU 内存的 0xXX00-0xXXFF 这一页复制到 OAM 中,
+    复制期间 CPU 暂停 513 个周期, 如果在奇数周期开始还要再多等 1 个周期
+     */
+    fn oam_dma(&mut self, page: u8) {
+        let start = (page as u16) << 8;
+        for offset in 0..256u16 {
+            let data = self.read(start + offset);
+            self.ppu.write_oam_data(data);
+        }
+        self.stall_cycles += 513 + (self.cycles % 2) as u16;
+    }
+}
+
+impl Default for NesBus {
+    // 没有插卡带的主机
+    fn default() -> Self {
+        NesBus::with_mapper(Box::new(Nrom::new(Cartridge::default())))
+    }
+}
+
+impl Bus for NesBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        match addr {
+            RAM..=RAM_MIRRORS_END => {
+                // 只保留低 11 位
+                let mirror_down_addr = addr & 0b0000_0111_1111_1111;
+                self.cpu_vram[mirror_down_addr as usize]
+            }
+            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END => {
+                let mirror_down_addr = addr & 0b0000_0000_0000_0111;
+                self.ppu.read_register(mirror_down_addr, &mut *self.mapper)
+            }
+            APU_STATUS => self.apu.read_status(),
+            JOYPAD_1 => self.joypads[0].read(),
+            JOYPAD_2 => self.joypads[1].read(),
+            APU_IO_REGISTERS..=APU_IO_REGISTERS_END => {
+                self.apu_io_registers[(addr - APU_IO_REGISTERS) as usize]
+            }
+            PRG_RAM..=0xFFFF => self.mapper.cpu_read(addr),
+            // 扩展区, 大部分卡带没有使用
+            CARTRIDGE_SPACE..=0x5FFF => 0,
+        }
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        match addr {
+            RAM..=RAM_MIRRORS_END => {
+                let mirror_down_addr = addr & 0b0000_0111_1111_1111;
+                self.cpu_vram[mirror_down_addr as usize] = data;
+            }
+            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END => {
+                let mirror_down_addr = addr & 0b0000_0000_0000_0111;
+                self.ppu.write_register(mirror_down_addr, data, &mut *self.mapper);
+            }
+            OAM_DMA => self.oam_dma(data),
+            // 两个手柄共用同一条 strobe 线
+            JOYPAD_1 => {
+                for joypad in self.joypads.iter_mut() {
+                    joypad.write(data);
+                }
+            }
+            APU_IO_REGISTERS..=APU_REGISTERS_END | APU_STATUS | APU_FRAME_COUNTER => {
+                self.apu.write_register(addr, data);
+            }
+            APU_IO_REGISTERS..=APU_IO_REGISTERS_END => {
+                self.apu_io_registers[(addr - APU_IO_REGISTERS) as usize] = data;
+            }
+            // 写 ROM 的地址通常是在写 mapper 的寄存器
+            PRG_RAM..=0xFFFF => self.mapper.cpu_write(addr, data),
+            CARTRIDGE_SPACE..=0x5FFF => {}
+        }
+    }
+
+    fn peek(&mut self, addr: u16) -> u8 {
+        match addr {
+            RAM..=RAM_MIRRORS_END => self.cpu_vram[(addr & 0b0000_0111_1111_1111) as usize],
+            // 寄存器的读都有副作用, 返回最后一次写入 PPU 的值
+            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END => self.ppu.io_latch,
+            APU_IO_REGISTERS..=APU_IO_REGISTERS_END => 0,
+            PRG_RAM..=0xFFFF => self.mapper.cpu_read(addr),
+            CARTRIDGE_SPACE..=0x5FFF => 0,
+        }
+    }
+
+    fn tick(&mut self, cycles: u16) {
+        self.cycles += cycles as u64;
+        // PPU 的时钟是 CPU 的 3 倍
+        self.ppu.tick(cycles * 3, &mut *self.mapper);
+        self.apu.tick(cycles, &*self.mapper);
+        self.stall_cycles += self.apu.take_stall_cycles();
+    }
+
+    fn poll_nmi(&mut self) -> bool {
+        self.ppu.poll_nmi_interrupt()
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.mapper.irq_pending() || self.apu.irq_pending()
+    }
+
+    fn take_stall_cycles(&mut self) -> u16 {
+        let stall = self.stall_cycles;
+        self.stall_cycles = 0;
+        stall
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl SaveState for NesBus {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_tag(b"BUS ");
+        state.write_bytes(&self.cpu_vram);
+        state.write_bytes(&self.apu_io_registers);
+        state.write_u16(self.stall_cycles);
+        state.write_u64(self.cycles);
+        self.ppu.save_state(state);
+        self.apu.save_state(state);
+        for joypad in &self.joypads {
+            joypad.save_state(state);
+        }
+        self.mapper.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.read_tag(b"BUS ")?;
+        state.read_bytes(&mut self.cpu_vram)?;
+        state.read_bytes(&mut self.apu_io_registers)?;
+        self.stall_cycles = state.read_u16()?;
+        self.cycles = state.read_u64()?;
+        self.ppu.load_state(state)?;
+        self.apu.load_state(state)?;
+        for joypad in &mut self.joypads {
+            joypad.load_state(state)?;
+        }
+        self.mapper.load_state(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::test::ines_rom;
+    use crate::cpu::CPU;
+    use crate::joypad::JoypadButton;
+
+    #[test]
+    fn test_ram_mirroring() {
+        let mut bus = NesBus::default();
+        bus.write(0x0012, 0x34);
+        assert_eq!(bus.read(0x0812), 0x34);
+        assert_eq!(bus.read(0x1012), 0x34);
+        bus.write(0x1fff, 0x56);
+        assert_eq!(bus.read(0x07ff), 0x56);
+    }
+
+    #[test]
+    fn test_ppu_register_mirroring() {
+        let mut bus = NesBus::default();
+        // 0x3ffe 是 PPUADDR 的镜像, 0x200f 是 PPUDATA 的镜像
+        bus.write(0x3ffe, 0x23);
+        bus.write(0x3ffe, 0x05);
+        bus.write(0x200f, 0x78);
+        assert_eq!(bus.ppu.v, 0x2306);
+        bus.write(0x2006, 0x23);
+        bus.write(0x2006, 0x05);
+        bus.read(0x2007);
+        assert_eq!(bus.read(0x3fff), 0x78);
+    }
+
+    #[test]
+    fn test_oam_dma() {
+        let mut bus = NesBus::default();
+        for i in 0..256u16 {
+            bus.write(0x0200 + i, i as u8);
+        }
+        bus.write(0x2003, 0x10);
+        bus.write(0x4014, 0x02);
+        assert_eq!(bus.ppu.oam_data[0x10], 0x00);
+        assert_eq!(bus.ppu.oam_data[0x0f], 0xff);
+        assert_eq!(bus.take_stall_cycles(), 513);
+        assert_eq!(bus.take_stall_cycles(), 0);
+    }
+
+    #[test]
+    fn test_peek_has_no_side_effects() {
+        let mut bus = NesBus::default();
+        bus.ppu.status.insert(crate::ppu::registers::StatusRegister::VBLANK_STARTED);
+        bus.write(0x0001, 0x42);
+        assert_eq!(bus.peek(0x0801), 0x42);
+        bus.peek(0x2002);
+        assert_eq!(bus.read(0x2002) & 0x80, 0x80);
+    }
+
+    #[test]
+    fn test_joypads() {
+        let mut bus = NesBus::default();
+        bus.joypads[0].set_button_pressed_status(JoypadButton::BUTTON_A, true);
+        bus.joypads[1].set_button_pressed_status(JoypadButton::BUTTON_B, true);
+        bus.write(0x4016, 1);
+        bus.write(0x4016, 0);
+        assert_eq!(bus.read(0x4016), 1);
+        assert_eq!(bus.read(0x4016), 0);
+        assert_eq!(bus.read(0x4017), 0);
+        assert_eq!(bus.read(0x4017), 1);
+        // 写 0x4017 是帧计数器, 不影响手柄
+        bus.write(0x4017, 1);
+        assert_eq!(bus.read(0x4017), 0);
+    }
+
+    #[test]
+    fn test_apu_registers() {
+        let mut bus = NesBus::default();
+        bus.write(0x4015, 0b0000_0100);
+        bus.write(0x400b, 0b0000_1000);
+        assert_eq!(bus.read(0x4015), 0b0000_0100);
+        // 4 步模式的帧中断通过总线传给 CPU
+        assert!(!bus.irq_pending());
+        for _ in 0..30 {
+            bus.tick(1000);
+        }
+        assert!(bus.irq_pending());
+        bus.write(0x4017, 0b0100_0000);
+        assert!(!bus.irq_pending());
+    }
+
+    #[test]
+    fn test_vblank_nmi() {
+        let mut bus = NesBus::default();
+        bus.write(0x2000, 0b1000_0000);
+        // 241 条扫描线大约是 27394 个 CPU 周期
+        for _ in 0..27000 / 100 {
+            bus.tick(100);
+        }
+        assert!(!bus.poll_nmi());
+        bus.tick(500);
+        assert!(bus.poll_nmi());
+        assert!(!bus.poll_nmi());
+        assert_eq!(bus.read(0x2002) & 0x80, 0x80);
+        assert_eq!(bus.read(0x2002) & 0x80, 0);
+    }
+
+    #[test]
+    fn test_prg_rom_16k_is_mirrored() {
+        let mut raw = ines_rom(0, 0, 1, 1);
+        raw[16] = 0x11;
+        raw[16 + 0x3ffc] = 0x00;
+        raw[16 + 0x3ffd] = 0x80;
+        let mut bus = NesBus::new(Cartridge::from_bytes(&raw).unwrap()).unwrap();
+        assert_eq!(bus.read(0x8000), 0x11);
+        assert_eq!(bus.read(0xc000), 0x11);
+        assert_eq!(bus.read(0xfffc), 0x00);
+        assert_eq!(bus.read(0xfffd), 0x80);
+        // ROM 不可写
+        bus.write(0x8000, 0x22);
+        assert_eq!(bus.read(0x8000), 0x11);
+    }
+
+    #[test]
+    fn test_prg_ram() {
+        let mut bus = NesBus::new(Cartridge::from_bytes(&ines_rom(0, 0, 2, 1)).unwrap()).unwrap();
+        bus.write(0x6000, 0x33);
+        bus.write(0x7fff, 0x44);
+        assert_eq!(bus.read(0x6000), 0x33);
+        assert_eq!(bus.read(0x7fff), 0x44);
+    }
+
+    #[test]
+    fn test_cpu_starts_at_reset_vector() {
+        let mut raw = ines_rom(0, 0, 2, 1);
+        // 0x8000: LDA #$42; BRK
+        raw[16] = 0xa9;
+        raw[17] = 0x42;
+        raw[18] = 0x00;
+        raw[16 + 0x7ffc] = 0x00;
+        raw[16 + 0x7ffd] = 0x80;
+        let mut cpu = CPU::with_bus(Box::new(NesBus::new(Cartridge::from_bytes(&raw).unwrap()).unwrap()));
+        cpu.reset();
+        cpu.halt_on_brk = true;
+        assert_eq!(cpu.program_counter, 0x8000);
+        cpu.interpret().unwrap();
+        assert_eq!(cpu.register_a, 0x42);
+    }
+}
@@ -0,0 +1,364 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
+
+/**
+.nes 文件的格式 https://www.nesdev.org/wiki/INES 和 https://www.nesdev.org/wiki/NES_2.0
+
+ 0-3  "NES" 加上 MS-DOS 的文件结束符 0x1A
+ 4    PRG-ROM 大小, 以 16KB 为单位
+ 5    CHR-ROM 大小, 以 8KB 为单位, 0 表示卡带使用 CHR-RAM
+ 6    Flags 6
+      76543210
+      ||||||||
+      |||||||+- 命名表镜像: 0 水平镜像, 1 垂直镜像
+      ||||||+-- 1: 卡带带有电池供电的 PRG-RAM (0x6000-0x7FFF)
+      |||||+--- 1: PRG-ROM 之前有 512 字节的 trainer
+      ||||+---- 1: 忽略镜像设置, 使用四屏 VRAM
+      ++++----- mapper 编号的低 4 位
+ 7    Flags 7
+      76543210
+      ||||||||
+      ||||++--- 等于 0b10 表示 NES 2.0 格式
+      ++++----- mapper 编号的高 4 位
+ 8-15 iNES 中大部分没有使用, NES 2.0 中用来扩展 mapper 编号和各种存储器的大小
+ */
+const NES_TAG: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+pub const PRG_ROM_PAGE_SIZE: usize = 16 * 1024;
+pub const CHR_ROM_PAGE_SIZE: usize = 8 * 1024;
+const PRG_RAM_PAGE_SIZE: usize = 8 * 1024;
+
+/**
+命名表镜像方式, 决定 PPU 的 4 个逻辑命名表如何映射到 2KB 的 VRAM 上
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    FourScreen,
+    // 单屏镜像只能由 mapper 在运行时设置(比如 MMC1)
+    SingleScreenLower,
+    SingleScreenUpper,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomFormat {
+    INes,
+    Nes2,
+}
+
+#[derive(Debug)]
+pub enum CartridgeError {
+    Io(std::io::Error),
+    // 文件比 16 字节的文件头还短
+    MissingHeader,
+    // 文件头不是以 "NES\x1A" 开头
+    InvalidTag,
+    // 文件头中声明的大小无法表示
+    InvalidSize(&'static str),
+    // 文件头声明的数据比实际文件长
+    Truncated { expected: usize, actual: usize },
+    // 模拟器还不支持的 mapper
+    UnsupportedMapper(u16),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CartridgeError::Io(err) => write!(f, "failed to read rom: {}", err),
+            CartridgeError::MissingHeader => write!(f, "rom is shorter than the {} byte header", HEADER_SIZE),
+            CartridgeError::InvalidTag => write!(f, "rom is not in iNES or NES 2.0 format"),
+            CartridgeError::InvalidSize(what) => write!(f, "invalid {} size in rom header", what),
+            CartridgeError::Truncated { expected, actual } => {
+                write!(f, "rom is truncated: header declares {} bytes but file has {}", expected, actual)
+            }
+            CartridgeError::UnsupportedMapper(mapper) => write!(f, "mapper {} is not supported", mapper),
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CartridgeError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for CartridgeError {
+    fn from(err: std::io::Error) -> Self {
+        CartridgeError::Io(err)
+    }
+}
+
+/**
+解析之后的卡带
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cartridge {
+    pub format: RomFormat,
+    pub prg_rom: Vec<u8>,
+    // CHR-ROM 为空时卡带使用 CHR-RAM, 大小见 chr_ram_size
+    pub chr_rom: Vec<u8>,
+    pub trainer: Option<Vec<u8>>,
+    pub mapper: u16,
+    // 只有 NES 2.0 才有子 mapper 编号
+    pub submapper: u8,
+    pub mirroring: Mirroring,
+    // 是否有电池供电的存储器(存档)
+    pub battery: bool,
+    pub prg_ram_size: usize,
+    pub chr_ram_size: usize,
+}
+
+impl Default for Cartridge {
+    // 空卡带, 只有 PRG-RAM 和 CHR-RAM
+    fn default() -> Self {
+        Cartridge {
+            format: RomFormat::INes,
+            prg_rom: Vec::new(),
+            chr_rom: Vec::new(),
+            trainer: None,
+            mapper: 0,
+            submapper: 0,
+            mirroring: Mirroring::Horizontal,
+            battery: false,
+            prg_ram_size: PRG_RAM_PAGE_SIZE,
+            chr_ram_size: CHR_ROM_PAGE_SIZE,
+        }
+    }
+}
+
+impl Cartridge {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Cartridge, CartridgeError> {
+        let raw = fs::read(path)?;
+        Cartridge::from_bytes(&raw)
+    }
+
+    pub fn from_bytes(raw: &[u8]) -> Result<Cartridge, CartridgeError> {
+        if raw.len() < HEADER_SIZE {
+            return Err(CartridgeError::MissingHeader);
+        }
+        if raw[0..4] != NES_TAG {
+            return Err(CartridgeError::InvalidTag);
+        }
+
+        let flags6 = raw[6];
+        let flags7 = raw[7];
+        let format = if flags7 & 0b0000_1100 == 0b0000_1000 {
+            RomFormat::Nes2
+        } else {
+            RomFormat::INes
+        };
+
+        let four_screen = flags6 & 0b1000 != 0;
+        let mirroring = match (four_screen, flags6 & 0b1 != 0) {
+            (true, _) => Mirroring::FourScreen,
+            (false, true) => Mirroring::Vertical,
+            (false, false) => Mirroring::Horizontal,
+        };
+        let battery = flags6 & 0b10 != 0;
+        let has_trainer = flags6 & 0b100 != 0;
+
+        let (mapper, submapper, prg_rom_size, chr_rom_size, prg_ram_size, chr_ram_size) = match format {
+            RomFormat::Nes2 => {
+                let mapper = (flags6 >> 4) as u16 | (flags7 & 0xF0) as u16 | ((raw[8] & 0x0F) as u16) << 8;
+                let submapper = raw[8] >> 4;
+                let prg_rom_size = nes2_rom_size(raw[4], raw[9] & 0x0F, PRG_ROM_PAGE_SIZE)
+                    .ok_or(CartridgeError::InvalidSize("PRG-ROM"))?;
+                let chr_rom_size = nes2_rom_size(raw[5], raw[9] >> 4, CHR_ROM_PAGE_SIZE)
+                    .ok_or(CartridgeError::InvalidSize("CHR-ROM"))?;
+                // 易失和非易失(电池)的 PRG-RAM 都映射在 0x6000-0x7FFF
+                let prg_ram_size = nes2_ram_size(raw[10] & 0x0F) + nes2_ram_size(raw[10] >> 4);
+                let chr_ram_size = nes2_ram_size(raw[11] & 0x0F) + nes2_ram_size(raw[11] >> 4);
+                (mapper, submapper, prg_rom_size, chr_rom_size, prg_ram_size, chr_ram_size)
+            }
+            RomFormat::INes => {
+                // 一些老的工具会在 7-15 字节写入 "DiskDude!" 之类的垃圾数据, 这种情况下 mapper 的高 4 位不可信
+                let upper = if raw[12..16].iter().any(|b| *b != 0) { 0 } else { flags7 & 0xF0 };
+                let mapper = (flags6 >> 4 | upper) as u16;
+                let prg_rom_size = raw[4] as usize * PRG_ROM_PAGE_SIZE;
+                let chr_rom_size = raw[5] as usize * CHR_ROM_PAGE_SIZE;
+                // 为了兼容, 0 也表示 8KB
+                let prg_ram_size = (raw[8].max(1)) as usize * PRG_RAM_PAGE_SIZE;
+                let chr_ram_size = if chr_rom_size == 0 { CHR_ROM_PAGE_SIZE } else { 0 };
+                (mapper, 0, prg_rom_size, chr_rom_size, prg_ram_size, chr_ram_size)
+            }
+        };
+
+        if prg_rom_size == 0 {
+            return Err(CartridgeError::InvalidSize("PRG-ROM"));
+        }
+
+        let trainer_size = if has_trainer { TRAINER_SIZE } else { 0 };
+        let prg_rom_start = HEADER_SIZE + trainer_size;
+        // NES 2.0 的指数表示法可以写出接近 usize::MAX 的大小, 相加时不能溢出
+        let chr_rom_start = prg_rom_start.checked_add(prg_rom_size).ok_or(CartridgeError::InvalidSize("PRG-ROM"))?;
+        let expected = chr_rom_start.checked_add(chr_rom_size).ok_or(CartridgeError::InvalidSize("CHR-ROM"))?;
+        if raw.len() < expected {
+            return Err(CartridgeError::Truncated { expected, actual: raw.len() });
+        }
+
+        Ok(Cartridge {
+            format,
+            prg_rom: raw[prg_rom_start..chr_rom_start].to_vec(),
+            chr_rom: raw[chr_rom_start..expected].to_vec(),
+            trainer: if has_trainer { Some(raw[HEADER_SIZE..prg_rom_start].to_vec()) } else { None },
+            mapper,
+            submapper,
+            mirroring,
+            battery,
+            prg_ram_size,
+            chr_ram_size,
+        })
+    }
+}
+
+/*
+NES 2.0 的 ROM 大小: 高 4 位不是 0xF 时, 大小为 (msb << 8 | lsb) 个页
+高 4 位是 0xF 时使用指数表示法, lsb 的格式为 EEEEEEMM, 大小为 2^E * (MM*2+1) 字节
+ */
+fn nes2_rom_size(lsb: u8, msb: u8, page_size: usize) -> Option<usize> {
+    if msb == 0x0F {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = (lsb & 0b11) as usize * 2 + 1;
+        1usize.checked_shl(exponent)?.checked_mul(multiplier)
+    } else {
+        Some(((msb as usize) << 8 | lsb as usize) * page_size)
+    }
+}
+
+/*
+NES 2.0 的 RAM 大小: 0 表示没有, 否则为 64 << n 字节
+ */
+fn nes2_ram_size(shift: u8) -> usize {
+    if shift == 0 {
+        0
+    } else {
+        64 << shift
+    }
+}
+
+impl SaveState for Mirroring {
+    fn save_state(&self, state: &mut StateWriter) {
+        let value = match self {
+            Mirroring::Horizontal => 0,
+            Mirroring::Vertical => 1,
+            Mirroring::FourScreen => 2,
+            Mirroring::SingleScreenLower => 3,
+            Mirroring::SingleScreenUpper => 4,
+        };
+        state.write_u8(value);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        *self = match state.read_u8()? {
+            0 => Mirroring::Horizontal,
+            1 => Mirroring::Vertical,
+            2 => Mirroring::FourScreen,
+            3 => Mirroring::SingleScreenLower,
+            4 => Mirroring::SingleScreenUpper,
+            value => return Err(StateError::Mismatch(format!("unknown mirroring {}", value))),
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    pub fn ines_rom(flags6: u8, flags7: u8, prg_pages: u8, chr_pages: u8) -> Vec<u8> {
+        let mut raw = vec![0x4E, 0x45, 0x53, 0x1A, prg_pages, chr_pages, flags6, flags7, 0, 0, 0, 0, 0, 0, 0, 0];
+        if flags6 & 0b100 != 0 {
+            raw.extend(vec![0x77; TRAINER_SIZE]);
+        }
+        raw.extend(vec![0x01; prg_pages as usize * PRG_ROM_PAGE_SIZE]);
+        raw.extend(vec![0x02; chr_pages as usize * CHR_ROM_PAGE_SIZE]);
+        raw
+    }
+
+    #[test]
+    fn test_ines_header() {
+        let cartridge = Cartridge::from_bytes(&ines_rom(0b0001_0011, 0b0100_0000, 2, 1)).unwrap();
+        assert_eq!(cartridge.format, RomFormat::INes);
+        assert_eq!(cartridge.mapper, 0x41);
+        assert_eq!(cartridge.mirroring, Mirroring::Vertical);
+        assert!(cartridge.battery);
+        assert_eq!(cartridge.trainer, None);
+        assert_eq!(cartridge.prg_rom.len(), 2 * PRG_ROM_PAGE_SIZE);
+        assert_eq!(cartridge.chr_rom.len(), CHR_ROM_PAGE_SIZE);
+        assert_eq!(cartridge.chr_ram_size, 0);
+        assert_eq!(cartridge.prg_ram_size, PRG_RAM_PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_ines_trainer_and_chr_ram() {
+        let cartridge = Cartridge::from_bytes(&ines_rom(0b0000_1100, 0, 1, 0)).unwrap();
+        assert_eq!(cartridge.mirroring, Mirroring::FourScreen);
+        assert_eq!(cartridge.trainer, Some(vec![0x77; TRAINER_SIZE]));
+        assert!(cartridge.prg_rom.iter().all(|b| *b == 0x01));
+        assert_eq!(cartridge.chr_ram_size, CHR_ROM_PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_nes2_header() {
+        let mut raw = ines_rom(0b0001_0000, 0b0000_1000, 1, 0);
+        // mapper 高 4 位 = 1, 子 mapper = 3
+        raw[8] = 0x31;
+        // PRG-RAM 8KB, 电池 PRG-RAM 8KB
+        raw[10] = 0x77;
+        // CHR-RAM 32KB
+        raw[11] = 0x09;
+        let cartridge = Cartridge::from_bytes(&raw).unwrap();
+        assert_eq!(cartridge.format, RomFormat::Nes2);
+        assert_eq!(cartridge.mapper, 0x101);
+        assert_eq!(cartridge.submapper, 3);
+        assert_eq!(cartridge.prg_ram_size, 16 * 1024);
+        assert_eq!(cartridge.chr_ram_size, 32 * 1024);
+    }
+
+    #[test]
+    fn test_nes2_exponent_size() {
+        assert_eq!(nes2_rom_size(0b0000_1101, 0x0F, PRG_ROM_PAGE_SIZE), Some(8 * 3));
+        assert_eq!(nes2_rom_size(0x02, 0x01, PRG_ROM_PAGE_SIZE), Some(0x102 * PRG_ROM_PAGE_SIZE));
+    }
+
+    #[test]
+    fn test_nes2_huge_exponent_sizes() {
+        // PRG-ROM 和 CHR-ROM 都是 2^63 字节, 加起来超出 usize
+        let mut raw = ines_rom(0, 0b0000_1000, 0, 0);
+        raw[4] = 0b1111_1100;
+        raw[5] = 0b1111_1100;
+        raw[9] = 0xFF;
+        assert!(matches!(Cartridge::from_bytes(&raw), Err(CartridgeError::InvalidSize("CHR-ROM"))));
+
+        // 2^62 * 3 各自都放得进 usize, 加起来就溢出了
+        raw[4] = 0b1111_1001;
+        raw[5] = 0b1111_1001;
+        assert!(matches!(Cartridge::from_bytes(&raw), Err(CartridgeError::InvalidSize("CHR-ROM"))));
+    }
+
+    #[test]
+    fn test_malformed_headers() {
+        assert!(matches!(Cartridge::from_bytes(&[0x4E, 0x45, 0x53]), Err(CartridgeError::MissingHeader)));
+
+        let mut raw = ines_rom(0, 0, 1, 1);
+        raw[3] = 0x00;
+        assert!(matches!(Cartridge::from_bytes(&raw), Err(CartridgeError::InvalidTag)));
+
+        let mut raw = ines_rom(0, 0, 1, 1);
+        raw.truncate(HEADER_SIZE + 100);
+        assert!(matches!(
+            Cartridge::from_bytes(&raw),
+            Err(CartridgeError::Truncated { expected, actual }) if expected == HEADER_SIZE + PRG_ROM_PAGE_SIZE + CHR_ROM_PAGE_SIZE && actual == HEADER_SIZE + 100
+        ));
+
+        let raw = ines_rom(0, 0, 0, 1);
+        assert!(matches!(Cartridge::from_bytes(&raw), Err(CartridgeError::InvalidSize("PRG-ROM"))));
+    }
+}
@@ -1,10 +1,15 @@
 use std::collections::HashMap;
+use std::fmt;
 
 use bitflags::bitflags;
 
-use crate::instruction::{CPU_INSTRUCTION_BUILTIN_MAP, InstructionBuiltin};
+use crate::instruction::{instruction_set, InstructionBuiltin};
 use crate::instruction::addressing::AddressingMode;
-use crate::memory::Memory;
+use crate::bus::Bus;
+use crate::interrupt::{self, Interrupt};
+use crate::memory::{program_end, Memory, ProgramTooLarge};
+use crate::debugger::watch::{Access, Watchpoints};
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
 
 const PROGRAM_START_ADDRESS: u16 = 0x0600;
 
@@ -37,6 +42,122 @@ bitflags! {
 
 }
 
+/**
+run_with_callback 的回调函数在每条指令执行之前被调用, 通过返回值告诉 CPU 是继续执行还是停下来
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunControl {
+    Continue,
+    Stop,
+}
+
+/**
+CPU::step 的返回值, 记录刚刚执行完的那一条指令
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepResult {
+    // 指令所在的地址
+    pub program_counter: u16,
+    pub opcode: u8,
+    pub mnemonic: &'static str,
+    // 通过 get_operand_address 解析出的操作数地址, 隐含寻址和累加器寻址没有地址
+    pub operand_address: Option<u16>,
+    // 这条指令消耗的周期数
+    pub cycles: u8,
+    // 是否因为 halt_on_brk 在 BRK 处停机
+    pub brk: bool,
+}
+
+/**
+CPU 无法继续执行时 step 返回的错误, 出错时程序计数器停在出错的指令上, 宿主可以报告错误或者修改状态之后继续
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CpuError {
+    // 指令表中没有这个操作码, 比如没有打开 unofficial-opcodes 特性时的非官方指令
+    UnknownOpcode { opcode: u8, program_counter: u16 },
+    // 执行了 KIL (JAM) 操作码, 真实的 CPU 会卡死直到复位
+    Jammed { opcode: u8, program_counter: u16 },
+    // 打开 stack_checks 时, 栈指针从 0x00 压栈回绕到 0xFF
+    StackOverflow { program_counter: u16 },
+    // 打开 stack_checks 时, 栈指针从 0xFF 出栈回绕到 0x00
+    StackUnderflow { program_counter: u16 },
+    // 累计周期数达到了 cycle_limit
+    BudgetExceeded { limit: u64 },
+    // load_and_run 的程序放不进内存
+    ProgramTooLarge(ProgramTooLarge),
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuError::UnknownOpcode { opcode, program_counter } => {
+                write!(f, "opcode {:02X} at {:04X} is not recognized", opcode, program_counter)
+            }
+            CpuError::Jammed { opcode, program_counter } => {
+                write!(f, "cpu jammed by opcode {:02X} at {:04X}", opcode, program_counter)
+            }
+            CpuError::StackOverflow { program_counter } => write!(f, "stack overflow at {:04X}", program_counter),
+            CpuError::StackUnderflow { program_counter } => write!(f, "stack underflow at {:04X}", program_counter),
+            CpuError::BudgetExceeded { limit } => write!(f, "execution budget of {} cycles exceeded", limit),
+            CpuError::ProgramTooLarge(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+impl From<ProgramTooLarge> for CpuError {
+    fn from(e: ProgramTooLarge) -> Self {
+        CpuError::ProgramTooLarge(e)
+    }
+}
+
+/**
+CPU 的型号, 同一个核心可以用在 NES 之外的 6502 项目里
+ NES 的 2A03 去掉了十进制模式, D 标志可以设置但是 ADC/SBC 总是做二进制运算
+ NMOS 6502 和 65C02 在 D 标志置位时按 BCD 运算, 两者的标志位有差别, 65C02 还要多消耗一个周期
+http://www.6502.org/tutorials/decimal_mode.html
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CpuVariant {
+    #[default]
+    Nes2A03,
+    Nmos6502,
+    Cmos65C02,
+}
+
+impl CpuVariant {
+    pub fn has_decimal_mode(self) -> bool {
+        self != CpuVariant::Nes2A03
+    }
+}
+
+impl std::str::FromStr for CpuVariant {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "2a03" | "nes" => Ok(CpuVariant::Nes2A03),
+            "6502" | "nmos" => Ok(CpuVariant::Nmos6502),
+            "65c02" | "cmos" => Ok(CpuVariant::Cmos65C02),
+            _ => Err(format!("unknown cpu variant `{}`, expected 2a03, 6502 or 65c02", name)),
+        }
+    }
+}
+
+// NMOS 6502 上让 CPU 卡死的操作码
+const JAM_OPCODES: [u8; 12] = [0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2];
+// 65C02 的 STP, 停机直到复位
+const STP: u8 = 0xDB;
+
+fn jams(variant: CpuVariant, opcode: u8) -> bool {
+    match variant {
+        CpuVariant::Cmos65C02 => opcode == STP,
+        CpuVariant::Nes2A03 | CpuVariant::Nmos6502 => JAM_OPCODES.contains(&opcode),
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
 pub struct CPU {
     // 负载累加器
     pub register_a: u8,
@@ -47,13 +168,33 @@ pub struct CPU {
     // 这个变量可能是一个字节大小的整数，其中的每个位对应一个特定的标志位。通过将特定的位设置为 1 或 0，可以表示相应的标志位状态
     // 通过按位或操作 | 和按位与操作 &，可以根据需要设置或取消设置特定的标志位，而不需要使用多个单独的变量
     pub status: CPUFlags,
-    pub memory: Memory,
+    // CPU 通过总线读写内存, 具体的地址映射由 Bus 的实现决定
+    pub bus: Box<dyn Bus>,
     pub stack_pointer: u8,
     // 这个相当于指令寄存器
     pub program_counter: u16,
+    // 从上电开始累计消耗的时钟周期数
+    pub cycles: u64,
+    // NMI 是边沿触发的, 触发之后一直挂起直到被响应
+    pub nmi_pending: bool,
+    // IRQ 是电平触发的, 只要线被拉低并且没有屏蔽中断就会一直进入中断
+    pub irq_line: bool,
+    // 把 BRK 当作停机指令, 而不是软件中断, 用于贪吃蛇和测试程序
+    pub halt_on_brk: bool,
+    pub variant: CpuVariant,
+    // 调试器设置的内存监视点, 没有监视点时不影响内存访问
+    pub watchpoints: Watchpoints,
+    // 累计周期数达到这个值之后 step 返回 CpuError::BudgetExceeded, 防止测试程序死循环
+    pub cycle_limit: Option<u64>,
+    // 检查栈指针回绕, 正常的程序不会让栈溢出, 默认关闭
+    pub stack_checks: bool,
+    // 指令执行过程中发现的错误, 在 step 结束时返回
+    fault: Option<CpuError>,
+    // 这条指令是否通过 jump 修改了程序计数器, 修改过就不再跳过操作数
+    jumped: bool,
 }
 
-/**
+/*
 https://www.nesdev.org/obelisk-6502-guide/reference.html
 ADC - Add with Carry
     加进位,该指令将存储单元的内容与进位位一起添加到累加器中。如果发生溢出，则设置进位位，这使得能够执行多字节加法。
@@ -103,6 +244,12 @@ SBC - Subtract with Carry
  */
 
 
+impl Default for CPU {
+    fn default() -> Self {
+        CPU::new()
+    }
+}
+
 /**
 CPU以恒定的周期工作：
 从指令存储器中取出下一条执行指令
@@ -112,80 +259,88 @@ CPU以恒定的周期工作：
  */
 impl CPU {
     pub fn new() -> Self {
-        CPU { register_a: 0, register_x: 0, register_y: 0, status: CPUFlags::from_bits_truncate(0b0010_0100), memory: Memory::default(), stack_pointer: STACK_RESET, program_counter: 0 }
+        CPU::with_bus(Box::<Memory>::default())
     }
-    pub fn interpret(&mut self) {
-        let ref builtins: HashMap<u8, &'static InstructionBuiltin> = *CPU_INSTRUCTION_BUILTIN_MAP;
 
-        loop {
-            let ops_code = self.offset_program();
-            let builtin = builtins.get(&ops_code).expect(&format!("Opcode {:x} is not recognized", ops_code));
-            match ops_code {
-                // BRK
-                0x00 => {
-                    return;
-                }
-                _ => {
-                    (builtin.execute)(self, &builtin.op.mode);
-                }
-            }
+    pub fn with_variant(bus: Box<dyn Bus>, variant: CpuVariant) -> Self {
+        CPU { variant, ..CPU::with_bus(bus) }
+    }
 
-            // 操作数偏移
-            self.program_counter += builtin.op.operand_len as u16;
-        }
+    pub fn with_bus(bus: Box<dyn Bus>) -> Self {
+        CPU { register_a: 0, register_x: 0, register_y: 0, status: CPUFlags::from_bits_truncate(0b0010_0100), bus, stack_pointer: STACK_RESET, program_counter: 0, cycles: 0, nmi_pending: false, irq_line: false, halt_on_brk: false, variant: CpuVariant::default(), watchpoints: Watchpoints::default(), cycle_limit: None, stack_checks: false, fault: None, jumped: false }
+    }
+    pub fn interpret(&mut self) -> Result<(), CpuError> {
+        self.run_with_callback(|_| RunControl::Continue)
     }
 
     /*
     通过寻址方式获取到操作数的内存地址,不负责修改程序段偏移
      */
     pub fn get_operand_address(&mut self, addressing_mode: &AddressingMode) -> u16 {
-        return match addressing_mode {
+        self.resolve_operand_address(addressing_mode, CPU::memory_read)
+    }
+
+    /*
+    和 get_operand_address 一样解析操作数地址, 但是所有的读取都通过 Bus::peek,
+    不会触发观察点, 也不会产生读寄存器的副作用, 给单步执行的记录和调试器使用
+     */
+    pub fn peek_operand_address(&mut self, addressing_mode: &AddressingMode) -> u16 {
+        self.resolve_operand_address(addressing_mode, |cpu, addr| cpu.bus.peek(addr))
+    }
+
+    fn resolve_operand_address(&mut self, addressing_mode: &AddressingMode, read: fn(&mut CPU, u16) -> u8) -> u16 {
+        let read_u16 = |cpu: &mut CPU, addr: u16| {
+            let lo = read(cpu, addr) as u16;
+            let hi = read(cpu, addr.wrapping_add(1)) as u16;
+            (hi << 8) | lo
+        };
+        let pc = self.program_counter;
+        match addressing_mode {
             /*
             Immediate: 立即寻址模式。操作数直接包含在指令中，例如：LDA #10，表示将值10加载到累加器（Accumulator）寄存器中。
             操作数地址为指令的下一个字节
              */
-            AddressingMode::Immediate => { self.program_counter }
+            AddressingMode::Immediate => { pc }
             /*
             ZeroPage: 零页寻址模式。操作数的地址位于零页（地址范围为0x0000-0x00FF）内，只需一个字节来表示地址。例如：LDA $45，表示将地址为0x45的内存单元的值加载到累加器寄存器中。
              */
-            AddressingMode::ZeroPage => { self.memory_read(self.program_counter) as u16 }
+            AddressingMode::ZeroPage => { read(self, pc) as u16 }
             /*
             ZeroPage_X: 零页X变址寻址模式。操作数的地址为零页内的一个字节，而X寄存器的值会被加到这个地址上。例如：LDX $25,X，表示将地址为0x25+X的内存单元的值加载到X寄存器中。
              */
             AddressingMode::ZeroPage_X => {
-                let pos = self.memory_read(self.program_counter);
-                let addr = pos.wrapping_add(self.register_x) as u16;
-                addr
+                let pos = read(self, pc);
+                pos.wrapping_add(self.register_x) as u16
             }
             /*
             ZeroPage_Y: 零页Y变址寻址模式。操作数的地址为零页内的一个字节，而Y寄存器的值会被加到这个地址上。例如：LDY $30,Y，表示将地址为0x30+Y的内存单元的值加载到Y寄存器中。
              */
-            AddressingMode::ZeroPage_Y => { self.memory_read(self.program_counter).wrapping_add(self.register_y) as u16 }
+            AddressingMode::ZeroPage_Y => { read(self, pc).wrapping_add(self.register_y) as u16 }
             /*
             Absolute: 绝对寻址模式。操作数的地址通过一个完整的地址表示。例如：LDA $2000，表示将地址为0x2000的内存单元的值加载到累加器寄存器中。
             操作数地址为指令的下两个字节
              */
-            AddressingMode::Absolute => { self.memory_read_u16(self.program_counter) }
+            AddressingMode::Absolute => { read_u16(self, pc) }
             /*
             Absolute_X: 绝对X变址寻址模式。操作数的地址为一个完整的地址，而X寄存器的值会被加到这个地址上。例如：STA $3000,X，表示将累加器寄存器的值存储到地址为0x3000+X的内存单元中。
              */
-            AddressingMode::Absolute_X => { self.memory_read_u16(self.program_counter).wrapping_add(self.register_x as u16) }
+            AddressingMode::Absolute_X => { read_u16(self, pc).wrapping_add(self.register_x as u16) }
             /*
             绝对Y变址寻址模式。操作数的地址为一个完整的地址，而Y寄存器的值会被加到这个地址上。例如：STA $4000,Y，表示将累加器寄存器的值存储到地址为0x4000+Y的内存单元中。
              */
-            AddressingMode::Absolute_Y => { self.memory_read_u16(self.program_counter).wrapping_add(self.register_y as u16) }
+            AddressingMode::Absolute_Y => { read_u16(self, pc).wrapping_add(self.register_y as u16) }
             /*
             间接X变址寻址模式。操作数的地址通过一个间接寻址的方式计算得到。首先，将一个字节与X寄存器相加得到一个地址，然后使用这个地址作为间接寻址的目标地址。
             例如：JMP ($20,X)，表示通过将0x20+X得到的地址所指向的内存单元中的值作为新的指令地址，实现间接跳转。
              */
             AddressingMode::Indirect_X => {
-                let base = self.memory_read(self.program_counter);
+                let base = read(self, pc);
                 let ptr = base.wrapping_add(self.register_x);
                 // 获取低位字节
-                let lo = self.memory_read(ptr as u16);
+                let lo = read(self, ptr as u16);
                 // +1 偏移到下一个字节所在位置
                 // 获取高位字节
-                let hi = self.memory_read(ptr.wrapping_add(1) as u16);
+                let hi = read(self, ptr.wrapping_add(1) as u16);
                 (hi as u16) << 8 | lo as u16
             }
             /*
@@ -193,27 +348,171 @@ impl CPU {
             例如：STA ($30),Y，表示将累加器寄存器的值存储到以地址0x30为间接寻址目标，再加上Y寄存器的值得到的最终地址所指向的内存单元中。
              */
             AddressingMode::Indirect_Y => {
-                let base = self.memory_read(self.program_counter);
+                let base = read(self, pc);
                 // 这个地方涉及到进位,所以不能直接用read_u16
-                let lo = self.memory_read(base as u16);
-                let hi = self.memory_read(base.wrapping_add(1) as u16);
+                let lo = read(self, base as u16);
+                let hi = read(self, base.wrapping_add(1) as u16);
                 let deref_base = (hi as u16) << 8 | lo as u16;
-                let deref = deref_base.wrapping_add(self.register_y as u16);
-                deref
+                deref_base.wrapping_add(self.register_y as u16)
+            }
+            /*
+            相对寻址模式。操作数是一个有符号的偏移量,跳转目标为下一条指令的地址加上偏移量。
+            例如：BNE $FB，表示条件成立时跳转到 (下一条指令地址 - 5) 的位置。
+             */
+            AddressingMode::Relative => {
+                let offset = read(self, pc) as i8;
+                // +1 跳过操作数本身,得到下一条指令的地址
+                pc.wrapping_add(1).wrapping_add(offset as u16)
+            }
+            /*
+            间接寻址模式。操作数是一个指针,指针指向的两个字节才是目标地址。例如：JMP ($1234)。
+            6502 有一个硬件bug: 如果指针的低字节是0xFF(比如 $30FF), 高字节会从同一页的开头($3000)读取,而不是下一页($3100)。
+            65C02 修正了这个 bug
+             */
+            AddressingMode::Indirect => {
+                let ptr = read_u16(self, pc);
+                if ptr & 0x00ff == 0x00ff && self.variant != CpuVariant::Cmos65C02 {
+                    let lo = read(self, ptr);
+                    let hi = read(self, ptr & 0xff00);
+                    (hi as u16) << 8 | lo as u16
+                } else {
+                    read_u16(self, ptr)
+                }
+            }
+            /*
+            65C02 的零页间接寻址模式。和 Indirect_Y 一样从零页取指针, 但是不加 Y。例如：LDA ($30)。
+             */
+            AddressingMode::Indirect_ZeroPage => {
+                let base = read(self, pc);
+                let lo = read(self, base as u16);
+                let hi = read(self, base.wrapping_add(1) as u16);
+                (hi as u16) << 8 | lo as u16
+            }
+            /*
+            65C02 的绝对变址间接寻址模式, 只用于 JMP ($1234,X): 指针的地址是操作数加上 X
+             */
+            AddressingMode::Indirect_Absolute_X => {
+                let ptr = read_u16(self, pc).wrapping_add(self.register_x as u16);
+                read_u16(self, ptr)
             }
             /*
+            65C02 的 BBR/BBS: 返回要测试的零页地址, 第二个操作数字节的偏移量由 branch_zero_page_relative 处理
+             */
+            AddressingMode::ZeroPage_Relative => { read(self, pc) as u16 }
+            /*
             无寻址模式。表示该指令没有操作数，或者操作数不需要通过寻址方式获取。
+            累加器寻址的操作数是累加器本身,同样没有内存地址。
              */
-            AddressingMode::NoneAddressing => {
+            AddressingMode::Accumulator | AddressingMode::NoneAddressing => {
                 panic!("mode {:?} is not supported", addressing_mode);
             }
+        }
+    }
+
+    /*
+    读内存的指令(LDA/ADC/CMP...)使用这个方法获取操作数地址:
+    Absolute_X / Absolute_Y / Indirect_Y 寻址时, 如果加上变址寄存器之后跨越了页(高字节发生变化), 需要多消耗一个周期
+    写内存和读-改-写的指令无论是否跨页都固定消耗最多的周期, 已经包含在 OpCode::cycles 中
+     */
+    pub fn get_operand_address_for_read(&mut self, addressing_mode: &AddressingMode) -> u16 {
+        let address = self.get_operand_address(addressing_mode);
+        let index = match addressing_mode {
+            AddressingMode::Absolute_X => Some(self.register_x),
+            AddressingMode::Absolute_Y | AddressingMode::Indirect_Y => Some(self.register_y),
+            _ => None,
         };
+        if let Some(index) = index {
+            let base = address.wrapping_sub(index as u16);
+            if base & 0xff00 != address & 0xff00 {
+                self.cycles += 1;
+            }
+        }
+        address
     }
 
     fn offset_program(&mut self) -> u8 {
         let ops_code = self.memory_read(self.program_counter);
         self.program_counter += 1;
-        return ops_code;
+        ops_code
+    }
+
+    /*
+    ADC: 十进制模式下按 BCD 相加, 否则做二进制加法
+    NMOS 6502 的 N 和 V 来自高位调整之前的中间结果, Z 来自二进制加法的结果, 65C02 的 N 和 Z 来自最终结果
+     */
+    pub fn add_with_carry(&mut self, data: u8) {
+        if !self.decimal_mode() {
+            self.add_to_register_a_address(data);
+            return;
+        }
+        let a = self.register_a;
+        let carry = self.carry_bit();
+        let mut low = (a & 0x0f) + (data & 0x0f) + carry;
+        if low >= 0x0a {
+            low = ((low + 0x06) & 0x0f) + 0x10;
+        }
+        let sum = (a & 0xf0) as u16 + (data & 0xf0) as u16 + low as u16;
+        let signed = (a & 0xf0) as i8 as i16 + (data & 0xf0) as i8 as i16 + low as i16;
+        let result = if sum >= 0xa0 { sum + 0x60 } else { sum };
+
+        self.set_carry_flag_from(result >= 0x100);
+        if !(-128..=127).contains(&signed) {
+            self.set_overflow_flag();
+        } else {
+            self.clear_overflow_flag();
+        }
+        self.register_a = result as u8;
+        if self.variant == CpuVariant::Cmos65C02 {
+            self.update_zero_and_negative_flags(self.register_a);
+            self.cycles += 1;
+        } else {
+            self.update_zero_and_negative_flags(a.wrapping_add(data).wrapping_add(carry));
+            if sum & 0x80 != 0 {
+                self.set_negative_flag();
+            } else {
+                self.clear_negative_flag();
+            }
+        }
+    }
+
+    /*
+    SBC: 进位和溢出标志 (NMOS 6502 上还有 N 和 Z) 总是和二进制减法一样, 十进制模式下只是累加器的结果不同
+     */
+    pub fn subtract_with_carry(&mut self, data: u8) {
+        let a = self.register_a;
+        let borrow = 1 - self.carry_bit() as i16;
+        self.add_to_register_a_address(data.wrapping_neg().wrapping_sub(1));
+        if !self.decimal_mode() {
+            return;
+        }
+        let mut low = (a & 0x0f) as i16 - (data & 0x0f) as i16 - borrow;
+        let mut result;
+        if self.variant == CpuVariant::Cmos65C02 {
+            result = a as i16 - data as i16 - borrow;
+            if result < 0 {
+                result -= 0x60;
+            }
+            if low < 0 {
+                result -= 0x06;
+            }
+        } else {
+            if low < 0 {
+                low = ((low - 0x06) & 0x0f) - 0x10;
+            }
+            result = (a & 0xf0) as i16 - (data & 0xf0) as i16 + low;
+            if result < 0 {
+                result -= 0x60;
+            }
+        }
+        self.register_a = result as u8;
+        if self.variant == CpuVariant::Cmos65C02 {
+            self.update_zero_and_negative_flags(self.register_a);
+            self.cycles += 1;
+        }
+    }
+
+    fn decimal_mode(&self) -> bool {
+        self.variant.has_decimal_mode() && self.status.contains(CPUFlags::DECIMAL_MODE)
     }
 
     pub fn add_to_register_a_address(&mut self, data: u8) {
@@ -256,6 +555,61 @@ impl CPU {
         self.set_register_a(result);
     }
 
+    /*
+    比较指令(CMP/CPX/CPY)的公共逻辑: 用寄存器的值减去内存的值,只设置标志位,不保存结果
+     */
+    pub fn compare(&mut self, addressing_mode: &AddressingMode, register: u8) {
+        let address = self.get_operand_address_for_read(addressing_mode);
+        let data = self.memory_read(address);
+        if register >= data {
+            self.set_carry_flag();
+        } else {
+            self.clear_carry_flag();
+        }
+        self.update_zero_and_negative_flags(register.wrapping_sub(data));
+    }
+
+    /*
+    分支指令的公共逻辑: 条件成立时跳转到相对寻址得到的地址
+     */
+    pub fn branch(&mut self, condition: bool) {
+        if condition {
+            // 分支成立多消耗一个周期, 跳转到另一页再多消耗一个周期
+            let next = self.program_counter.wrapping_add(1);
+            let target = self.get_operand_address(&AddressingMode::Relative);
+            self.cycles += 1;
+            if next & 0xff00 != target & 0xff00 {
+                self.cycles += 1;
+            }
+            self.jump(target);
+        }
+    }
+
+    /*
+    BBR/BBS 的分支: 第一个操作数字节是零页地址, 第二个字节才是偏移量, 周期数的计算和普通分支一样
+     */
+    pub fn branch_zero_page_relative(&mut self, condition: bool) {
+        if condition {
+            let next = self.program_counter.wrapping_add(2);
+            let offset = self.memory_read(self.program_counter.wrapping_add(1)) as i8;
+            let target = next.wrapping_add(offset as u16);
+            self.cycles += 1;
+            if next & 0xff00 != target & 0xff00 {
+                self.cycles += 1;
+            }
+            self.jump(target);
+        }
+    }
+
+    /*
+    跳转类指令 (JMP/JSR/RTS/RTI/BRK 和分支) 通过这个方法修改程序计数器
+    不能用执行前后程序计数器是否相同来判断, 跳转目标可能刚好就是操作数的地址, 比如 BNE $FF
+     */
+    pub fn jump(&mut self, target: u16) {
+        self.program_counter = target;
+        self.jumped = true;
+    }
+
     pub fn update_zero_and_negative_flags(&mut self, result: u8) {
         // 必须根据结果设置或取消设置 CPU 标志状态。
         if result == 0 {
@@ -280,6 +634,19 @@ impl CPU {
         self.status.remove(CPUFlags::CARRY);
     }
 
+    pub fn set_carry_flag_from(&mut self, carry: bool) {
+        if carry {
+            self.set_carry_flag();
+        } else {
+            self.clear_carry_flag();
+        }
+    }
+
+    // 进位标志的值(0或1), 供移位指令使用
+    pub fn carry_bit(&self) -> u8 {
+        self.status.bits() & CPUFlags::CARRY.bits()
+    }
+
     pub fn set_zero_flag(&mut self) {
         self.status.insert(CPUFlags::ZERO);
     }
@@ -323,12 +690,15 @@ impl CPU {
 }
 
 impl CPU {
-    pub fn memory_read(&self, addr: u16) -> u8 {
-        return self.memory.read(addr);
+    pub fn memory_read(&mut self, addr: u16) -> u8 {
+        let data = self.bus.read(addr);
+        self.watchpoints.check(addr, data, Access::Read);
+        data
     }
 
     pub fn memory_write(&mut self, addr: u16, data: u8) {
-        self.memory.write(addr, data);
+        self.watchpoints.check(addr, data, Access::Write);
+        self.bus.write(addr, data);
     }
 
     /*
@@ -336,7 +706,7 @@ impl CPU {
     */
     pub fn memory_read_u16(&mut self, pos: u16) -> u16 {
         let lo = self.memory_read(pos) as u16;
-        let hi = self.memory_read(pos + 1) as u16;
+        let hi = self.memory_read(pos.wrapping_add(1)) as u16;
         (hi << 8) | lo
     }
 
@@ -347,17 +717,21 @@ impl CPU {
         let hi = (data >> 8) as u8;
         let lo = (data & 0xff) as u8;
         self.memory_write(pos, lo);
-        self.memory_write(pos + 1, hi);
+        self.memory_write(pos.wrapping_add(1), hi);
     }
 
     /*
     load 方法应将程序加载到 PRG ROM 空间并将代码引用保存到 0xFFFC 存储单元中
     */
-    pub fn memory_load_program(&mut self, program: Vec<u8>) {
-        self.memory.load_program(PROGRAM_START_ADDRESS, program);
+    pub fn memory_load_program(&mut self, program: Vec<u8>) -> Result<(), ProgramTooLarge> {
+        program_end(PROGRAM_START_ADDRESS, program.len())?;
+        for (i, data) in program.into_iter().enumerate() {
+            self.memory_write(PROGRAM_START_ADDRESS + i as u16, data);
+        }
         // 设置指令寄存器为程序的起始地址
         // self.program_counter = PROGRAM_START_ADDRESS;
         self.memory_write_u16(0xFFFC, PROGRAM_START_ADDRESS);
+        Ok(())
     }
 }
 
@@ -370,33 +744,158 @@ impl CPU {
         self.register_x = 0;
         self.status = CPUFlags::from_bits_truncate(0b0010_0100);
         self.program_counter = self.memory_read_u16(0xFFFC);
+        // 复位过程本身需要7个周期
+        self.cycles = 7;
     }
 
-    pub fn run_with_callback<F>(&mut self, mut callback: F)
-        where F: FnMut(&mut CPU) {
-        todo!();
-/*                let ref opcodes: HashMap<u8, &'static OpCode> = *CPU_INSTRUCTION_BUILTIN_MAP;
-                loop {
-                    callback(self);
-                    // match code {  }
-                }*/
+    /*
+    取指 -> 译码 -> 执行 的主循环, 每条指令执行之前都会调用一次回调函数
+    回调函数返回 RunControl::Stop 时循环结束, 遇到 BRK 同样结束, 指令出错时返回错误
+     */
+    pub fn run_with_callback<F>(&mut self, mut callback: F) -> Result<(), CpuError>
+        where F: FnMut(&mut CPU) -> RunControl {
+        loop {
+            if callback(self) == RunControl::Stop {
+                return Ok(());
+            }
+            if self.step()?.brk {
+                return Ok(());
+            }
+        }
+    }
+
+    /*
+    单步执行: 取指 -> 译码 -> 执行 恰好一条指令, 并返回这条指令的执行记录
+     */
+    pub fn step(&mut self) -> Result<StepResult, CpuError> {
+        let builtins: &HashMap<u8, &'static InstructionBuiltin> = instruction_set(self.variant);
+        if let Some(limit) = self.cycle_limit {
+            if self.cycles >= limit {
+                return Err(CpuError::BudgetExceeded { limit });
+            }
+        }
+
+        let tick_start = self.cycles;
+        // PPU 在上一条指令期间进入了 vblank, 先响应中断再取下一条指令
+        if self.bus.poll_nmi() {
+            self.nmi_pending = true;
+        }
+        if self.nmi_pending {
+            self.nmi_pending = false;
+            self.interrupt(&interrupt::NMI);
+            self.cycles += interrupt::NMI.cpu_cycles as u64;
+        } else if (self.irq_line || self.bus.irq_pending()) && !self.status.contains(CPUFlags::INTERRUPT_DISABLE) {
+            self.interrupt(&interrupt::IRQ);
+            self.cycles += interrupt::IRQ.cpu_cycles as u64;
+        }
+
+        let program_counter = self.program_counter;
+        let ops_code = self.offset_program();
+        let Some(builtin) = builtins.get(&ops_code) else {
+            // 不执行这条指令, 程序计数器停在它上面
+            self.program_counter = program_counter;
+            return Err(if jams(self.variant, ops_code) {
+                CpuError::Jammed { opcode: ops_code, program_counter }
+            } else {
+                CpuError::UnknownOpcode { opcode: ops_code, program_counter }
+            });
+        };
+        let operand_address = match builtin.op.mode {
+            AddressingMode::Accumulator | AddressingMode::NoneAddressing => None,
+            // 只用 peek 计算, 指令执行时会自己再解析一次, 不能让总线读两次
+            _ => Some(self.peek_operand_address(&builtin.op.mode)),
+        };
+        let start_cycles = self.cycles;
+        self.cycles += builtin.op.cycles as u64;
+        let mut result = StepResult {
+            program_counter,
+            opcode: ops_code,
+            mnemonic: builtin.op.mnemonic,
+            operand_address,
+            cycles: 0,
+            brk: false,
+        };
+
+        match ops_code {
+            0x00 if self.halt_on_brk => {
+                result.brk = true;
+            }
+            _ => {
+                self.jumped = false;
+                (builtin.execute)(self, &builtin.op.mode);
+                // 跳转类指令会自己修改程序计数器,此时不再做操作数偏移
+                if !self.jumped {
+                    // 操作数偏移
+                    self.program_counter += builtin.op.operand_len as u16;
+                }
+            }
+        }
+        // 基础周期数加上跨页和分支的额外周期
+        result.cycles = (self.cycles - start_cycles) as u8;
+        // OAM DMA 期间 CPU 暂停
+        self.cycles += self.bus.take_stall_cycles() as u64;
+        self.bus.tick((self.cycles - tick_start) as u16);
+        // 栈溢出时指令已经执行完, 栈指针已经回绕, 报告的是这条指令的地址
+        match self.fault.take() {
+            None => Ok(result),
+            Some(CpuError::StackOverflow { .. }) => Err(CpuError::StackOverflow { program_counter }),
+            Some(CpuError::StackUnderflow { .. }) => Err(CpuError::StackUnderflow { program_counter }),
+            Some(fault) => Err(fault),
+        }
+    }
+
+    /*
+    进入中断: 把程序计数器和状态寄存器压栈, 屏蔽 IRQ, 跳转到中断向量中的地址
+    压栈的状态寄存器中 B 标志由中断类型决定, CPU 内部的状态寄存器并没有 B 标志
+     */
+    pub fn interrupt(&mut self, interrupt: &Interrupt) {
+        self.stack_push_u16(self.program_counter);
+        let mut flag = self.status;
+        flag.remove(CPUFlags::BREAK | CPUFlags::BREAK2);
+        flag.insert(CPUFlags::from_bits_truncate(interrupt.b_flag_mask));
+        self.stack_push(flag.bits());
+        self.status.insert(CPUFlags::INTERRUPT_DISABLE);
+        // 65C02 进入中断时清除十进制标志, NMOS 6502 保持不变
+        if self.variant == CpuVariant::Cmos65C02 {
+            self.status.remove(CPUFlags::DECIMAL_MODE);
+        }
+        let vector = self.memory_read_u16(interrupt.vector_addr);
+        self.jump(vector);
+    }
+
+    // 外部设备请求 NMI, 在下一条指令之前响应
+    pub fn trigger_nmi(&mut self) {
+        self.nmi_pending = true;
     }
 
+    /*
+    取出具体类型的总线, 比如前端从 NesBus 中读取 PPU 的画面
+     */
+    pub fn bus_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.bus.as_any_mut().downcast_mut::<T>()
+    }
 
-    pub fn load_and_run(&mut self, program: Vec<u8>) {
-        self.memory_load_program(program);
+    pub fn load_and_run(&mut self, program: Vec<u8>) -> Result<(), CpuError> {
+        self.halt_on_brk = true;
+        self.memory_load_program(program)?;
         self.reset();
-        self.interpret();
+        self.interpret()
     }
 }
 
 impl CPU {
     pub fn stack_pop(&mut self) -> u8 {
+        if self.stack_checks && self.stack_pointer == 0xff {
+            self.fault = Some(CpuError::StackUnderflow { program_counter: self.program_counter });
+        }
         self.stack_pointer = self.stack_pointer.wrapping_add(1);
-        return self.memory_read(STACK + self.stack_pointer as u16);
+        self.memory_read(STACK + self.stack_pointer as u16)
     }
 
     pub fn stack_push(&mut self, data: u8) {
+        if self.stack_checks && self.stack_pointer == 0x00 {
+            self.fault = Some(CpuError::StackOverflow { program_counter: self.program_counter });
+        }
         // 从高地址往低地址写
         self.memory_write(STACK + self.stack_pointer as u16, data);
         self.stack_pointer = self.stack_pointer.wrapping_sub(1);
@@ -405,7 +904,7 @@ impl CPU {
     pub fn stack_pop_u16(&mut self) -> u16 {
         let lo = self.stack_pop() as u16;
         let hi = self.stack_pop() as u16;
-        return (hi << 8) | lo;
+        (hi << 8) | lo
     }
     pub fn stack_push_u16(&mut self, data: u16) {
         let hi = (data >> 8) as u8;
@@ -415,30 +914,64 @@ impl CPU {
     }
 }
 
+/*
+halt_on_brk 和监视点是运行方式的设置, 不属于机器的状态
+ */
+impl SaveState for CPU {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_tag(b"CPU ");
+        state.write_u8(self.register_a);
+        state.write_u8(self.register_x);
+        state.write_u8(self.register_y);
+        state.write_u8(self.status.bits());
+        state.write_u8(self.stack_pointer);
+        state.write_u16(self.program_counter);
+        state.write_u64(self.cycles);
+        state.write_bool(self.nmi_pending);
+        state.write_bool(self.irq_line);
+        self.bus.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.read_tag(b"CPU ")?;
+        self.register_a = state.read_u8()?;
+        self.register_x = state.read_u8()?;
+        self.register_y = state.read_u8()?;
+        self.status = CPUFlags::from_bits_truncate(state.read_u8()?);
+        self.stack_pointer = state.read_u8()?;
+        self.program_counter = state.read_u16()?;
+        self.cycles = state.read_u64()?;
+        self.nmi_pending = state.read_bool()?;
+        self.irq_line = state.read_bool()?;
+        self.bus.load_state(state)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::bus::NesBus;
 
     #[test]
     fn test_lda_immediate_load_data() {
         let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0x05, 0x00]);
+        cpu.load_and_run(vec![0xa9, 0x05, 0x00]).unwrap();
         assert_eq!(cpu.register_a, 0x05);
-        assert_eq!(cpu.status.contains(CPUFlags::ZERO), false);
-        assert_eq!(cpu.status.contains(CPUFlags::NEGATIV), false);
+        assert!(!cpu.status.contains(CPUFlags::ZERO));
+        assert!(!cpu.status.contains(CPUFlags::NEGATIV));
     }
 
     #[test]
     fn test_lda_zero_flag() {
         let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0x00, 0x00]);
-        assert_eq!(cpu.status.contains(CPUFlags::ZERO), true);
+        cpu.load_and_run(vec![0xa9, 0x00, 0x00]).unwrap();
+        assert!(cpu.status.contains(CPUFlags::ZERO));
     }
 
     #[test]
     fn test_sta_immediate() {
         let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0x01, 0x85, 0xff, 0x00]);
+        cpu.load_and_run(vec![0xa9, 0x01, 0x85, 0xff, 0x00]).unwrap();
         assert_eq!(cpu.memory_read(0xff), 0x01);
     }
 
@@ -451,7 +984,7 @@ mod test {
         0x9d 0x00 0xff  将负载累加器的值复制到0xff10的内存位置
         */
         // 多字节操作数要按小端顺序写入                         // 这两个操作数要按小端顺序写入
-        cpu.load_and_run(vec![0xa9, 0x10, 0xaa, 0x9d, 0x00, 0xff, 0x00]);
+        cpu.load_and_run(vec![0xa9, 0x10, 0xaa, 0x9d, 0x00, 0xff, 0x00]).unwrap();
 
         assert_eq!(cpu.memory_read(0xff10), 0x10);
     }
@@ -460,33 +993,685 @@ mod test {
     fn test_inx_overflow() {
         // 越界测试
         let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0xff, 0xaa, 0xe8, 0x00]);
+        cpu.load_and_run(vec![0xa9, 0xff, 0xaa, 0xe8, 0x00]).unwrap();
         assert_eq!(cpu.register_x, 0);
-        assert_eq!(cpu.status.contains(CPUFlags::ZERO), true);
+        assert!(cpu.status.contains(CPUFlags::ZERO));
     }
 
     #[test]
     fn test_5_ops_working_together() {
         let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00]);
+        cpu.load_and_run(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00]).unwrap();
 
         assert_eq!(cpu.register_x, 0xc1)
     }
 
+    #[test]
+    fn test_instruction_table_covers_official_opcodes() {
+        let official = crate::instruction::CPU_INSTRUCTION_BUILTIN_MAP.values().filter(|builtin| builtin.op.official).count();
+        assert_eq!(official, 151);
+        let unofficial = if cfg!(feature = "unofficial-opcodes") { 85 } else { 0 };
+        assert_eq!(crate::instruction::CPU_INSTRUCTION_BUILTIN_MAP.len(), 151 + unofficial);
+    }
+
+    #[cfg(feature = "unofficial-opcodes")]
+    #[test]
+    fn test_unofficial_load_store_and_read_modify_write() {
+        let mut cpu = CPU::new();
+        // LDA #$33; LDX #$0F; SAX $10; LAX $10; DCP $10; ISB $10; BRK
+        cpu.load_and_run(vec![0xa9, 0x33, 0xa2, 0x0f, 0x87, 0x10, 0xa7, 0x10, 0xc7, 0x10, 0xe7, 0x10, 0x00]).unwrap();
+        assert_eq!(cpu.memory_read(0x10), 0x03);
+        assert_eq!(cpu.register_x, 0x03);
+        assert_eq!(cpu.register_a, 0x00);
+        assert!(cpu.status.contains(CPUFlags::ZERO));
+        assert!(cpu.status.contains(CPUFlags::CARRY));
+
+        let mut cpu = CPU::new();
+        // LDA #$01; STA $20; SLO $20; RLA $20; SRE $20; RRA $20; BRK
+        cpu.load_and_run(vec![0xa9, 0x01, 0x85, 0x20, 0x07, 0x20, 0x27, 0x20, 0x47, 0x20, 0x67, 0x20, 0x00]).unwrap();
+        assert_eq!(cpu.memory_read(0x20), 0x01);
+        assert_eq!(cpu.register_a, 0x03);
+        assert!(!cpu.status.contains(CPUFlags::CARRY));
+    }
+
+    #[cfg(feature = "unofficial-opcodes")]
+    #[test]
+    fn test_unofficial_immediate_and_nop() {
+        let mut cpu = CPU::new();
+        // LDA #$FF; ANC #$80; ALR #$FF; LDX #$7F; AXS #$10; BRK
+        cpu.load_and_run(vec![0xa9, 0xff, 0x0b, 0x80, 0x4b, 0xff, 0xa2, 0x7f, 0xcb, 0x10, 0x00]).unwrap();
+        assert_eq!(cpu.register_a, 0x40);
+        assert_eq!(cpu.register_x, 0x30);
+        assert!(cpu.status.contains(CPUFlags::CARRY));
+
+        let mut cpu = CPU::new();
+        // SEC; LDA #$FF; ARR #$FF; NOP $10; NOP; NOP $0200; NOP #$01; NOP $0200,X; BRK
+        cpu.load_and_run(vec![
+            0x38, 0xa9, 0xff, 0x6b, 0xff, 0x04, 0x10, 0x1a, 0x0c, 0x00, 0x02, 0x80, 0x01, 0x1c, 0x00, 0x02, 0x00,
+        ]).unwrap();
+        assert_eq!(cpu.register_a, 0xff);
+        assert!(cpu.status.contains(CPUFlags::CARRY));
+        assert!(!cpu.status.contains(CPUFlags::OVERFLOW));
+        // 每条指令都按正确的长度跳过了操作数, 最后执行的是程序末尾的 BRK
+        assert_eq!(cpu.program_counter, 0x0600 + 17);
+    }
+
+    #[test]
+    fn test_and_eor_ora_write_accumulator() {
+        let mut cpu = CPU::new();
+        // LDA #$0f; AND #$3c; EOR #$ff; ORA #$01; BRK
+        cpu.load_and_run(vec![0xa9, 0x0f, 0x29, 0x3c, 0x49, 0xff, 0x09, 0x01, 0x00]).unwrap();
+        assert_eq!(cpu.register_a, 0xf3);
+        assert_eq!(cpu.register_x, 0);
+        assert!(cpu.status.contains(CPUFlags::NEGATIV));
+    }
+
+    #[test]
+    fn test_asl_accumulator() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x81, 0x0a, 0x00]).unwrap();
+        assert_eq!(cpu.register_a, 0x02);
+        assert!(cpu.status.contains(CPUFlags::CARRY));
+    }
+
+    #[test]
+    fn test_ror_rotates_carry_in() {
+        let mut cpu = CPU::new();
+        // SEC; LDA #$02; ROR A; BRK
+        cpu.load_and_run(vec![0x38, 0xa9, 0x02, 0x6a, 0x00]).unwrap();
+        assert_eq!(cpu.register_a, 0x81);
+        assert!(!cpu.status.contains(CPUFlags::CARRY));
+    }
+
+    #[test]
+    fn test_branch_loop() {
+        let mut cpu = CPU::new();
+        // LDX #$05; LDY #$00; loop: INY; DEX; BNE loop; BRK
+        cpu.load_and_run(vec![0xa2, 0x05, 0xa0, 0x00, 0xc8, 0xca, 0xd0, 0xfc, 0x00]).unwrap();
+        assert_eq!(cpu.register_x, 0);
+        assert_eq!(cpu.register_y, 5);
+    }
+
+    #[test]
+    fn test_jsr_rts() {
+        let mut cpu = CPU::new();
+        // JSR $0607; LDX #$02; BRK; BRK; sub: LDA #$01; RTS
+        cpu.load_and_run(vec![0x20, 0x07, 0x06, 0xa2, 0x02, 0x00, 0x00, 0xa9, 0x01, 0x60]).unwrap();
+        assert_eq!(cpu.register_a, 0x01);
+        assert_eq!(cpu.register_x, 0x02);
+        assert_eq!(cpu.stack_pointer, STACK_RESET);
+    }
+
+    #[test]
+    fn test_jmp_indirect_page_boundary_bug() {
+        let mut cpu = CPU::new();
+        cpu.memory_write(0x30ff, 0x10);
+        cpu.memory_write(0x3000, 0x06);
+        cpu.memory_write(0x3100, 0x07);
+        // JMP ($30FF); ...; $0610: LDA #$42; BRK
+        let mut program = vec![0x6c, 0xff, 0x30];
+        program.resize(0x10, 0xea);
+        program.extend([0xa9, 0x42, 0x00]);
+        cpu.load_and_run(program).unwrap();
+        assert_eq!(cpu.register_a, 0x42);
+    }
+
+    #[test]
+    fn test_cmp_sets_carry_and_zero() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x10, 0xc9, 0x10, 0x00]).unwrap();
+        assert!(cpu.status.contains(CPUFlags::CARRY));
+        assert!(cpu.status.contains(CPUFlags::ZERO));
+    }
+
+    #[test]
+    fn test_bit_copies_high_bits() {
+        let mut cpu = CPU::new();
+        cpu.memory_write(0x10, 0xc0);
+        cpu.load_and_run(vec![0xa9, 0x01, 0x24, 0x10, 0x00]).unwrap();
+        assert!(cpu.status.contains(CPUFlags::ZERO));
+        assert!(cpu.status.contains(CPUFlags::NEGATIV));
+        assert!(cpu.status.contains(CPUFlags::OVERFLOW));
+    }
+
+    #[test]
+    fn test_php_plp_pha_pla() {
+        let mut cpu = CPU::new();
+        // LDA #$80; PHA; PHP; LDA #$00; PLP; PLA; BRK
+        cpu.load_and_run(vec![0xa9, 0x80, 0x48, 0x08, 0xa9, 0x00, 0x28, 0x68, 0x00]).unwrap();
+        assert_eq!(cpu.register_a, 0x80);
+        assert!(cpu.status.contains(CPUFlags::NEGATIV));
+        assert!(!cpu.status.contains(CPUFlags::BREAK));
+        assert_eq!(cpu.stack_pointer, STACK_RESET);
+    }
+
+    #[test]
+    fn test_run_with_callback_stops_on_request() {
+        let mut cpu = CPU::new();
+        // loop: INX; JMP loop
+        cpu.memory_load_program(vec![0xe8, 0x4c, 0x00, 0x06]).unwrap();
+        cpu.reset();
+        let mut calls = 0;
+        cpu.run_with_callback(|cpu| {
+            calls += 1;
+            if cpu.register_x == 10 {
+                RunControl::Stop
+            } else {
+                RunControl::Continue
+            }
+        }).unwrap();
+        assert_eq!(cpu.register_x, 10);
+        // 10 次 INX 和 9 次 JMP, 最后一次回调时停止
+        assert_eq!(calls, 20);
+    }
+
+    #[test]
+    fn test_unknown_and_jam_opcodes_return_errors() {
+        let mut cpu = CPU::new();
+        // LDA #$01; KIL
+        let error = cpu.load_and_run(vec![0xa9, 0x01, 0x02]).unwrap_err();
+        assert_eq!(error, CpuError::Jammed { opcode: 0x02, program_counter: 0x0602 });
+        assert_eq!(cpu.program_counter, 0x0602);
+        assert_eq!(cpu.register_a, 0x01);
+        // 卡死之后再执行还是同样的错误
+        assert_eq!(cpu.step().unwrap_err(), error);
+
+        let mut cpu = CPU::new();
+        // 0x8B (XAA) 是不稳定的非官方指令, 没有实现
+        let error = cpu.load_and_run(vec![0x8b, 0x00]).unwrap_err();
+        assert_eq!(error, CpuError::UnknownOpcode { opcode: 0x8b, program_counter: 0x0600 });
+        assert_eq!(error.to_string(), "opcode 8B at 0600 is not recognized");
+    }
+
+    #[test]
+    fn test_cycle_limit() {
+        let mut cpu = CPU::new();
+        cpu.cycle_limit = Some(1000);
+        // loop: JMP loop
+        let error = cpu.load_and_run(vec![0x4c, 0x00, 0x06]).unwrap_err();
+        assert_eq!(error, CpuError::BudgetExceeded { limit: 1000 });
+        assert!(cpu.cycles >= 1000 && cpu.cycles < 1003);
+    }
+
+    #[test]
+    fn test_load_program_past_end_of_memory() {
+        let mut cpu = CPU::new();
+        let error = cpu.load_and_run(vec![0xea; 0x10000 - 0x0600 + 1]).unwrap_err();
+        assert_eq!(error, CpuError::ProgramTooLarge(ProgramTooLarge { addr: 0x0600, len: 0xfa01 }));
+        assert_eq!(cpu.memory_read(0x0600), 0x00);
+    }
+
+    #[test]
+    fn test_stack_checks() {
+        let mut cpu = CPU::new();
+        cpu.stack_checks = true;
+        // LDX #$00; TXS; PHA; BRK
+        let error = cpu.load_and_run(vec![0xa2, 0x00, 0x9a, 0x48, 0x00]).unwrap_err();
+        assert_eq!(error, CpuError::StackOverflow { program_counter: 0x0603 });
+        assert_eq!(cpu.stack_pointer, 0xff);
+
+        let mut cpu = CPU::new();
+        cpu.stack_checks = true;
+        // LDX #$FF; TXS; PLA; BRK
+        let error = cpu.load_and_run(vec![0xa2, 0xff, 0x9a, 0x68, 0x00]).unwrap_err();
+        assert_eq!(error, CpuError::StackUnderflow { program_counter: 0x0603 });
+
+        // 默认不检查, 栈指针直接回绕
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa2, 0x00, 0x9a, 0x48, 0x00]).unwrap();
+        assert_eq!(cpu.stack_pointer, 0xff);
+    }
+
+    #[test]
+    fn test_parse_cpu_variant() {
+        assert_eq!("65C02".parse(), Ok(CpuVariant::Cmos65C02));
+        assert_eq!("6502".parse(), Ok(CpuVariant::Nmos6502));
+        assert_eq!("nes".parse(), Ok(CpuVariant::Nes2A03));
+        assert!("z80".parse::<CpuVariant>().is_err());
+    }
+
+    #[test]
+    fn test_decimal_mode_ignored_on_2a03() {
+        let mut cpu = CPU::new();
+        // SED; CLC; LDA #$09; ADC #$01; BRK
+        cpu.load_and_run(vec![0xf8, 0x18, 0xa9, 0x09, 0x69, 0x01, 0x00]).unwrap();
+        assert_eq!(cpu.register_a, 0x0a);
+    }
+
+    fn run_decimal(variant: CpuVariant, program: Vec<u8>) -> CPU {
+        let mut cpu = CPU::new();
+        cpu.variant = variant;
+        cpu.load_and_run(program).unwrap();
+        cpu
+    }
+
+    #[test]
+    fn test_decimal_adc() {
+        for variant in [CpuVariant::Nmos6502, CpuVariant::Cmos65C02] {
+            // SED; CLC; LDA #$09; ADC #$01; BRK
+            let cpu = run_decimal(variant, vec![0xf8, 0x18, 0xa9, 0x09, 0x69, 0x01, 0x00]);
+            assert_eq!(cpu.register_a, 0x10);
+            assert!(!cpu.status.contains(CPUFlags::CARRY));
+            // SED; SEC; LDA #$58; ADC #$46; BRK
+            let cpu = run_decimal(variant, vec![0xf8, 0x38, 0xa9, 0x58, 0x69, 0x46, 0x00]);
+            assert_eq!(cpu.register_a, 0x05);
+            assert!(cpu.status.contains(CPUFlags::CARRY));
+        }
+
+        // 99 + 1 = 100: NMOS 的 Z 来自二进制结果 0x9A, N 来自中间结果 0xA0
+        let program = vec![0xf8, 0x18, 0xa9, 0x99, 0x69, 0x01, 0x00];
+        let nmos = run_decimal(CpuVariant::Nmos6502, program.clone());
+        assert_eq!(nmos.register_a, 0x00);
+        assert!(nmos.status.contains(CPUFlags::CARRY));
+        assert!(!nmos.status.contains(CPUFlags::ZERO));
+        assert!(nmos.status.contains(CPUFlags::NEGATIV));
+        let cmos = run_decimal(CpuVariant::Cmos65C02, program);
+        assert_eq!(cmos.register_a, 0x00);
+        assert!(cmos.status.contains(CPUFlags::ZERO));
+        assert!(!cmos.status.contains(CPUFlags::NEGATIV));
+        // 65C02 的十进制加法多一个周期
+        assert_eq!(cmos.cycles, nmos.cycles + 1);
+    }
+
+    #[test]
+    fn test_decimal_sbc() {
+        for variant in [CpuVariant::Nmos6502, CpuVariant::Cmos65C02] {
+            // SED; SEC; LDA #$50; SBC #$25; BRK
+            let cpu = run_decimal(variant, vec![0xf8, 0x38, 0xa9, 0x50, 0xe9, 0x25, 0x00]);
+            assert_eq!(cpu.register_a, 0x25);
+            assert!(cpu.status.contains(CPUFlags::CARRY));
+            // SED; SEC; LDA #$00; SBC #$01; BRK
+            let cpu = run_decimal(variant, vec![0xf8, 0x38, 0xa9, 0x00, 0xe9, 0x01, 0x00]);
+            assert_eq!(cpu.register_a, 0x99);
+            assert!(!cpu.status.contains(CPUFlags::CARRY));
+            // SED; CLC; LDA #$32; SBC #$02; BRK  借位时多减 1
+            let cpu = run_decimal(variant, vec![0xf8, 0x18, 0xa9, 0x32, 0xe9, 0x02, 0x00]);
+            assert_eq!(cpu.register_a, 0x29);
+        }
+    }
+
+    fn run_cmos(program: Vec<u8>) -> CPU {
+        let mut cpu = CPU::with_variant(Box::<Memory>::default(), CpuVariant::Cmos65C02);
+        cpu.load_and_run(program).unwrap();
+        cpu
+    }
+
+    #[test]
+    fn test_cmos_instruction_table() {
+        let builtins = instruction_set(CpuVariant::Cmos65C02);
+        // 除了 STP (0xDB) 和 WAI (0xCB) 以外每个操作码都有定义
+        assert_eq!(builtins.len(), 254);
+        assert!(!builtins.contains_key(&0xdb) && !builtins.contains_key(&0xcb));
+        assert_eq!(builtins[&0x80].op.mnemonic, "BRA");
+        assert_eq!(builtins[&0xb2].op.mode, AddressingMode::Indirect_ZeroPage);
+        assert_eq!(instruction_set(CpuVariant::Nes2A03)[&0x6c].op.cycles, 5);
+        assert_eq!(builtins[&0x6c].op.cycles, 6);
+    }
+
+    #[test]
+    fn test_cmos_bra_and_register_stack() {
+        // BRA +2; LDA #$01; LDX #$42; PHX; PLY; BRK
+        let cpu = run_cmos(vec![0x80, 0x02, 0xa9, 0x01, 0xa2, 0x42, 0xda, 0x7a, 0x00]);
+        assert_eq!(cpu.register_a, 0);
+        assert_eq!(cpu.register_y, 0x42);
+        assert_eq!(cpu.stack_pointer, STACK_RESET);
+    }
+
+    #[test]
+    fn test_cmos_stz_tsb_trb() {
+        // LDA #$3C; STA $10; STA $11; LDA #$0F; TSB $10; LDA #$30; TRB $11; STZ $12; BRK
+        let mut cpu = CPU::with_variant(Box::<Memory>::default(), CpuVariant::Cmos65C02);
+        cpu.memory_write(0x12, 0x99);
+        cpu.load_and_run(vec![
+            0xa9, 0x3c, 0x85, 0x10, 0x85, 0x11, 0xa9, 0x0f, 0x04, 0x10, 0xa9, 0x30, 0x14, 0x11, 0x64, 0x12, 0x00,
+        ]).unwrap();
+        assert_eq!(cpu.memory_read(0x10), 0x3f);
+        assert_eq!(cpu.memory_read(0x11), 0x0c);
+        assert_eq!(cpu.memory_read(0x12), 0x00);
+        // TRB 的零标志来自 A & M = 0x30 & 0x3C
+        assert!(!cpu.status.contains(CPUFlags::ZERO));
+    }
+
+    #[test]
+    fn test_cmos_zero_page_indirect() {
+        let mut cpu = CPU::with_variant(Box::<Memory>::default(), CpuVariant::Cmos65C02);
+        cpu.memory_write_u16(0x20, 0x0400);
+        cpu.memory_write(0x0400, 0x55);
+        // LDA ($20); STA $10; BRK
+        cpu.load_and_run(vec![0xb2, 0x20, 0x85, 0x10, 0x00]).unwrap();
+        assert_eq!(cpu.memory_read(0x10), 0x55);
+    }
+
+    #[test]
+    fn test_cmos_bit_manipulation() {
+        // LDA #$FF; STA $10; RMB3 $10; BBR3 $10,+2; LDX #$01; BBS3 $10,+2; LDY #$02; SMB7 $11; BRK
+        let mut cpu = run_cmos(vec![
+            0xa9, 0xff, 0x85, 0x10, 0x37, 0x10, 0x3f, 0x10, 0x02, 0xa2, 0x01, 0xbf, 0x10, 0x02, 0xa0, 0x02, 0xf7, 0x11,
+            0x00,
+        ]);
+        assert_eq!(cpu.memory_read(0x10), 0xf7);
+        assert_eq!(cpu.memory_read(0x11), 0x80);
+        assert_eq!(cpu.register_x, 0);
+        assert_eq!(cpu.register_y, 0x02);
+    }
+
+    /*
+    JMP ($02FF): NMOS 从 $0200 读高字节, 65C02 从 $0300 读
+     */
+    #[test]
+    fn test_cmos_fixes_indirect_jmp_page_wrap() {
+        for (variant, expected) in [(CpuVariant::Nmos6502, 0x02), (CpuVariant::Cmos65C02, 0x01)] {
+            let mut cpu = CPU::with_variant(Box::<Memory>::default(), variant);
+            cpu.memory_write(0x02ff, 0x00);
+            cpu.memory_write(0x0300, 0x07);
+            cpu.memory_write(0x0200, 0x08);
+            // $0700: LDA #$01; BRK   $0800: LDA #$02; BRK
+            for (address, value) in [(0x0700, 0x01), (0x0800, 0x02)] {
+                cpu.memory_write(address, 0xa9);
+                cpu.memory_write(address + 1, value);
+                cpu.memory_write(address + 2, 0x00);
+            }
+            cpu.load_and_run(vec![0x6c, 0xff, 0x02]).unwrap();
+            assert_eq!(cpu.register_a, expected, "{:?}", variant);
+        }
+    }
+
+    #[test]
+    fn test_cmos_indexed_indirect_jmp() {
+        let mut cpu = CPU::with_variant(Box::<Memory>::default(), CpuVariant::Cmos65C02);
+        cpu.memory_write_u16(0x0304, 0x0700);
+        cpu.memory_write(0x0700, 0x00);
+        // LDX #$04; JMP ($0300,X)
+        cpu.load_and_run(vec![0xa2, 0x04, 0x7c, 0x00, 0x03]).unwrap();
+        assert_eq!(cpu.program_counter, 0x0701);
+    }
+
+    #[test]
+    fn test_cmos_stp_and_undefined_nops() {
+        // 0x02 在 NMOS 上会卡死, 65C02 上是两个字节的 NOP
+        let cpu = run_cmos(vec![0x02, 0xff, 0xa9, 0x07, 0x00]);
+        assert_eq!(cpu.register_a, 0x07);
+
+        let mut cpu = CPU::with_variant(Box::<Memory>::default(), CpuVariant::Cmos65C02);
+        let error = cpu.load_and_run(vec![0xea, 0xdb]).unwrap_err();
+        assert_eq!(error, CpuError::Jammed { opcode: 0xdb, program_counter: 0x0601 });
+    }
+
+    #[test]
+    fn test_step_returns_execution_record() {
+        let mut cpu = CPU::new();
+        // LDA #$05; STA $10,X; ASL A; BRK
+        cpu.memory_load_program(vec![0xa9, 0x05, 0x95, 0x10, 0x0a, 0x00]).unwrap();
+        cpu.reset();
+        cpu.halt_on_brk = true;
+        cpu.register_x = 0x02;
+
+        let lda = cpu.step().unwrap();
+        assert_eq!(lda, StepResult {
+            program_counter: 0x0600,
+            opcode: 0xa9,
+            mnemonic: "LDA",
+            operand_address: Some(0x0601),
+            cycles: 2,
+            brk: false,
+        });
+        assert_eq!(cpu.register_a, 0x05);
+
+        let sta = cpu.step().unwrap();
+        assert_eq!(sta.operand_address, Some(0x12));
+        assert_eq!(sta.cycles, 4);
+        assert_eq!(cpu.memory_read(0x12), 0x05);
+
+        let asl = cpu.step().unwrap();
+        assert_eq!(asl.mnemonic, "ASL");
+        assert_eq!(asl.operand_address, None);
+
+        let brk = cpu.step().unwrap();
+        assert!(brk.brk);
+        assert_eq!(cpu.program_counter, 0x0606);
+    }
+
+    /*
+    单步执行的记录中的操作数地址只通过 peek 计算, 间接寻址时指针只被真正读取一次
+     */
+    #[test]
+    fn test_step_reads_indirect_pointer_once() {
+        use crate::debugger::watch::{WatchKind, Watchpoint};
+        let mut cpu = CPU::new();
+        cpu.memory_write_u16(0x20, 0x0400);
+        // LDA ($20),Y; BRK
+        cpu.memory_load_program(vec![0xb1, 0x20, 0x00]).unwrap();
+        cpu.reset();
+        cpu.register_y = 0x02;
+        cpu.watchpoints.add(Watchpoint { start: 0x20, end: 0x21, kind: WatchKind::Read });
+        cpu.watchpoints.add(Watchpoint { start: 0x0402, end: 0x0402, kind: WatchKind::Read });
+
+        let lda = cpu.step().unwrap();
+        assert_eq!(lda.operand_address, Some(0x0402));
+        let hits: Vec<u16> = cpu.watchpoints.take_hits().iter().map(|hit| hit.addr).collect();
+        assert_eq!(hits, vec![0x20, 0x21, 0x0402]);
+    }
+
+    /*
+    分支目标刚好是操作数的地址时程序计数器看起来没有变化, 也不能再跳过操作数
+     */
+    #[test]
+    fn test_branch_to_operand_address() {
+        let mut cpu = CPU::new();
+        // LDX #$01; BNE $FF
+        cpu.memory_load_program(vec![0xa2, 0x01, 0xd0, 0xff]).unwrap();
+        cpu.reset();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.program_counter, 0x0603);
+
+        let mut cpu = CPU::with_variant(Box::<Memory>::default(), CpuVariant::Cmos65C02);
+        // BBR0 $10,$FE
+        cpu.memory_load_program(vec![0x0f, 0x10, 0xfe]).unwrap();
+        cpu.reset();
+        cpu.step().unwrap();
+        assert_eq!(cpu.program_counter, 0x0601);
+    }
+
+    #[test]
+    fn test_cycles_page_cross_penalty() {
+        let mut cpu = CPU::new();
+        // LDX #$01; LDA $10ff,X; LDA $1000,X; STA $10ff,X; BRK
+        cpu.memory_load_program(vec![0xa2, 0x01, 0xbd, 0xff, 0x10, 0xbd, 0x00, 0x10, 0x9d, 0xff, 0x10, 0x00]).unwrap();
+        cpu.reset();
+        assert_eq!(cpu.step().unwrap().cycles, 2);
+        // 跨页: 4 + 1
+        assert_eq!(cpu.step().unwrap().cycles, 5);
+        assert_eq!(cpu.step().unwrap().cycles, 4);
+        // 写指令没有跨页惩罚
+        assert_eq!(cpu.step().unwrap().cycles, 5);
+        assert_eq!(cpu.cycles, 7 + 2 + 5 + 4 + 5);
+    }
+
+    #[test]
+    fn test_cycles_indirect_y_page_cross() {
+        let mut cpu = CPU::new();
+        cpu.memory_write_u16(0x20, 0x12ff);
+        // LDY #$01; LDA ($20),Y; BRK
+        cpu.memory_load_program(vec![0xa0, 0x01, 0xb1, 0x20, 0x00]).unwrap();
+        cpu.reset();
+        cpu.step().unwrap();
+        assert_eq!(cpu.step().unwrap().cycles, 6);
+    }
+
+    #[test]
+    fn test_cycles_branch_penalty() {
+        let mut cpu = CPU::new();
+        // $0600: CLC; BCS +2 (不跳转); BCC +$7a (跳转到 $067f, 同一页)
+        cpu.memory_load_program(vec![0x18, 0xb0, 0x02, 0x90, 0x7a]).unwrap();
+        cpu.reset();
+        cpu.step().unwrap();
+        assert_eq!(cpu.step().unwrap().cycles, 2);
+        assert_eq!(cpu.step().unwrap().cycles, 3);
+        assert_eq!(cpu.program_counter, 0x067f);
+
+        // $06f0: BNE +$10 跳转到 $0702, 跨页
+        cpu.program_counter = 0x06f0;
+        cpu.memory_write(0x06f0, 0xd0);
+        cpu.memory_write(0x06f1, 0x10);
+        cpu.clear_zero_flag();
+        assert_eq!(cpu.step().unwrap().cycles, 4);
+        assert_eq!(cpu.program_counter, 0x0702);
+    }
+
+    #[test]
+    fn test_memory_covers_full_address_space() {
+        let mut cpu = CPU::new();
+        cpu.memory_write(0xffff, 0xab);
+        assert_eq!(cpu.memory_read(0xffff), 0xab);
+    }
+
+    #[test]
+    fn test_brk_is_software_interrupt() {
+        let mut cpu = CPU::new();
+        // 0x0600: BRK; (填充字节); INX
+        // 0x0700: LDA #$42; RTI
+        cpu.memory_load_program(vec![0x00, 0xff, 0xe8]).unwrap();
+        cpu.reset();
+        cpu.status.remove(CPUFlags::INTERRUPT_DISABLE);
+        for (i, byte) in [0xa9, 0x42, 0x40].iter().enumerate() {
+            cpu.memory_write(0x0700 + i as u16, *byte);
+        }
+        cpu.memory_write_u16(0xfffe, 0x0700);
+
+        let result = cpu.step().unwrap();
+        assert!(!result.brk);
+        assert_eq!(result.cycles, 7);
+        assert_eq!(cpu.program_counter, 0x0700);
+        assert!(cpu.status.contains(CPUFlags::INTERRUPT_DISABLE));
+        // 返回地址跳过填充字节, 压栈的状态寄存器带 B 标志
+        let status = cpu.memory_read(STACK + cpu.stack_pointer as u16 + 1);
+        assert_eq!(status & 0b0011_0000, 0b0011_0000);
+        assert_eq!(cpu.memory_read_u16(STACK + cpu.stack_pointer as u16 + 2), 0x0602);
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.program_counter, 0x0602);
+        assert!(!cpu.status.contains(CPUFlags::INTERRUPT_DISABLE));
+        cpu.step().unwrap();
+        assert_eq!(cpu.register_x, 1);
+        assert_eq!(cpu.register_a, 0x42);
+    }
+
+    #[test]
+    fn test_halt_on_brk() {
+        let mut cpu = CPU::new();
+        cpu.memory_load_program(vec![0x00]).unwrap();
+        cpu.reset();
+        cpu.halt_on_brk = true;
+        let result = cpu.step().unwrap();
+        assert!(result.brk);
+        assert_eq!(cpu.stack_pointer, STACK_RESET);
+    }
+
+    #[test]
+    fn test_irq_respects_interrupt_disable() {
+        let mut cpu = CPU::new();
+        // 0x0600: NOP; CLI; NOP
+        cpu.memory_load_program(vec![0xea, 0x58, 0xea]).unwrap();
+        cpu.reset();
+        cpu.memory_write_u16(0xfffe, 0x0700);
+        cpu.memory_write(0x0700, 0xea);
+        cpu.irq_line = true;
+        // 复位之后中断是屏蔽的
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.program_counter, 0x0602);
+        let before = cpu.cycles;
+        let result = cpu.step().unwrap();
+        assert_eq!(result.program_counter, 0x0700);
+        assert_eq!(cpu.cycles - before, 7 + 2);
+        let status = cpu.memory_read(STACK + cpu.stack_pointer as u16 + 1);
+        assert_eq!(status & 0b0011_0000, 0b0010_0000);
+        assert_eq!(cpu.memory_read_u16(STACK + cpu.stack_pointer as u16 + 2), 0x0602);
+    }
+
+    #[test]
+    fn test_nmi_ignores_interrupt_disable() {
+        let mut cpu = CPU::new();
+        cpu.memory_load_program(vec![0xea]).unwrap();
+        cpu.reset();
+        cpu.memory_write_u16(0xfffa, 0x0700);
+        cpu.memory_write(0x0700, 0xea);
+        cpu.trigger_nmi();
+        let result = cpu.step().unwrap();
+        assert_eq!(result.program_counter, 0x0700);
+        assert_eq!(cpu.program_counter, 0x0701);
+        assert!(!cpu.nmi_pending);
+    }
+
+    #[test]
+    fn test_nmi_on_vblank() {
+        let mut cpu = CPU::with_bus(Box::<NesBus>::default());
+        // 0x0000: LDA #$80; STA $2000; JMP $0005
+        let program = [0xa9, 0x80, 0x8d, 0x00, 0x20, 0x4c, 0x05, 0x00];
+        for (i, byte) in program.iter().enumerate() {
+            cpu.memory_write(i as u16, *byte);
+        }
+        cpu.program_counter = 0x0000;
+        cpu.step().unwrap();
+        // 没有卡带时 0xFFFA 读出 0, NMI 会跳回 0x0000 重新执行 LDA
+        let mut steps = 0;
+        while cpu.step().unwrap().program_counter != 0x0000 && steps < 20_000 {
+            steps += 1;
+        }
+        assert!(steps < 20_000);
+        assert!(cpu.status.contains(CPUFlags::INTERRUPT_DISABLE));
+        assert_eq!(cpu.stack_pointer, STACK_RESET.wrapping_sub(3));
+        let status = cpu.memory_read(STACK + cpu.stack_pointer as u16 + 1);
+        assert_eq!(status & 0b0011_0000, 0b0010_0000);
+        assert_eq!(cpu.memory_read_u16(STACK + cpu.stack_pointer as u16 + 2), 0x0005);
+    }
+
+    #[test]
+    fn test_oam_dma_stalls_cpu() {
+        let mut cpu = CPU::with_bus(Box::<NesBus>::default());
+        // LDA #$02; STA $4014
+        let program = [0xa9, 0x02, 0x8d, 0x14, 0x40];
+        for (i, byte) in program.iter().enumerate() {
+            cpu.memory_write(i as u16, *byte);
+        }
+        cpu.program_counter = 0;
+        cpu.step().unwrap();
+        let before = cpu.cycles;
+        let result = cpu.step().unwrap();
+        assert_eq!(result.cycles, 4);
+        assert_eq!(cpu.cycles - before, 4 + 513);
+    }
+
+    #[test]
+    fn test_run_on_nes_bus() {
+        let mut cpu = CPU::with_bus(Box::<NesBus>::default());
+        // LDA #$42; STA $0810 (镜像到 $0010); BRK
+        cpu.memory_load_program(vec![0xa9, 0x42, 0x8d, 0x10, 0x08, 0x00]).unwrap();
+        cpu.reset();
+        // 没有卡带时复位向量读出来是 0, 手动设置程序入口
+        cpu.program_counter = 0x0600;
+        cpu.halt_on_brk = true;
+        cpu.interpret().unwrap();
+        assert_eq!(cpu.memory_read(0x0010), 0x42);
+        assert_eq!(cpu.memory_read(0x1810), 0x42);
+    }
+
     #[test]
     fn test_write_u16() {
         let mut cpu = CPU::new();
         cpu.memory_write_u16(0xff00, 0x1234);
-        assert_eq!(cpu.memory.read(0xff00), 0x34);
-        assert_eq!(cpu.memory.read(0xff01), 0x12);
+        assert_eq!(cpu.bus.read(0xff00), 0x34);
+        assert_eq!(cpu.bus.read(0xff01), 0x12);
     }
 
     #[test]
     fn test_read_u16() {
         let x = 0x1234u16;
         let mut cpu = CPU::new();
-        cpu.memory.write(0xff00, 0x34);
-        cpu.memory.write(0xff01, 0x12);
+        cpu.bus.write(0xff00, 0x34);
+        cpu.bus.write(0xff01, 0x12);
         assert_eq!(cpu.memory_read_u16(0xff00), 0x1234);
     }
 }
@@ -0,0 +1,589 @@
+use std::collections::BTreeSet;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use crate::cpu::{CPUFlags, CPU};
+use super::watch::{Access, WatchKind, Watchpoint};
+use super::{DebugHook, DebugState};
+
+// 运行期间每执行这么多条指令检查一次客户端有没有发来 Ctrl-C
+const POLL_INTERVAL: u32 = 1024;
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+/*
+GDB 没有 6502 架构, 通过 target.xml 告诉客户端寄存器的布局
+g 包中寄存器的顺序和这里一致: A X Y P SP 各一个字节, PC 两个字节 (小端)
+ */
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.nes_platform.cpu">
+    <reg name="a" bitsize="8" regnum="0"/>
+    <reg name="x" bitsize="8"/>
+    <reg name="y" bitsize="8"/>
+    <reg name="p" bitsize="8"/>
+    <reg name="sp" bitsize="8"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StubState {
+    // 等待客户端的命令
+    Stopped,
+    Running,
+    // 还要执行多少条指令
+    Step(u32),
+    // 客户端断开或者 detach, 之后不再干预模拟
+    Detached,
+}
+
+/**
+GDB 远程串行协议 (RSP) 的服务端, 让 gdb 或者其他兼容 RSP 的工具通过 TCP 调试 CPU
+https://sourceware.org/gdb/current/onlinedocs/gdb.html/Remote-Protocol.html
+支持的包: ? g G p P m M c s Z0-Z4 z0-z4 k D qSupported qXfer:features:read qAttached
+和终端调试器一样在每条指令之前调用 before_instruction, 停止时不阻塞, 前端可以继续处理窗口事件
+ */
+pub struct GdbStub {
+    stream: TcpStream,
+    // 还没有处理完的输入
+    input: Vec<u8>,
+    breakpoints: BTreeSet<u16>,
+    state: StubState,
+    // 从断点处继续运行时, 第一条指令不再触发这个断点
+    skip_breakpoint: Option<u16>,
+    poll_countdown: u32,
+}
+
+impl GdbStub {
+    /*
+    在 addr 上监听并等待一个客户端连接, 连接之前一直阻塞
+     */
+    pub fn listen<A: ToSocketAddrs>(addr: A) -> io::Result<GdbStub> {
+        let listener = TcpListener::bind(addr)?;
+        let (stream, _) = listener.accept()?;
+        GdbStub::new(stream)
+    }
+
+    // 连接上之后 CPU 处于停止状态, 等待客户端的命令
+    pub fn new(stream: TcpStream) -> io::Result<GdbStub> {
+        stream.set_nodelay(true)?;
+        Ok(GdbStub {
+            stream,
+            input: Vec::new(),
+            breakpoints: BTreeSet::new(),
+            state: StubState::Stopped,
+            skip_breakpoint: None,
+            poll_countdown: POLL_INTERVAL,
+        })
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.state != StubState::Detached
+    }
+
+    /*
+    不阻塞地读取客户端已经发来的数据, 客户端断开时返回 false
+     */
+    fn receive(&mut self) -> bool {
+        if self.stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let mut buf = [0u8; 4096];
+        let connected = loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => break false,
+                Ok(n) => self.input.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break true,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => break false,
+            }
+        };
+        connected && self.stream.set_nonblocking(false).is_ok()
+    }
+
+    /*
+    从输入中取出下一个完整的包, 确认 (+) 或者要求重发 (-) 也在这里处理
+    Ctrl-C 不是包, 单独作为一个 0x03 字节发送, 返回 Interrupt
+     */
+    fn next_packet(&mut self) -> Option<Incoming> {
+        loop {
+            let start = self.input.iter().position(|&b| b == b'$' || b == 0x03)?;
+            if self.input[start] == 0x03 {
+                self.input.drain(..=start);
+                return Some(Incoming::Interrupt);
+            }
+            let hash = start + self.input[start..].iter().position(|&b| b == b'#')?;
+            if self.input.len() < hash + 3 {
+                return None;
+            }
+            let data = self.input[start + 1..hash].to_vec();
+            let checksum = std::str::from_utf8(&self.input[hash + 1..hash + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            self.input.drain(..hash + 3);
+            if checksum == Some(packet_checksum(&data)) {
+                self.write_raw(b"+");
+                return Some(Incoming::Packet(String::from_utf8_lossy(&data).into_owned()));
+            }
+            self.write_raw(b"-");
+        }
+    }
+
+    fn send_packet(&mut self, data: &str) {
+        let packet = format!("${}#{:02x}", data, packet_checksum(data.as_bytes()));
+        self.write_raw(packet.as_bytes());
+    }
+
+    fn write_raw(&mut self, bytes: &[u8]) {
+        if self.stream.write_all(bytes).and_then(|_| self.stream.flush()).is_err() {
+            self.state = StubState::Detached;
+        }
+    }
+
+    fn stop(&mut self, reply: &str) {
+        self.state = StubState::Stopped;
+        self.send_packet(reply);
+    }
+
+    fn resume(&mut self, cpu: &CPU, state: StubState) {
+        self.state = state;
+        self.skip_breakpoint = Some(cpu.program_counter);
+        self.poll_countdown = POLL_INTERVAL;
+    }
+
+    fn detach(&mut self, cpu: &mut CPU) {
+        self.state = StubState::Detached;
+        self.breakpoints.clear();
+        cpu.watchpoints.clear();
+    }
+
+    /*
+    处理一个包, 返回需要回复的内容. c 和 s 没有立即的回复, 在 CPU 停下时才发送停止原因
+     */
+    fn handle_packet(&mut self, cpu: &mut CPU, packet: &str) -> Option<String> {
+        let (command, args) = packet.split_at(packet.chars().next().map(|c| c.len_utf8()).unwrap_or(0));
+        let reply = match command {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => encode_hex(&registers(cpu)),
+            "G" => match decode_hex(args) {
+                Some(bytes) if bytes.len() == 7 => {
+                    set_registers(cpu, &bytes);
+                    "OK".to_string()
+                }
+                _ => "E01".to_string(),
+            },
+            "p" => match usize::from_str_radix(args, 16).ok().and_then(register_range) {
+                Some(range) => encode_hex(&registers(cpu)[range]),
+                None => "E01".to_string(),
+            },
+            "P" => {
+                let parsed = args.split_once('=').and_then(|(n, value)| {
+                    let range = register_range(usize::from_str_radix(n, 16).ok()?)?;
+                    let value = decode_hex(value)?;
+                    (value.len() == range.len()).then_some((range, value))
+                });
+                match parsed {
+                    Some((range, value)) => {
+                        let mut bytes = registers(cpu);
+                        bytes[range].copy_from_slice(&value);
+                        set_registers(cpu, &bytes);
+                        "OK".to_string()
+                    }
+                    None => "E01".to_string(),
+                }
+            }
+            "m" => match parse_addr_len(args) {
+                Some((addr, len)) => {
+                    let bytes: Vec<u8> = (0..len).map(|i| cpu.memory_read(addr.wrapping_add(i))).collect();
+                    // 调试器自己的内存访问不算命中监视点
+                    cpu.watchpoints.take_hits();
+                    encode_hex(&bytes)
+                }
+                None => "E01".to_string(),
+            },
+            "M" => {
+                let parsed = args.split_once(':').and_then(|(range, data)| Some((parse_addr_len(range)?, decode_hex(data)?)));
+                match parsed {
+                    Some(((addr, len), data)) if data.len() == len as usize => {
+                        for (i, byte) in data.into_iter().enumerate() {
+                            cpu.memory_write(addr.wrapping_add(i as u16), byte);
+                        }
+                        cpu.watchpoints.take_hits();
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            "c" | "s" => {
+                if !args.is_empty() {
+                    match u16::from_str_radix(args, 16) {
+                        Ok(addr) => cpu.program_counter = addr,
+                        Err(_) => return Some("E01".to_string()),
+                    }
+                }
+                let state = if command == "c" { StubState::Running } else { StubState::Step(1) };
+                self.resume(cpu, state);
+                return None;
+            }
+            "Z" | "z" => self.handle_breakpoint(cpu, command == "Z", args),
+            "D" => {
+                self.send_packet("OK");
+                self.detach(cpu);
+                return None;
+            }
+            "H" => "OK".to_string(),
+            "q" => {
+                if args.starts_with("Supported") {
+                    "PacketSize=1000;qXfer:features:read+".to_string()
+                } else if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+                    match parse_addr_len(range) {
+                        Some((offset, len)) => {
+                            let rest = TARGET_XML.get(offset as usize..).unwrap_or("");
+                            if rest.len() > len as usize {
+                                format!("m{}", &rest[..len as usize])
+                            } else {
+                                format!("l{}", rest)
+                            }
+                        }
+                        None => "E01".to_string(),
+                    }
+                } else if args == "Attached" {
+                    "1".to_string()
+                } else if args == "C" {
+                    "QC1".to_string()
+                } else {
+                    String::new()
+                }
+            }
+            // 不支持的包回复空包
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+
+    /*
+    Z0/Z1 软件和硬件断点都作为 PC 断点, Z2/Z3/Z4 分别是写, 读和读写监视点, kind 是监视的字节数
+     */
+    fn handle_breakpoint(&mut self, cpu: &mut CPU, insert: bool, args: &str) -> String {
+        let mut fields = args.split(',');
+        let parsed = (|| {
+            let kind = fields.next()?;
+            let addr = u16::from_str_radix(fields.next()?, 16).ok()?;
+            let len = u16::from_str_radix(fields.next()?, 16).ok()?;
+            Some((kind, addr, len))
+        })();
+        let Some((kind, addr, len)) = parsed else {
+            return "E01".to_string();
+        };
+        let watch_kind = match kind {
+            "0" | "1" => {
+                if insert {
+                    self.breakpoints.insert(addr);
+                } else {
+                    self.breakpoints.remove(&addr);
+                }
+                return "OK".to_string();
+            }
+            "2" => WatchKind::Write,
+            "3" => WatchKind::Read,
+            "4" => WatchKind::ReadWrite,
+            _ => return String::new(),
+        };
+        if insert {
+            let end = addr.saturating_add(len.max(1) - 1);
+            cpu.watchpoints.add(Watchpoint { start: addr, end, kind: watch_kind });
+        } else {
+            cpu.watchpoints.remove(addr);
+        }
+        "OK".to_string()
+    }
+}
+
+enum Incoming {
+    Packet(String),
+    Interrupt,
+}
+
+impl DebugHook for GdbStub {
+    fn before_instruction(&mut self, cpu: &mut CPU) -> DebugState {
+        if self.state == StubState::Detached {
+            return DebugState::Running;
+        }
+
+        if self.state != StubState::Stopped {
+            let pc = cpu.program_counter;
+            let hits = cpu.watchpoints.take_hits();
+            if let Some(hit) = hits.first() {
+                let name = match hit.access {
+                    Access::Read => "rwatch",
+                    Access::Write => "watch",
+                };
+                self.stop(&format!("T{:02x}{}:{:04x};", SIGTRAP, name, hit.addr));
+            } else if (self.breakpoints.contains(&pc) && self.skip_breakpoint != Some(pc)) || self.state == StubState::Step(0) {
+                self.stop(&format!("S{:02x}", SIGTRAP));
+            } else {
+                self.poll_countdown -= 1;
+                if self.poll_countdown == 0 {
+                    self.poll_countdown = POLL_INTERVAL;
+                    if !self.receive() {
+                        self.detach(cpu);
+                        return DebugState::Running;
+                    }
+                    // 运行时只关心 Ctrl-C, 其他数据留到停止之后处理
+                    if let Some(index) = self.input.iter().position(|&b| b == 0x03) {
+                        self.input.remove(index);
+                        self.stop(&format!("S{:02x}", SIGINT));
+                    }
+                }
+            }
+            self.skip_breakpoint = None;
+        }
+
+        if self.state == StubState::Stopped {
+            if !self.receive() {
+                self.detach(cpu);
+                return DebugState::Running;
+            }
+            while self.state == StubState::Stopped {
+                let Some(incoming) = self.next_packet() else { break };
+                let Incoming::Packet(packet) = incoming else { continue };
+                // k 要求结束被调试的程序, 没有回复
+                if packet == "k" {
+                    self.detach(cpu);
+                    return DebugState::Quit;
+                }
+                if let Some(reply) = self.handle_packet(cpu, &packet) {
+                    self.send_packet(&reply);
+                }
+            }
+        }
+
+        match self.state {
+            StubState::Stopped => DebugState::Paused,
+            StubState::Step(count) => {
+                self.state = StubState::Step(count.saturating_sub(1));
+                DebugState::Running
+            }
+            StubState::Running | StubState::Detached => DebugState::Running,
+        }
+    }
+}
+
+fn packet_checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+fn registers(cpu: &CPU) -> Vec<u8> {
+    let [pc_lo, pc_hi] = cpu.program_counter.to_le_bytes();
+    vec![cpu.register_a, cpu.register_x, cpu.register_y, cpu.status.bits(), cpu.stack_pointer, pc_lo, pc_hi]
+}
+
+fn set_registers(cpu: &mut CPU, bytes: &[u8]) {
+    cpu.register_a = bytes[0];
+    cpu.register_x = bytes[1];
+    cpu.register_y = bytes[2];
+    cpu.status = CPUFlags::from_bits_truncate(bytes[3]);
+    cpu.stack_pointer = bytes[4];
+    cpu.program_counter = u16::from_le_bytes([bytes[5], bytes[6]]);
+}
+
+// 寄存器编号在 g 包中对应的字节
+fn register_range(n: usize) -> Option<std::ops::Range<usize>> {
+    match n {
+        0..=4 => Some(n..n + 1),
+        5 => Some(5..7),
+        _ => None,
+    }
+}
+
+fn parse_addr_len(text: &str) -> Option<(u16, u16)> {
+    let (addr, len) = text.split_once(',')?;
+    Some((u16::from_str_radix(addr, 16).ok()?, u16::from_str_radix(len, 16).ok()?))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::BufReader;
+    use std::thread;
+
+    /*
+    客户端: 发送一个包, 读回确认和回复
+     */
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Client {
+        // stub 已经确认或者回复, 回复和确认在同一次调用中写出, 所以之后可以阻塞读取回复
+        fn ready(&mut self) -> bool {
+            if !self.reader.buffer().is_empty() {
+                return true;
+            }
+            self.writer.set_nonblocking(true).unwrap();
+            let ready = matches!(self.writer.peek(&mut [0u8]), Ok(n) if n > 0);
+            self.writer.set_nonblocking(false).unwrap();
+            ready
+        }
+
+        fn send(&mut self, data: &str) {
+            let packet = format!("${}#{:02x}", data, packet_checksum(data.as_bytes()));
+            self.writer.write_all(packet.as_bytes()).unwrap();
+        }
+
+        fn read_reply(&mut self) -> String {
+            let mut byte = [0u8];
+            loop {
+                self.reader.read_exact(&mut byte).unwrap();
+                if byte[0] == b'$' {
+                    break;
+                }
+            }
+            let mut data = Vec::new();
+            loop {
+                self.reader.read_exact(&mut byte).unwrap();
+                if byte[0] == b'#' {
+                    break;
+                }
+                data.push(byte[0]);
+            }
+            let mut checksum = [0u8; 2];
+            self.reader.read_exact(&mut checksum).unwrap();
+            assert_eq!(std::str::from_utf8(&checksum).unwrap(), format!("{:02x}", packet_checksum(&data)));
+            String::from_utf8(data).unwrap()
+        }
+    }
+
+    fn connect() -> (GdbStub, Client) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || TcpStream::connect(addr).unwrap());
+        let (stream, _) = listener.accept().unwrap();
+        let writer = client.join().unwrap();
+        let reader = BufReader::new(writer.try_clone().unwrap());
+        (GdbStub::new(stream).unwrap(), Client { reader, writer })
+    }
+
+    fn setup() -> CPU {
+        let mut cpu = CPU::new();
+        cpu.halt_on_brk = true;
+        cpu.memory_load_program(crate::asm!(
+            "        LDX #$03",   // 0600
+            "loop:   DEX",        // 0602
+            "        STX $10",    // 0603
+            "        BNE loop",   // 0605
+            "        BRK",        // 0607
+        )).unwrap();
+        cpu.reset();
+        cpu
+    }
+
+    // 像前端一样运行, 直到 stub 停下来发送回复
+    fn run_until_stopped(stub: &mut GdbStub, cpu: &mut CPU) {
+        loop {
+            match stub.before_instruction(cpu) {
+                DebugState::Running => {
+                    cpu.step().unwrap();
+                }
+                _ => return,
+            }
+        }
+    }
+
+    // 把包交给 stub 处理, 返回 stub 的回复
+    fn request(stub: &mut GdbStub, cpu: &mut CPU, client: &mut Client, data: &str) -> String {
+        client.send(data);
+        while !client.ready() {
+            stub.before_instruction(cpu);
+            thread::sleep(std::time::Duration::from_millis(1));
+        }
+        client.read_reply()
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        let (mut stub, mut client) = connect();
+        let mut cpu = setup();
+        assert_eq!(request(&mut stub, &mut cpu, &mut client, "?"), "S05");
+        assert_eq!(request(&mut stub, &mut cpu, &mut client, "g"), "00000024fd0006");
+        assert_eq!(request(&mut stub, &mut cpu, &mut client, "P0=7f"), "OK");
+        assert_eq!(cpu.register_a, 0x7f);
+        assert_eq!(request(&mut stub, &mut cpu, &mut client, "p5"), "0006");
+        assert_eq!(request(&mut stub, &mut cpu, &mut client, "m600,3"), "a203ca");
+        assert_eq!(request(&mut stub, &mut cpu, &mut client, "M20,2:beef"), "OK");
+        assert_eq!(cpu.memory_read(0x21), 0xef);
+        assert_eq!(request(&mut stub, &mut cpu, &mut client, "G0102032401ff00"), "OK");
+        assert_eq!(cpu.program_counter, 0x00ff);
+        assert_eq!(cpu.register_y, 0x03);
+        assert_eq!(request(&mut stub, &mut cpu, &mut client, "vMustReplyEmpty"), "");
+        assert!(request(&mut stub, &mut cpu, &mut client, "qXfer:features:read:target.xml:0,1000").starts_with("l<?xml"));
+    }
+
+    #[test]
+    fn test_breakpoint_step_and_watchpoint() {
+        let (mut stub, mut client) = connect();
+        let mut cpu = setup();
+        assert_eq!(request(&mut stub, &mut cpu, &mut client, "Z0,605,1"), "OK");
+        client.send("c");
+        run_until_stopped(&mut stub, &mut cpu);
+        assert_eq!(client.read_reply(), "S05");
+        assert_eq!(cpu.program_counter, 0x0605);
+        assert_eq!(cpu.register_x, 2);
+
+        client.send("s");
+        run_until_stopped(&mut stub, &mut cpu);
+        assert_eq!(client.read_reply(), "S05");
+        assert_eq!(cpu.program_counter, 0x0602);
+
+        assert_eq!(request(&mut stub, &mut cpu, &mut client, "z0,605,1"), "OK");
+        assert_eq!(request(&mut stub, &mut cpu, &mut client, "Z2,10,1"), "OK");
+        client.send("c");
+        run_until_stopped(&mut stub, &mut cpu);
+        assert_eq!(client.read_reply(), "T05watch:0010;");
+        assert_eq!(cpu.memory_read(0x10), 1);
+    }
+
+    #[test]
+    fn test_detach_and_disconnect() {
+        let (mut stub, mut client) = connect();
+        let mut cpu = setup();
+        assert_eq!(request(&mut stub, &mut cpu, &mut client, "Z2,10,1"), "OK");
+        assert_eq!(request(&mut stub, &mut cpu, &mut client, "D"), "OK");
+        assert!(!stub.is_attached());
+        assert!(cpu.watchpoints.points().is_empty());
+        assert_eq!(stub.before_instruction(&mut cpu), DebugState::Running);
+
+        let (mut stub, client) = connect();
+        drop(client);
+        // 断开的通知可能还在路上
+        while stub.before_instruction(&mut cpu) == DebugState::Paused {
+            thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert!(!stub.is_attached());
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(encode_hex(&[0x00, 0xab]), "00ab");
+        assert_eq!(decode_hex("00ab"), Some(vec![0x00, 0xab]));
+        assert_eq!(decode_hex("0"), None);
+        assert_eq!(decode_hex("zz"), None);
+        assert_eq!(packet_checksum(b"OK"), 0x9a);
+    }
+}
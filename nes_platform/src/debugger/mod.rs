@@ -0,0 +1,568 @@
+pub mod watch;
+pub mod gdbstub;
+
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use crate::cpu::{CPUFlags, CPU};
+use crate::trace::trace;
+use self::watch::{Access, WatchKind, Watchpoint};
+
+const JSR: u8 = 0x20;
+const RTS: u8 = 0x60;
+const RTI: u8 = 0x40;
+
+const HELP: &str = "\
+break <addr>            在 addr 处设置断点 (b)
+delete <addr>           删除断点或者从 addr 开始的监视点 (d)
+watch <addr>[-<end>] [r|w|rw]
+                        监视内存读写, 默认 rw (w)
+list                    列出断点和监视点 (l)
+step [n]                执行 n 条指令, 默认 1 (s)
+next                    执行一条指令, JSR 整个子程序一起执行完 (n)
+finish                  执行到当前子程序 RTS/RTI 返回 (f)
+continue                继续运行直到断点或监视点 (c)
+regs                    显示寄存器和标志位 (r)
+mem <addr> [len]        按十六进制显示内存, 默认 64 字节 (x)
+help                    显示帮助 (h)
+quit                    退出模拟器 (q)
+地址按十六进制解析, 可以带 $ 或 0x 前缀";
+
+/**
+调试器的命令, 从终端输入的一行文本解析得到
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Break(u16),
+    Delete(u16),
+    Watch(Watchpoint),
+    List,
+    Step(u32),
+    Next,
+    Finish,
+    Continue,
+    Registers,
+    Memory(u16, u16),
+    Help,
+    Quit,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().ok_or_else(|| "empty command".to_string())?;
+        let args: Vec<&str> = words.collect();
+        let arg = |index: usize| args.get(index).copied().ok_or_else(|| format!("`{}` needs more arguments", name));
+        let command = match name {
+            "break" | "b" => Command::Break(parse_addr(arg(0)?)?),
+            "delete" | "d" => Command::Delete(parse_addr(arg(0)?)?),
+            "watch" | "w" => {
+                let range = arg(0)?;
+                let (start, end) = match range.split_once('-') {
+                    Some((start, end)) => (parse_addr(start)?, parse_addr(end)?),
+                    None => (parse_addr(range)?, parse_addr(range)?),
+                };
+                if end < start {
+                    return Err(format!("bad range `{}`", range));
+                }
+                let kind = match args.get(1).copied() {
+                    None | Some("rw") => WatchKind::ReadWrite,
+                    Some("r") => WatchKind::Read,
+                    Some("w") => WatchKind::Write,
+                    Some(other) => return Err(format!("bad watch kind `{}`, expected r, w or rw", other)),
+                };
+                Command::Watch(Watchpoint { start, end, kind })
+            }
+            "list" | "l" => Command::List,
+            "step" | "s" => {
+                let count = match args.first() {
+                    Some(count) => count.parse().map_err(|_| format!("bad count `{}`", count))?,
+                    None => 1,
+                };
+                Command::Step(count)
+            }
+            "next" | "n" => Command::Next,
+            "finish" | "f" => Command::Finish,
+            "continue" | "c" => Command::Continue,
+            "regs" | "r" => Command::Registers,
+            "mem" | "x" => {
+                let len = match args.get(1) {
+                    Some(len) => len.parse().map_err(|_| format!("bad length `{}`", len))?,
+                    None => 64,
+                };
+                Command::Memory(parse_addr(arg(0)?)?, len)
+            }
+            "help" | "h" | "?" => Command::Help,
+            "quit" | "q" => Command::Quit,
+            _ => return Err(format!("unknown command `{}`, type `help`", name)),
+        };
+        if args.len() > command.max_args() {
+            return Err(format!("too many arguments for `{}`", name));
+        }
+        Ok(command)
+    }
+
+    fn max_args(&self) -> usize {
+        match self {
+            Command::Break(_) | Command::Delete(_) | Command::Step(_) => 1,
+            Command::Watch(_) | Command::Memory(_, _) => 2,
+            _ => 0,
+        }
+    }
+}
+
+fn parse_addr(text: &str) -> Result<u16, String> {
+    let hex = text.strip_prefix('$').or_else(|| text.strip_prefix("0x")).unwrap_or(text);
+    u16::from_str_radix(hex, 16).map_err(|_| format!("bad address `{}`", text))
+}
+
+/**
+before_instruction 的返回值, 告诉前端这条指令能不能执行
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugState {
+    Running,
+    // 暂停中, 前端应该继续处理窗口事件, 稍后再询问
+    Paused,
+    Quit,
+}
+
+/**
+前端在每条指令之前询问调试器, 终端调试器和 GDB 远程调试都实现这个接口
+ */
+pub trait DebugHook {
+    fn before_instruction(&mut self, cpu: &mut CPU) -> DebugState;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Running,
+    Paused,
+    // 还要执行多少条指令
+    Step(u32),
+    // 跳过 JSR 调用的子程序, 在返回地址并且栈恢复时停下
+    StepOver { return_pc: u16, stack_pointer: u8 },
+    // 执行 RTS/RTI 之后栈指针高于进入时的值, 说明从当前子程序返回了
+    StepOut { stack_pointer: u8 },
+}
+
+/**
+终端调试器: 在 run_with_callback 的回调里每条指令之前调用 before_instruction
+命令从 Receiver 中读取, 不会阻塞模拟循环, 所以 SDL 窗口在暂停时仍然可以响应事件
+ */
+pub struct Debugger {
+    commands: Receiver<String>,
+    output: Box<dyn Write>,
+    breakpoints: BTreeSet<u16>,
+    mode: Mode,
+    // 从断点处继续运行时, 第一条指令不再触发这个断点
+    skip_breakpoint: Option<u16>,
+    // 上一条已经执行的指令的地址和操作码, 用于报告监视点和判断子程序返回
+    last_pc: u16,
+    last_opcode: u8,
+    // 暂停的位置是否已经打印过
+    shown_pause: bool,
+}
+
+impl Debugger {
+    pub fn new(commands: Receiver<String>, output: Box<dyn Write>) -> Self {
+        Debugger {
+            commands,
+            output,
+            breakpoints: BTreeSet::new(),
+            mode: Mode::Paused,
+            skip_breakpoint: None,
+            last_pc: 0,
+            last_opcode: 0,
+            shown_pause: false,
+        }
+    }
+
+    /*
+    从标准输入读取命令的调试器, 读取在单独的线程中进行
+    创建之后处于暂停状态, 可以先设置断点再 continue
+     */
+    pub fn stdin() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Debugger::new(receiver, Box::new(io::stdout()))
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.mode == Mode::Paused
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &u16> {
+        self.breakpoints.iter()
+    }
+
+    pub fn execute(&mut self, cpu: &mut CPU, command: Command) {
+        let pc = cpu.program_counter;
+        match command {
+            Command::Break(addr) => {
+                self.breakpoints.insert(addr);
+                self.print(&format!("breakpoint at ${:04X}", addr));
+            }
+            Command::Delete(addr) => {
+                let removed = self.breakpoints.remove(&addr) | cpu.watchpoints.remove(addr);
+                if !removed {
+                    self.print(&format!("nothing at ${:04X}", addr));
+                }
+            }
+            Command::Watch(watchpoint) => {
+                cpu.watchpoints.add(watchpoint);
+                self.print(&format!("watchpoint {}", watchpoint));
+            }
+            Command::List => {
+                let mut lines: Vec<String> = self.breakpoints.iter().map(|addr| format!("break ${:04X}", addr)).collect();
+                lines.extend(cpu.watchpoints.points().iter().map(|point| format!("watch {}", point)));
+                if lines.is_empty() {
+                    lines.push("no breakpoints or watchpoints".to_string());
+                }
+                self.print(&lines.join("\n"));
+            }
+            Command::Step(count) => self.resume(pc, Mode::Step(count)),
+            Command::Next => {
+                let mode = if cpu.bus.peek(pc) == JSR {
+                    Mode::StepOver { return_pc: pc.wrapping_add(3), stack_pointer: cpu.stack_pointer }
+                } else {
+                    Mode::Step(1)
+                };
+                self.resume(pc, mode);
+            }
+            Command::Finish => self.resume(pc, Mode::StepOut { stack_pointer: cpu.stack_pointer }),
+            Command::Continue => self.resume(pc, Mode::Running),
+            Command::Registers => {
+                let line = registers(cpu);
+                self.print(&line);
+            }
+            Command::Memory(addr, len) => {
+                let dump = hexdump(cpu, addr, len);
+                self.print(&dump);
+            }
+            Command::Help => self.print(HELP),
+            Command::Quit => {}
+        }
+    }
+
+    fn resume(&mut self, pc: u16, mode: Mode) {
+        self.mode = mode;
+        self.skip_breakpoint = Some(pc);
+        self.shown_pause = false;
+    }
+
+    fn print(&mut self, text: &str) {
+        // 终端关闭时输出失败也不影响模拟
+        let _ = writeln!(self.output, "{}", text);
+        let _ = self.output.flush();
+    }
+}
+
+impl DebugHook for Debugger {
+    /*
+    在每条指令之前调用: 先处理已经输入的命令, 再检查监视点, 断点和单步的条件
+     */
+    fn before_instruction(&mut self, cpu: &mut CPU) -> DebugState {
+        loop {
+            match self.commands.try_recv() {
+                Ok(line) if line.trim().is_empty() => {}
+                Ok(line) => match Command::parse(&line) {
+                    Ok(Command::Quit) => return DebugState::Quit,
+                    Ok(command) => self.execute(cpu, command),
+                    Err(message) => self.print(&message),
+                },
+                Err(TryRecvError::Empty) => break,
+                // 输入结束 (比如 stdin 被关闭) 时不再暂停, 让程序继续运行
+                Err(TryRecvError::Disconnected) => {
+                    if self.is_paused() {
+                        self.resume(cpu.program_counter, Mode::Running);
+                    }
+                    break;
+                }
+            }
+        }
+
+        let pc = cpu.program_counter;
+        let hits = cpu.watchpoints.take_hits();
+        let reason = if !hits.is_empty() {
+            let last_pc = self.last_pc;
+            Some(
+                hits.iter()
+                    .map(|hit| {
+                        let access = match hit.access {
+                            Access::Read => "read",
+                            Access::Write => "write",
+                        };
+                        format!("watchpoint: {} ${:04X} = ${:02X} at ${:04X}", access, hit.addr, hit.data, last_pc)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )
+        } else if self.breakpoints.contains(&pc) && self.skip_breakpoint != Some(pc) && !self.is_paused() {
+            Some(format!("breakpoint at ${:04X}", pc))
+        } else {
+            match self.mode {
+                Mode::Step(0) => Some(String::new()),
+                Mode::StepOver { return_pc, stack_pointer } if pc == return_pc && cpu.stack_pointer == stack_pointer => Some(String::new()),
+                Mode::StepOut { stack_pointer }
+                    if matches!(self.last_opcode, RTS | RTI) && cpu.stack_pointer > stack_pointer =>
+                {
+                    Some(String::new())
+                }
+                _ => None,
+            }
+        };
+        self.skip_breakpoint = None;
+
+        if let Some(reason) = reason {
+            self.mode = Mode::Paused;
+            if !reason.is_empty() {
+                self.print(&reason);
+            }
+        }
+        if self.is_paused() {
+            if !self.shown_pause {
+                self.shown_pause = true;
+                let line = trace(cpu);
+                self.print(&line);
+            }
+            return DebugState::Paused;
+        }
+
+        if let Mode::Step(count) = self.mode {
+            self.mode = Mode::Step(count - 1);
+        }
+        self.last_pc = pc;
+        self.last_opcode = cpu.bus.peek(pc);
+        DebugState::Running
+    }
+}
+
+/**
+寄存器和标志位, 大写字母表示标志位被设置
+ A:00 X:00 Y:00 SP:FD PC:0600 P:24 [nv-bdIzc] CYC:7
+ */
+pub fn registers(cpu: &CPU) -> String {
+    let flags = [
+        (CPUFlags::NEGATIV, 'n'),
+        (CPUFlags::OVERFLOW, 'v'),
+        (CPUFlags::BREAK2, '-'),
+        (CPUFlags::BREAK, 'b'),
+        (CPUFlags::DECIMAL_MODE, 'd'),
+        (CPUFlags::INTERRUPT_DISABLE, 'i'),
+        (CPUFlags::ZERO, 'z'),
+        (CPUFlags::CARRY, 'c'),
+    ];
+    let flags: String = flags
+        .iter()
+        .map(|(flag, name)| if cpu.status.contains(*flag) { name.to_ascii_uppercase() } else { *name })
+        .collect();
+    format!(
+        "A:{:02X} X:{:02X} Y:{:02X} SP:{:02X} PC:{:04X} P:{:02X} [{}] CYC:{}",
+        cpu.register_a,
+        cpu.register_x,
+        cpu.register_y,
+        cpu.stack_pointer,
+        cpu.program_counter,
+        cpu.status.bits(),
+        flags,
+        cpu.cycles
+    )
+}
+
+/**
+每行 16 个字节, 通过 Bus::peek 读取, 查看 PPU 和 APU 寄存器不会产生副作用
+ 0200  01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F 10  ................
+ */
+pub fn hexdump(cpu: &mut CPU, addr: u16, len: u16) -> String {
+    let bytes: Vec<u8> = (0..len as u32).map(|i| cpu.bus.peek(addr.wrapping_add(i as u16))).collect();
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let hex = chunk.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                .collect();
+            format!("{:04X}  {:47}  {}", addr.wrapping_add(row as u16 * 16), hex, ascii)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::mpsc::Sender;
+
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedOutput {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.borrow_mut())).unwrap()
+        }
+    }
+
+    /*
+    子程序 add3 被调用两次, 结果写入 $10
+     */
+    fn setup() -> (CPU, Debugger, Sender<String>, SharedOutput) {
+        let mut cpu = CPU::new();
+        cpu.halt_on_brk = true;
+        cpu.memory_load_program(crate::asm!(
+            "main:   LDA #$01",   // 0600
+            "        JSR add3",   // 0602
+            "        JSR add3",   // 0605
+            "        STA $10",    // 0608
+            "        BRK",        // 060A
+            "add3:   CLC",        // 060B
+            "        ADC #$03",   // 060C
+            "        RTS",        // 060E
+        )).unwrap();
+        cpu.reset();
+        let (sender, receiver) = mpsc::channel();
+        let output = SharedOutput::default();
+        let debugger = Debugger::new(receiver, Box::new(output.clone()));
+        (cpu, debugger, sender, output)
+    }
+
+    // 像前端一样运行, 直到暂停或者 BRK 停机
+    fn run_until_paused(cpu: &mut CPU, debugger: &mut Debugger) -> DebugState {
+        loop {
+            match debugger.before_instruction(cpu) {
+                DebugState::Running => {
+                    if cpu.step().unwrap().brk {
+                        return DebugState::Running;
+                    }
+                }
+                state => return state,
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(Command::parse("b $0600"), Ok(Command::Break(0x0600)));
+        assert_eq!(Command::parse("break 0x8000"), Ok(Command::Break(0x8000)));
+        assert_eq!(Command::parse("s 5"), Ok(Command::Step(5)));
+        assert_eq!(Command::parse("x 200 16"), Ok(Command::Memory(0x0200, 16)));
+        assert_eq!(
+            Command::parse("watch 0200-02ff w"),
+            Ok(Command::Watch(Watchpoint { start: 0x0200, end: 0x02ff, kind: WatchKind::Write }))
+        );
+        assert!(Command::parse("watch 0300-0200").is_err());
+        assert!(Command::parse("break").is_err());
+        assert!(Command::parse("break zz").is_err());
+        assert!(Command::parse("step 1 2").is_err());
+        assert!(Command::parse("jump").is_err());
+    }
+
+    #[test]
+    fn test_breakpoint_and_continue() {
+        let (mut cpu, mut debugger, sender, output) = setup();
+        assert_eq!(debugger.before_instruction(&mut cpu), DebugState::Paused);
+        assert!(output.take().starts_with("0600  A9 01"));
+
+        sender.send("b 060c".to_string()).unwrap();
+        sender.send("c".to_string()).unwrap();
+        assert_eq!(run_until_paused(&mut cpu, &mut debugger), DebugState::Paused);
+        assert_eq!(cpu.program_counter, 0x060c);
+        assert!(output.take().contains("breakpoint at $060C"));
+
+        // 继续运行时不会马上停在当前的断点上, 第二次调用时再停下
+        sender.send("c".to_string()).unwrap();
+        assert_eq!(run_until_paused(&mut cpu, &mut debugger), DebugState::Paused);
+        assert_eq!(cpu.program_counter, 0x060c);
+        assert_eq!(cpu.register_a, 0x04);
+
+        sender.send("d 060c".to_string()).unwrap();
+        sender.send("c".to_string()).unwrap();
+        assert_eq!(run_until_paused(&mut cpu, &mut debugger), DebugState::Running);
+        assert_eq!(cpu.memory_read(0x10), 0x07);
+    }
+
+    #[test]
+    fn test_step_over_and_out() {
+        let (mut cpu, mut debugger, sender, _output) = setup();
+        sender.send("s".to_string()).unwrap();
+        run_until_paused(&mut cpu, &mut debugger);
+        assert_eq!(cpu.program_counter, 0x0602);
+
+        // next 把整个子程序执行完
+        sender.send("n".to_string()).unwrap();
+        run_until_paused(&mut cpu, &mut debugger);
+        assert_eq!(cpu.program_counter, 0x0605);
+        assert_eq!(cpu.register_a, 0x04);
+
+        // 进入子程序之后 finish 回到调用者
+        sender.send("s 2".to_string()).unwrap();
+        run_until_paused(&mut cpu, &mut debugger);
+        assert_eq!(cpu.program_counter, 0x060c);
+        sender.send("f".to_string()).unwrap();
+        run_until_paused(&mut cpu, &mut debugger);
+        assert_eq!(cpu.program_counter, 0x0608);
+        assert_eq!(cpu.register_a, 0x07);
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let (mut cpu, mut debugger, sender, output) = setup();
+        sender.send("w 10 w".to_string()).unwrap();
+        sender.send("c".to_string()).unwrap();
+        assert_eq!(run_until_paused(&mut cpu, &mut debugger), DebugState::Paused);
+        // 停在写入 $10 的 STA 之后
+        assert_eq!(cpu.program_counter, 0x060a);
+        assert!(output.take().contains("watchpoint: write $0010 = $07 at $0608"));
+
+        // 只读监视点不会被写入触发, 调试器查看内存也不会触发
+        let mut watchpoints = watch::Watchpoints::default();
+        watchpoints.add(Watchpoint { start: 0x10, end: 0x10, kind: WatchKind::Read });
+        watchpoints.check(0x10, 1, Access::Write);
+        assert!(watchpoints.take_hits().is_empty());
+        watchpoints.check(0x10, 1, Access::Read);
+        assert_eq!(watchpoints.take_hits().len(), 1);
+        sender.send("x 10 1".to_string()).unwrap();
+        assert_eq!(debugger.before_instruction(&mut cpu), DebugState::Paused);
+        assert!(cpu.watchpoints.take_hits().is_empty());
+        assert_eq!(output.take(), "0010  07                                               .\n");
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut cpu = CPU::new();
+        cpu.register_a = 0x12;
+        cpu.status = CPUFlags::from_bits_truncate(0b1010_0101);
+        cpu.program_counter = 0x8000;
+        assert_eq!(registers(&cpu), "A:12 X:00 Y:00 SP:FD PC:8000 P:A5 [Nv-bdIzC] CYC:0");
+    }
+
+    #[test]
+    fn test_quit() {
+        let (mut cpu, mut debugger, sender, _output) = setup();
+        sender.send("q".to_string()).unwrap();
+        assert_eq!(debugger.before_instruction(&mut cpu), DebugState::Quit);
+    }
+}
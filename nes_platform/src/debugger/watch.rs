@@ -0,0 +1,110 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/**
+监视点关心的访问类型
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    fn matches(&self, access: Access) -> bool {
+        matches!(
+            (self, access),
+            (WatchKind::ReadWrite, _) | (WatchKind::Read, Access::Read) | (WatchKind::Write, Access::Write)
+        )
+    }
+}
+
+/**
+监视 [start, end] 之间的地址 (包含两端)
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub kind: WatchKind,
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            WatchKind::Read => "r",
+            WatchKind::Write => "w",
+            WatchKind::ReadWrite => "rw",
+        };
+        if self.start == self.end {
+            write!(f, "${:04X} {}", self.start, kind)
+        } else {
+            write!(f, "${:04X}-${:04X} {}", self.start, self.end, kind)
+        }
+    }
+}
+
+/**
+一次命中监视点的内存访问
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub addr: u16,
+    pub data: u8,
+    pub access: Access,
+}
+
+/*
+CPU::memory_read 和 CPU::memory_write 在每次访问时调用 check, 命中的访问先记下来,
+由调试器在下一条指令之前取走并暂停. 取指和读操作数也经过 memory_read, 同样会触发读监视点
+Bus::peek 不经过这里, 调试器自己查看内存不会触发监视点
+ */
+#[derive(Debug, Default)]
+pub struct Watchpoints {
+    points: Vec<Watchpoint>,
+    hits: Vec<WatchHit>,
+}
+
+impl Watchpoints {
+    pub fn add(&mut self, watchpoint: Watchpoint) {
+        if !self.points.contains(&watchpoint) {
+            self.points.push(watchpoint);
+        }
+    }
+
+    // 删除从 start 开始的监视点, 返回是否删除了
+    pub fn remove(&mut self, start: u16) -> bool {
+        let len = self.points.len();
+        self.points.retain(|point| point.start != start);
+        self.points.len() != len
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+        self.hits.clear();
+    }
+
+    pub fn points(&self) -> &[Watchpoint] {
+        &self.points
+    }
+
+    #[inline]
+    pub fn check(&mut self, addr: u16, data: u8, access: Access) {
+        if self.points.is_empty() {
+            return;
+        }
+        if self.points.iter().any(|point| point.kind.matches(access) && (point.start..=point.end).contains(&addr)) {
+            self.hits.push(WatchHit { addr, data, access });
+        }
+    }
+
+    pub fn take_hits(&mut self) -> Vec<WatchHit> {
+        std::mem::take(&mut self.hits)
+    }
+}
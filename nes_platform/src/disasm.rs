@@ -0,0 +1,210 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::instruction::addressing::{AddressingMode, OpCode};
+use crate::cpu::CpuVariant;
+use crate::instruction::instruction_set;
+
+/**
+反汇编出来的一行
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisasmLine {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    // 不认识的操作码和末尾不完整的指令输出成 .byte
+    pub mnemonic: &'static str,
+    pub operand: String,
+    // 分支, JSR 和 JMP 的目标地址
+    pub target: Option<u16>,
+    // 这一行被其它指令跳转到时的标签
+    pub label: Option<String>,
+}
+
+impl fmt::Display for DisasmLine {
+    /*
+    0600  20 06 06  L0600:  JSR L0606
+     */
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = self.bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
+        let label = self.label.as_ref().map(|label| format!("{}:", label)).unwrap_or_default();
+        let line = format!("{:04X}  {:8}  {:7} {} {}", self.address, hex, label, self.mnemonic, self.operand);
+        write!(f, "{}", line.trim_end())
+    }
+}
+
+fn label_name(address: u16) -> String {
+    format!("L{:04X}", address)
+}
+
+/*
+把从 origin 开始的一段机器码反汇编成汇编指令, 跳转目标在这段代码里面时用标签代替地址
+ */
+pub fn disassemble(bytes: &[u8], origin: u16) -> Vec<DisasmLine> {
+    disassemble_for(bytes, origin, CpuVariant::default())
+}
+
+// 按指定 CPU 型号的指令表反汇编, 65C02 的新指令在 NES 的 2A03 上是不认识的操作码
+pub fn disassemble_for(bytes: &[u8], origin: u16, variant: CpuVariant) -> Vec<DisasmLine> {
+    let builtins = instruction_set(variant);
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let address = origin.wrapping_add(offset as u16);
+        let code = bytes[offset];
+        let line = match builtins.get(&code) {
+            Some(builtin) if offset + builtin.op.len as usize <= bytes.len() => {
+                let instruction = &bytes[offset..offset + builtin.op.len as usize];
+                decode(&builtin.op, address, instruction)
+            }
+            _ => DisasmLine {
+                address,
+                bytes: vec![code],
+                mnemonic: ".byte",
+                operand: format!("${:02X}", code),
+                target: None,
+                label: None,
+            },
+        };
+        offset += line.bytes.len();
+        lines.push(line);
+    }
+
+    // 第二遍: 给跳转目标加上标签, 把操作数中的地址换成标签
+    let end = origin as u32 + bytes.len() as u32;
+    let in_range = |address: u16| (origin as u32..end).contains(&(address as u32));
+    let starts: BTreeSet<u16> = lines.iter().map(|line| line.address).collect();
+    let targets: BTreeSet<u16> = lines
+        .iter()
+        .filter_map(|line| line.target)
+        .filter(|target| in_range(*target) && starts.contains(target))
+        .collect();
+    for line in lines.iter_mut() {
+        if targets.contains(&line.address) {
+            line.label = Some(label_name(line.address));
+        }
+        if let Some(target) = line.target {
+            if targets.contains(&target) {
+                line.operand = line.operand.replace(&format!("${:04X}", target), &label_name(target));
+            }
+        }
+    }
+    lines
+}
+
+fn decode(op: &OpCode, address: u16, bytes: &[u8]) -> DisasmLine {
+    let byte = bytes.get(1).copied().unwrap_or(0);
+    let word = u16::from_le_bytes([byte, bytes.get(2).copied().unwrap_or(0)]);
+    let mut target = None;
+    let operand = match op.mode {
+        AddressingMode::NoneAddressing => String::new(),
+        AddressingMode::Accumulator => "A".to_string(),
+        AddressingMode::Immediate => format!("#${:02X}", byte),
+        AddressingMode::ZeroPage => format!("${:02X}", byte),
+        AddressingMode::ZeroPage_X => format!("${:02X},X", byte),
+        AddressingMode::ZeroPage_Y => format!("${:02X},Y", byte),
+        AddressingMode::Absolute => {
+            if op.mnemonic == "JMP" || op.mnemonic == "JSR" {
+                target = Some(word);
+            }
+            format!("${:04X}", word)
+        }
+        AddressingMode::Absolute_X => format!("${:04X},X", word),
+        AddressingMode::Absolute_Y => format!("${:04X},Y", word),
+        AddressingMode::Indirect => format!("(${:04X})", word),
+        AddressingMode::Indirect_X => format!("(${:02X},X)", byte),
+        AddressingMode::Indirect_Y => format!("(${:02X}),Y", byte),
+        AddressingMode::Indirect_ZeroPage => format!("(${:02X})", byte),
+        AddressingMode::Indirect_Absolute_X => format!("(${:04X},X)", word),
+        AddressingMode::ZeroPage_Relative => {
+            let offset = bytes.get(2).copied().unwrap_or(0);
+            let address = address.wrapping_add(3).wrapping_add(offset as i8 as u16);
+            target = Some(address);
+            format!("${:02X},${:04X}", byte, address)
+        }
+        AddressingMode::Relative => {
+            let address = address.wrapping_add(2).wrapping_add(byte as i8 as u16);
+            target = Some(address);
+            format!("${:04X}", address)
+        }
+    };
+    DisasmLine { address, bytes: bytes.to_vec(), mnemonic: op.mnemonic, operand, target, label: None }
+}
+
+// 整段反汇编的文本, 每行一条指令
+pub fn format_listing(lines: &[DisasmLine]) -> String {
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::snake::{SNAKE_GAME_CODE, SNAKE_LOAD_ADDRESS};
+
+    #[test]
+    fn test_addressing_mode_syntax() {
+        let program = [
+            0xb1, 0x30, // LDA ($30),Y
+            0x6c, 0x34, 0x12, // JMP ($1234)
+            0xa1, 0x10, // LDA ($10,X)
+            0x0a, // ASL A
+            0xbd, 0x00, 0x30, // LDA $3000,X
+            0xb6, 0x20, // LDX $20,Y
+            0xa9, 0x0f, // LDA #$0F
+        ];
+        let lines = disassemble(&program, 0x8000);
+        let text: Vec<String> = lines.iter().map(|line| format!("{} {}", line.mnemonic, line.operand).trim().to_string()).collect();
+        assert_eq!(
+            text,
+            vec!["LDA ($30),Y", "JMP ($1234)", "LDA ($10,X)", "ASL A", "LDA $3000,X", "LDX $20,Y", "LDA #$0F"]
+        );
+    }
+
+    #[test]
+    fn test_cmos_instructions() {
+        let program = [
+            0xb2, 0x30, // LDA ($30)
+            0x7c, 0x34, 0x12, // JMP ($1234,X)
+            0x8f, 0x10, 0xfd, // BBS0 $10,$8005
+        ];
+        let lines = disassemble_for(&program, 0x8000, CpuVariant::Cmos65C02);
+        let text: Vec<String> = lines.iter().map(|line| format!("{} {}", line.mnemonic, line.operand).trim().to_string()).collect();
+        assert_eq!(text, vec!["LDA ($30)", "JMP ($1234,X)", "BBS0 $10,L8005"]);
+        // NES 的 2A03 不认识 0xB2
+        assert_eq!(disassemble(&program, 0x8000)[0].mnemonic, ".byte");
+    }
+
+    #[test]
+    fn test_labels_for_branch_and_jsr_targets() {
+        // loop: DEX; BNE loop; JSR $9000; RTS
+        let lines = disassemble(&[0xca, 0xd0, 0xfd, 0x20, 0x00, 0x90, 0x60], 0x8000);
+        assert_eq!(lines[0].label, Some("L8000".to_string()));
+        assert_eq!(lines[1].operand, "L8000");
+        assert_eq!(lines[1].target, Some(0x8000));
+        // 目标不在这段代码里, 保留地址
+        assert_eq!(lines[2].operand, "$9000");
+        assert_eq!(lines[0].to_string(), "8000  CA        L8000:  DEX");
+        assert_eq!(lines[1].to_string(), "8001  D0 FD             BNE L8000");
+    }
+
+    #[test]
+    fn test_unknown_and_truncated_bytes() {
+        let lines = disassemble(&[0x02, 0xad, 0x00], 0x0000);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].mnemonic, ".byte");
+        assert_eq!(lines[0].operand, "$02");
+        assert_eq!(lines[1].operand, "$AD");
+    }
+
+    #[test]
+    fn test_disassemble_snake() {
+        let lines = disassemble(&SNAKE_GAME_CODE, SNAKE_LOAD_ADDRESS);
+        let listing = format_listing(&lines);
+        assert!(listing.starts_with("0600  20 06 06          JSR L0606\n0603  20 38 06          JSR L0638\n"));
+        // 所有字节都被解码成了指令
+        assert!(lines.iter().all(|line| line.mnemonic != ".byte"));
+        let last = lines.last().unwrap();
+        assert_eq!(last.to_string(), "0734  60                RTS");
+        assert!(lines.iter().any(|line| line.to_string().ends_with("L060D:  LDA #$02")));
+    }
+}
@@ -0,0 +1,122 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::checksum::{adler32, crc32};
+use crate::ppu::frame::Frame;
+
+/*
+把 PPU 的画面保存成图片, 不依赖任何图片库
+ PPM (P6): 文本头加上原始的 RGB 数据
+ PNG: 不压缩的 deflate 块, 文件比较大, 但是任何看图软件都能打开
+ */
+pub fn write_ppm<W: Write>(writer: &mut W, frame: &Frame) -> io::Result<()> {
+    write!(writer, "P6\n{} {}\n255\n", Frame::WIDTH, Frame::HEIGHT)?;
+    writer.write_all(&frame.data)
+}
+
+pub fn write_png<W: Write>(writer: &mut W, frame: &Frame) -> io::Result<()> {
+    writer.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(Frame::WIDTH as u32).to_be_bytes());
+    header.extend_from_slice(&(Frame::HEIGHT as u32).to_be_bytes());
+    // 8 位深度, 颜色类型 2 (RGB), 默认压缩, 默认过滤, 不隔行
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_png_chunk(writer, b"IHDR", &header)?;
+
+    // 每一行前面加一个过滤类型字节 0 (不过滤)
+    let row_len = Frame::WIDTH * 3;
+    let mut raw = Vec::with_capacity((row_len + 1) * Frame::HEIGHT);
+    for row in frame.data.chunks(row_len) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    write_png_chunk(writer, b"IDAT", &zlib_stored(&raw))?;
+    write_png_chunk(writer, b"IEND", &[])
+}
+
+fn write_png_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut crc_data = Vec::with_capacity(4 + data.len());
+    crc_data.extend_from_slice(kind);
+    crc_data.extend_from_slice(data);
+    writer.write_all(&crc_data)?;
+    writer.write_all(&crc32(&crc_data).to_be_bytes())
+}
+
+/*
+zlib 数据流, 内容是不压缩的 deflate 块, 每块最多 65535 字节
+ */
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        out.push(last as u8);
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/*
+按扩展名选择格式, .png 保存成 PNG, 其它保存成 PPM
+ */
+pub fn save_frame<P: AsRef<Path>>(path: P, frame: &Frame) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut writer = BufWriter::new(File::create(path)?);
+    let is_png = path.extension().map(|ext| ext.eq_ignore_ascii_case("png")).unwrap_or(false);
+    if is_png {
+        write_png(&mut writer, frame)?;
+    } else {
+        write_ppm(&mut writer, frame)?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_write_ppm() {
+        let mut frame = Frame::new();
+        frame.set_pixel(1, 0, (1, 2, 3));
+        let mut buffer = Vec::new();
+        write_ppm(&mut buffer, &frame).unwrap();
+        let header = b"P6\n256 240\n255\n";
+        assert_eq!(&buffer[..header.len()], header);
+        assert_eq!(buffer.len(), header.len() + Frame::WIDTH * Frame::HEIGHT * 3);
+        assert_eq!(&buffer[header.len() + 3..header.len() + 6], &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_write_png() {
+        let frame = Frame::new();
+        let mut buffer = Vec::new();
+        write_png(&mut buffer, &frame).unwrap();
+        assert_eq!(&buffer[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&buffer[12..16], b"IHDR");
+        assert_eq!(&buffer[buffer.len() - 8..buffer.len() - 4], b"IEND");
+        // IHDR 的 CRC
+        let crc = u32::from_be_bytes([buffer[29], buffer[30], buffer[31], buffer[32]]);
+        assert_eq!(crc, crc32(&buffer[12..29]));
+    }
+
+    #[test]
+    fn test_zlib_stored_blocks() {
+        let data = vec![7u8; 0x10000];
+        let out = zlib_stored(&data);
+        // 两个块, 第一个不是最后一块
+        assert_eq!(out[2], 0);
+        assert_eq!(out[2 + 5 + 0xFFFF], 1);
+        assert_eq!(out.len(), 2 + 5 + 0xFFFF + 5 + 1 + 4);
+    }
+}
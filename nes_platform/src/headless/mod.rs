@@ -0,0 +1,393 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::bus::NesBus;
+use crate::cartridge::{Cartridge, CartridgeError};
+use crate::cpu::{CpuError, CPU};
+use crate::joypad::{Joypad, JoypadButton};
+use crate::memory::{Memory, ProgramTooLarge};
+use crate::movie::Movie;
+use crate::ppu::frame::Frame;
+use crate::trace::trace;
+
+pub mod image;
+
+// 没有 PPU 的原始程序按 NTSC 每帧的 CPU 周期数来划分帧
+pub const NTSC_CYCLES_PER_FRAME: u64 = 29_781;
+
+/**
+无界面运行时的停止条件
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunLimit {
+    Frames(u64),
+    Steps(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "input script line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ScriptError {}
+
+/**
+无界面运行出错: 写跟踪日志失败, 或者 CPU 无法继续执行
+ */
+#[derive(Debug)]
+pub enum RunError {
+    Io(io::Error),
+    Cpu(CpuError),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::Io(e) => write!(f, "trace: {}", e),
+            RunError::Cpu(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for RunError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RunError::Io(e) => Some(e),
+            RunError::Cpu(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for RunError {
+    fn from(e: io::Error) -> Self {
+        RunError::Io(e)
+    }
+}
+
+impl From<CpuError> for RunError {
+    fn from(e: CpuError) -> Self {
+        RunError::Cpu(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct InputEvent {
+    frame: u64,
+    player: usize,
+    buttons: JoypadButton,
+}
+
+/**
+输入脚本, 在指定的帧设置某个手柄的按键状态, 一直保持到下一次设置
+每行的格式是 `<帧号> <玩家 1|2> <按键>`, 多个按键用逗号分隔, `-` 表示全部松开, `#` 开头的是注释:
+ # 第 30 帧按下 Start, 第 32 帧松开
+ 30 1 start
+ 32 1 -
+ 90 1 right,a
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputScript {
+    events: Vec<InputEvent>,
+}
+
+impl InputScript {
+    pub fn parse(text: &str) -> Result<Self, ScriptError> {
+        let mut events = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| ScriptError { line: index + 1, message };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 3 {
+                return Err(error(format!("expected `<frame> <player> <buttons>`, got `{}`", line)));
+            }
+            let frame = fields[0].parse::<u64>().map_err(|e| error(format!("bad frame `{}`: {}", fields[0], e)))?;
+            let player = match fields[1] {
+                "1" => 0,
+                "2" => 1,
+                other => return Err(error(format!("player must be 1 or 2, got `{}`", other))),
+            };
+            let mut buttons = JoypadButton::empty();
+            if fields[2] != "-" {
+                for name in fields[2].split(',') {
+                    buttons |= parse_button(name).ok_or_else(|| error(format!("unknown button `{}`", name)))?;
+                }
+            }
+            events.push(InputEvent { frame, player, buttons });
+        }
+        // 同一帧的多次设置保持脚本中的顺序
+        events.sort_by_key(|event| event.frame);
+        Ok(InputScript { events })
+    }
+
+    /*
+    把录像转换成输入脚本, 只在按键变化的帧上设置, 用来在无界面运行器里回放录像
+     */
+    pub fn from_movie(movie: &Movie) -> Self {
+        let mut events = Vec::new();
+        let mut previous = [JoypadButton::empty(); 2];
+        let mut frame = 0;
+        while let Some(input) = movie.input(frame) {
+            for player in 0..2 {
+                if input[player] != previous[player] {
+                    events.push(InputEvent { frame, player, buttons: input[player] });
+                }
+            }
+            previous = input;
+            frame += 1;
+        }
+        InputScript { events }
+    }
+
+    // 把第 frame 帧的设置应用到手柄上
+    pub fn apply(&self, frame: u64, joypads: &mut [Joypad; 2]) {
+        for event in self.events.iter().filter(|event| event.frame == frame) {
+            joypads[event.player].button_status = event.buttons;
+        }
+    }
+}
+
+fn parse_button(name: &str) -> Option<JoypadButton> {
+    match name.to_ascii_lowercase().as_str() {
+        "a" => Some(JoypadButton::BUTTON_A),
+        "b" => Some(JoypadButton::BUTTON_B),
+        "select" => Some(JoypadButton::SELECT),
+        "start" => Some(JoypadButton::START),
+        "up" => Some(JoypadButton::UP),
+        "down" => Some(JoypadButton::DOWN),
+        "left" => Some(JoypadButton::LEFT),
+        "right" => Some(JoypadButton::RIGHT),
+        _ => None,
+    }
+}
+
+/**
+不打开窗口, 运行卡带或者原始的 6502 程序, 用于在没有显示器的机器上做回归测试
+ */
+pub struct Headless {
+    pub cpu: CPU,
+    // 已经完成的帧数和执行的指令数
+    pub frames: u64,
+    pub steps: u64,
+    // 是否因为 BRK 停机
+    pub halted: bool,
+    frame_start_cycles: u64,
+    // 打开跟踪时每条指令执行前写一行 nestest 格式的日志
+    trace: Option<Box<dyn Write>>,
+}
+
+impl Headless {
+    pub fn with_rom(cartridge: Cartridge) -> Result<Self, CartridgeError> {
+        let mut cpu = CPU::with_bus(Box::new(NesBus::new(cartridge)?));
+        cpu.reset();
+        Ok(Headless::with_cpu(cpu))
+    }
+
+    /*
+    原始程序加载到平坦的 64KB 内存中, 从加载地址开始执行, 遇到 BRK 停机
+     */
+    pub fn with_program(program: &[u8], load_addr: u16) -> Result<Self, ProgramTooLarge> {
+        let mut memory = Memory::default();
+        memory.load_program(load_addr, program.to_vec())?;
+        let mut cpu = CPU::with_bus(Box::new(memory));
+        cpu.halt_on_brk = true;
+        cpu.program_counter = load_addr;
+        Ok(Headless::with_cpu(cpu))
+    }
+
+    fn with_cpu(cpu: CPU) -> Self {
+        let frame_start_cycles = cpu.cycles;
+        Headless { cpu, frames: 0, steps: 0, halted: false, frame_start_cycles, trace: None }
+    }
+
+    pub fn set_trace(&mut self, writer: Box<dyn Write>) {
+        self.trace = Some(writer);
+    }
+
+    /*
+    CPU 出错时停止运行, 已经写出的跟踪日志仍然会刷新到文件里, 方便查看出错之前执行了什么
+     */
+    pub fn run(&mut self, limit: RunLimit, script: &InputScript) -> Result<(), RunError> {
+        if self.steps == 0 {
+            self.apply_input(script);
+        }
+        while !self.halted {
+            match limit {
+                RunLimit::Frames(frames) if self.frames >= frames => break,
+                RunLimit::Steps(steps) if self.steps >= steps => break,
+                _ => {}
+            }
+            if let Some(writer) = &mut self.trace {
+                writeln!(writer, "{}", trace(&mut self.cpu))?;
+            }
+            let step = match self.cpu.step() {
+                Ok(step) => step,
+                Err(e) => {
+                    self.flush_trace()?;
+                    return Err(e.into());
+                }
+            };
+            self.halted = step.brk;
+            self.steps += 1;
+            if self.frame_finished() {
+                self.frames += 1;
+                self.apply_input(script);
+            }
+        }
+        Ok(self.flush_trace()?)
+    }
+
+    fn flush_trace(&mut self) -> io::Result<()> {
+        match &mut self.trace {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn frame_finished(&mut self) -> bool {
+        let cycles = self.cpu.cycles;
+        match self.cpu.bus_mut::<NesBus>() {
+            Some(bus) => {
+                // 没有人播放声音, 丢掉采样
+                bus.apu.take_samples();
+                bus.ppu.frame_complete()
+            }
+            None if cycles - self.frame_start_cycles >= NTSC_CYCLES_PER_FRAME => {
+                self.frame_start_cycles += NTSC_CYCLES_PER_FRAME;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn apply_input(&mut self, script: &InputScript) {
+        let frame = self.frames;
+        if let Some(bus) = self.cpu.bus_mut::<NesBus>() {
+            script.apply(frame, &mut bus.joypads);
+        }
+    }
+
+    // 运行卡带时返回 PPU 的画面, 原始程序没有画面
+    pub fn frame(&mut self) -> Option<&Frame> {
+        self.cpu.bus_mut::<NesBus>().map(|bus| &bus.ppu.frame)
+    }
+
+    // 卡带返回主机的 2KB 内存, 原始程序返回整个 64KB 内存
+    pub fn ram(&mut self) -> Vec<u8> {
+        let len = if self.cpu.bus_mut::<NesBus>().is_some() { 0x0800 } else { 0x10000 };
+        (0..len).map(|addr| self.cpu.memory_read(addr as u16)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::test::ines_rom;
+
+    #[test]
+    fn test_parse_input_script() {
+        let script = InputScript::parse("# comment\n\n30 1 start\n32 1 -\n10 2 Right,A\n").unwrap();
+        let mut joypads = [Joypad::new(), Joypad::new()];
+        script.apply(10, &mut joypads);
+        assert_eq!(joypads[1].button_status, JoypadButton::RIGHT | JoypadButton::BUTTON_A);
+        script.apply(30, &mut joypads);
+        assert_eq!(joypads[0].button_status, JoypadButton::START);
+        script.apply(32, &mut joypads);
+        assert_eq!(joypads[0].button_status, JoypadButton::empty());
+    }
+
+    #[test]
+    fn test_parse_input_script_errors() {
+        assert_eq!(InputScript::parse("1 1 start\nx 1 a").unwrap_err().line, 2);
+        assert!(InputScript::parse("1 3 a").is_err());
+        assert!(InputScript::parse("1 1 turbo").is_err());
+        assert!(InputScript::parse("1 1").is_err());
+    }
+
+    #[test]
+    fn test_input_script_from_movie() {
+        let mut movie = Movie::new(0, None);
+        let mut joypads = [Joypad::new(), Joypad::new()];
+        movie.record(&joypads);
+        joypads[1].button_status = JoypadButton::BUTTON_B;
+        movie.record(&joypads);
+        movie.record(&joypads);
+        joypads[1].button_status = JoypadButton::empty();
+        movie.record(&joypads);
+        let script = InputScript::from_movie(&movie);
+        assert_eq!(script.events.len(), 2);
+
+        let mut replayed = [Joypad::new(), Joypad::new()];
+        for frame in 0..4 {
+            script.apply(frame, &mut replayed);
+            assert_eq!(replayed[1].button_status, movie.input(frame).unwrap()[1]);
+        }
+    }
+
+    #[test]
+    fn test_run_raw_program() {
+        // LDA #$01; STA $0200; INX; BRK
+        let mut headless = Headless::with_program(&[0xa9, 0x01, 0x8d, 0x00, 0x02, 0xe8, 0x00], 0x0600).unwrap();
+        headless.run(RunLimit::Steps(2), &InputScript::default()).unwrap();
+        assert_eq!(headless.steps, 2);
+        assert!(!headless.halted);
+        headless.run(RunLimit::Steps(100), &InputScript::default()).unwrap();
+        assert!(headless.halted);
+        assert_eq!(headless.cpu.register_x, 1);
+        let ram = headless.ram();
+        assert_eq!(ram.len(), 0x10000);
+        assert_eq!(ram[0x0200], 1);
+        assert!(headless.frame().is_none());
+    }
+
+    #[test]
+    fn test_run_reports_cpu_error() {
+        // INX; KIL
+        let mut headless = Headless::with_program(&[0xe8, 0x02], 0x0600).unwrap();
+        match headless.run(RunLimit::Steps(10), &InputScript::default()) {
+            Err(RunError::Cpu(CpuError::Jammed { program_counter, .. })) => assert_eq!(program_counter, 0x0601),
+            other => panic!("expected jam, got {:?}", other),
+        }
+        assert_eq!(headless.steps, 1);
+    }
+
+    #[test]
+    fn test_program_past_end_of_memory() {
+        assert_eq!(Headless::with_program(&[0xea; 2], 0xffff).err(), Some(ProgramTooLarge { addr: 0xffff, len: 2 }));
+        assert!(Headless::with_program(&[0xea; 0x100], 0xff00).is_ok());
+    }
+
+    #[test]
+    fn test_run_rom_frames_with_input() {
+        let mut raw = ines_rom(0, 0, 2, 1);
+        // 0x8000: 读手柄 1 的 A 键存到 $10, 然后循环
+        //   LDA #$01; STA $4016; LDA #$00; STA $4016; LDA $4016; STA $10; JMP $8000
+        let program = [
+            0xa9, 0x01, 0x8d, 0x16, 0x40, 0xa9, 0x00, 0x8d, 0x16, 0x40,
+            0xad, 0x16, 0x40, 0x85, 0x10, 0x4c, 0x00, 0x80,
+        ];
+        raw[16..16 + program.len()].copy_from_slice(&program);
+        raw[16 + 0x7ffc] = 0x00;
+        raw[16 + 0x7ffd] = 0x80;
+        let mut headless = Headless::with_rom(Cartridge::from_bytes(&raw).unwrap()).unwrap();
+        let script = InputScript::parse("2 1 a\n4 1 -").unwrap();
+
+        headless.run(RunLimit::Frames(3), &script).unwrap();
+        assert_eq!(headless.frames, 3);
+        assert_eq!(headless.ram()[0x10], 1);
+        headless.run(RunLimit::Frames(5), &script).unwrap();
+        assert_eq!(headless.ram()[0x10], 0);
+        assert_eq!(headless.ram().len(), 0x0800);
+        assert!(headless.frame().is_some());
+    }
+}
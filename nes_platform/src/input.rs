@@ -1,27 +1,222 @@
+use std::collections::{HashMap, HashSet};
+
+use sdl2::controller::{Button, GameController};
 use sdl2::event::Event;
 use sdl2::EventPump;
+use sdl2::GameControllerSubsystem;
 use sdl2::keyboard::Keycode;
-use crate::cpu::CPU;
+use nes_platform::cpu::RunControl;
+use nes_platform::joypad::{Joypad, JoypadButton};
 
-pub fn handle_user_input(cpu: &mut CPU, event_pump: &mut EventPump) {
-    for event in event_pump.poll_iter() {
-        match event {
-            Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                std::process::exit(0)
-            }
-            Event::KeyDown { keycode: Some(Keycode::W), .. } => {
-                cpu.memory_write(0xff, 0x77);
-            }
-            Event::KeyDown { keycode: Some(Keycode::S), .. } => {
-                cpu.memory_write(0xff, 0x73);
-            }
-            Event::KeyDown { keycode: Some(Keycode::A), .. } => {
-                cpu.memory_write(0xff, 0x61);
-            }
-            Event::KeyDown { keycode: Some(Keycode::D), .. } => {
-                cpu.memory_write(0xff, 0x64);
+/**
+模拟器自己的功能键, 不会传给游戏
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hotkey {
+    SaveState,
+    LoadState,
+    // 按住时倒带
+    Rewind,
+}
+
+/**
+按键绑定表: 把键盘按键和手柄按钮映射到某个玩家的 NES 手柄按键上
+游戏手柄按插入的顺序分配给玩家 1 和玩家 2, 所以手柄按钮只需要映射到 NES 按键
+ */
+pub struct KeyBindings {
+    keys: HashMap<Keycode, (usize, JoypadButton)>,
+    controller_buttons: HashMap<Button, JoypadButton>,
+    hotkeys: HashMap<Keycode, Hotkey>,
+}
+
+impl KeyBindings {
+    pub fn empty() -> Self {
+        KeyBindings { keys: HashMap::new(), controller_buttons: HashMap::new(), hotkeys: HashMap::new() }
+    }
+
+    pub fn bind_key(&mut self, keycode: Keycode, player: usize, button: JoypadButton) {
+        self.keys.insert(keycode, (player, button));
+    }
+
+    pub fn bind_controller_button(&mut self, controller_button: Button, button: JoypadButton) {
+        self.controller_buttons.insert(controller_button, button);
+    }
+
+    pub fn bind_hotkey(&mut self, keycode: Keycode, hotkey: Hotkey) {
+        self.hotkeys.insert(keycode, hotkey);
+    }
+
+    pub fn hotkey(&self, keycode: Keycode) -> Option<Hotkey> {
+        self.hotkeys.get(&keycode).copied()
+    }
+
+    pub fn key(&self, keycode: Keycode) -> Option<(usize, JoypadButton)> {
+        self.keys.get(&keycode).copied()
+    }
+
+    pub fn controller_button(&self, controller_button: Button) -> Option<JoypadButton> {
+        self.controller_buttons.get(&controller_button).copied()
+    }
+}
+
+impl Default for KeyBindings {
+    /*
+    玩家 1: WASD 方向, J = B, K = A, 空格 = Select, 回车 = Start
+    玩家 2: 方向键, 小键盘 1 = B, 小键盘 2 = A, 小键盘 3 = Select, 小键盘回车 = Start
+    游戏手柄: 方向键, 按钮按位置对应 (下面的 A 是 NES 的 B, 右边的 B 是 NES 的 A)
+    F5 保存状态, F9 读取状态, 按住退格键倒带
+     */
+    fn default() -> Self {
+        let mut bindings = KeyBindings::empty();
+        let player_1 = [
+            (Keycode::W, JoypadButton::UP),
+            (Keycode::S, JoypadButton::DOWN),
+            (Keycode::A, JoypadButton::LEFT),
+            (Keycode::D, JoypadButton::RIGHT),
+            (Keycode::J, JoypadButton::BUTTON_B),
+            (Keycode::K, JoypadButton::BUTTON_A),
+            (Keycode::Space, JoypadButton::SELECT),
+            (Keycode::Return, JoypadButton::START),
+        ];
+        let player_2 = [
+            (Keycode::Up, JoypadButton::UP),
+            (Keycode::Down, JoypadButton::DOWN),
+            (Keycode::Left, JoypadButton::LEFT),
+            (Keycode::Right, JoypadButton::RIGHT),
+            (Keycode::Kp1, JoypadButton::BUTTON_B),
+            (Keycode::Kp2, JoypadButton::BUTTON_A),
+            (Keycode::Kp3, JoypadButton::SELECT),
+            (Keycode::KpEnter, JoypadButton::START),
+        ];
+        for (keycode, button) in player_1 {
+            bindings.bind_key(keycode, 0, button);
+        }
+        for (keycode, button) in player_2 {
+            bindings.bind_key(keycode, 1, button);
+        }
+
+        let controller = [
+            (Button::DPadUp, JoypadButton::UP),
+            (Button::DPadDown, JoypadButton::DOWN),
+            (Button::DPadLeft, JoypadButton::LEFT),
+            (Button::DPadRight, JoypadButton::RIGHT),
+            (Button::A, JoypadButton::BUTTON_B),
+            (Button::B, JoypadButton::BUTTON_A),
+            (Button::Back, JoypadButton::SELECT),
+            (Button::Start, JoypadButton::START),
+        ];
+        for (controller_button, button) in controller {
+            bindings.bind_controller_button(controller_button, button);
+        }
+        bindings.bind_hotkey(Keycode::F5, Hotkey::SaveState);
+        bindings.bind_hotkey(Keycode::F9, Hotkey::LoadState);
+        bindings.bind_hotkey(Keycode::Backspace, Hotkey::Rewind);
+        bindings
+    }
+}
+
+/**
+把 SDL 的键盘和游戏手柄事件转换成两个 NES 手柄的按键状态
+ */
+pub struct Input {
+    bindings: KeyBindings,
+    controller_subsystem: Option<GameControllerSubsystem>,
+    // 已经打开的游戏手柄, 下标就是玩家编号
+    controllers: Vec<GameController>,
+    // 按下之后还没有被前端处理的功能键
+    hotkeys: Vec<Hotkey>,
+    // 正在按住的功能键
+    held_hotkeys: HashSet<Hotkey>,
+}
+
+impl Input {
+    pub fn new(bindings: KeyBindings, controller_subsystem: Option<GameControllerSubsystem>) -> Self {
+        Input { bindings, controller_subsystem, controllers: Vec::new(), hotkeys: Vec::new(), held_hotkeys: HashSet::new() }
+    }
+
+    pub fn handle_events(&mut self, event_pump: &mut EventPump, joypads: &mut [Joypad; 2]) -> RunControl {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    return RunControl::Stop;
+                }
+                Event::KeyDown { keycode: Some(keycode), repeat, .. } => {
+                    if let Some(hotkey) = self.bindings.hotkey(keycode) {
+                        self.held_hotkeys.insert(hotkey);
+                        // 按住不放时的自动重复不算新的按下
+                        if !repeat {
+                            self.hotkeys.push(hotkey);
+                        }
+                    } else if let Some((player, button)) = self.bindings.key(keycode) {
+                        joypads[player].set_button_pressed_status(button, true);
+                    }
+                }
+                Event::KeyUp { keycode: Some(keycode), .. } => {
+                    if let Some(hotkey) = self.bindings.hotkey(keycode) {
+                        self.held_hotkeys.remove(&hotkey);
+                    } else if let Some((player, button)) = self.bindings.key(keycode) {
+                        joypads[player].set_button_pressed_status(button, false);
+                    }
+                }
+                Event::ControllerButtonDown { which, button, .. } => {
+                    self.set_controller_button(which, button, true, joypads);
+                }
+                Event::ControllerButtonUp { which, button, .. } => {
+                    self.set_controller_button(which, button, false, joypads);
+                }
+                // 启动时已经插着的手柄也会产生这个事件
+                Event::ControllerDeviceAdded { which, .. } if self.controllers.len() < joypads.len() => {
+                    if let Some(subsystem) = &self.controller_subsystem {
+                        if let Ok(controller) = subsystem.open(which) {
+                            self.controllers.push(controller);
+                        }
+                    }
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    self.controllers.retain(|controller| controller.instance_id() != which);
+                }
+                _ => {}
             }
-            _ => {}
         }
+        RunControl::Continue
+    }
+
+    pub fn hotkey_held(&self, hotkey: Hotkey) -> bool {
+        self.held_hotkeys.contains(&hotkey)
+    }
+
+    // 取出上次处理事件以来按下的功能键
+    pub fn take_hotkeys(&mut self) -> Vec<Hotkey> {
+        std::mem::take(&mut self.hotkeys)
+    }
+
+    fn set_controller_button(&self, which: u32, controller_button: Button, pressed: bool, joypads: &mut [Joypad; 2]) {
+        let player = self.controllers.iter().position(|controller| controller.instance_id() == which);
+        if let (Some(player), Some(button)) = (player, self.bindings.controller_button(controller_button)) {
+            joypads[player].set_button_pressed_status(button, pressed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_bindings() {
+        let bindings = KeyBindings::default();
+        assert_eq!(bindings.key(Keycode::W), Some((0, JoypadButton::UP)));
+        assert_eq!(bindings.key(Keycode::Left), Some((1, JoypadButton::LEFT)));
+        assert_eq!(bindings.key(Keycode::F1), None);
+        assert_eq!(bindings.controller_button(Button::Start), Some(JoypadButton::START));
+        assert_eq!(bindings.hotkey(Keycode::F5), Some(Hotkey::SaveState));
+        assert_eq!(bindings.hotkey(Keycode::W), None);
+    }
+
+    #[test]
+    fn test_rebind_key() {
+        let mut bindings = KeyBindings::default();
+        bindings.bind_key(Keycode::W, 1, JoypadButton::BUTTON_A);
+        assert_eq!(bindings.key(Keycode::W), Some((1, JoypadButton::BUTTON_A)));
     }
-}
\ No newline at end of file
+}
@@ -2,7 +2,7 @@ use crate::cpu::CPU;
 use crate::instruction::addressing::AddressingMode;
 
 pub fn adc(cpu: &mut CPU, addressing_mode: &AddressingMode) {
-    let address = cpu.get_operand_address(addressing_mode);
+    let address = cpu.get_operand_address_for_read(addressing_mode);
     let val = cpu.memory_read(address);
-    cpu.add_to_register_a_address(val);
+    cpu.add_with_carry(val);
 }
\ No newline at end of file
@@ -0,0 +1,12 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+非官方指令: AND 立即数之后把 A 右移一位, 相当于 AND + LSR A
+ */
+pub fn alr(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address(addressing_mode);
+    let data = cpu.memory_read(address) & cpu.register_a;
+    cpu.set_carry_flag_from(data & 1 == 1);
+    cpu.set_register_a(data >> 1);
+}
\ No newline at end of file
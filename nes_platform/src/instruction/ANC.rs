@@ -0,0 +1,12 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+非官方指令: AND 立即数, 然后把结果的位7复制到进位标志
+ */
+pub fn anc(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address(addressing_mode);
+    let data = cpu.memory_read(address);
+    cpu.set_register_a(cpu.register_a & data);
+    cpu.set_carry_flag_from(cpu.register_a >> 7 == 1);
+}
\ No newline at end of file
@@ -0,0 +1,21 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+非官方指令: AND 立即数之后把 A 循环右移一位
+ 标志位和 ROR 不同: 进位标志是结果的位6, 溢出标志是结果的位6 异或位5
+ */
+pub fn arr(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address(addressing_mode);
+    let data = cpu.memory_read(address) & cpu.register_a;
+    let result = data >> 1 | cpu.carry_bit() << 7;
+    cpu.set_register_a(result);
+    let bit_6 = (result >> 6) & 1;
+    let bit_5 = (result >> 5) & 1;
+    cpu.set_carry_flag_from(bit_6 == 1);
+    if bit_6 ^ bit_5 == 1 {
+        cpu.set_overflow_flag();
+    } else {
+        cpu.clear_overflow_flag();
+    }
+}
\ No newline at end of file
@@ -2,13 +2,15 @@ use crate::cpu::CPU;
 use crate::instruction::addressing::AddressingMode;
 
 pub(crate) fn asl(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    if let AddressingMode::Accumulator = addressing_mode {
+        let data = cpu.register_a;
+        cpu.set_carry_flag_from(data >> 7 == 1);
+        cpu.set_register_a(data << 1);
+        return;
+    }
     let address = cpu.get_operand_address(addressing_mode);
     let data = cpu.memory_read(address);
-    if data >> 7 == 1 {
-        cpu.set_carry_flag();
-    } else {
-        cpu.clear_carry_flag()
-    }
+    cpu.set_carry_flag_from(data >> 7 == 1);
     let carry_data = data << 1;
     cpu.memory_write(address, carry_data);
     cpu.update_zero_and_negative_flags(carry_data);
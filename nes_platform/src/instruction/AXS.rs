@@ -0,0 +1,13 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+非官方指令: X = (A & X) - 立即数, 像 CMP 一样设置进位标志 (不借位时为 1), 不受进位和十进制模式影响
+ */
+pub fn axs(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address(addressing_mode);
+    let data = cpu.memory_read(address);
+    let value = cpu.register_a & cpu.register_x;
+    cpu.set_carry_flag_from(value >= data);
+    cpu.set_register_x(value.wrapping_sub(data));
+}
\ No newline at end of file
@@ -0,0 +1,11 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+65C02 的位操作指令: 零页内存的第 BIT 位为 0 时跳转
+ */
+pub fn bbr<const BIT: u8>(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address(addressing_mode);
+    let data = cpu.memory_read(address);
+    cpu.branch_zero_page_relative(data & (1 << BIT) == 0);
+}
\ No newline at end of file
@@ -0,0 +1,6 @@
+use crate::cpu::{CPU, CPUFlags};
+use crate::instruction::addressing::AddressingMode;
+
+pub fn beq(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    cpu.branch(cpu.status.contains(CPUFlags::ZERO));
+}
\ No newline at end of file
@@ -0,0 +1,21 @@
+use crate::cpu::{CPU, CPUFlags};
+use crate::instruction::addressing::AddressingMode;
+
+/*
+BIT 用累加器与内存做按位与来设置零标志(结果不保存), 内存的位7和位6分别复制到负数标志和溢出标志
+ */
+pub fn bit(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address_for_read(addressing_mode);
+    let data = cpu.memory_read(address);
+    if cpu.register_a & data == 0 {
+        cpu.set_zero_flag();
+    } else {
+        cpu.clear_zero_flag();
+    }
+    // 65C02 的 BIT #立即数 只影响零标志
+    if let AddressingMode::Immediate = addressing_mode {
+        return;
+    }
+    cpu.status.set(CPUFlags::NEGATIV, data & 0b1000_0000 > 0);
+    cpu.status.set(CPUFlags::OVERFLOW, data & 0b0100_0000 > 0);
+}
\ No newline at end of file
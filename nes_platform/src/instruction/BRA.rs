@@ -0,0 +1,9 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+65C02: 无条件的相对跳转
+ */
+pub fn bra(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    cpu.branch(true);
+}
\ No newline at end of file
@@ -0,0 +1,13 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+use crate::interrupt;
+
+/*
+BRK 强制中断: 操作码后面还有一个填充字节, 压栈的返回地址跳过它,
+然后和 IRQ 一样通过 0xFFFE 的向量进入中断处理程序, 压栈的状态寄存器带 B 标志
+ */
+pub fn brk(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    cpu.program_counter = cpu.program_counter.wrapping_add(1);
+    // interrupt 通过 jump 跳转到中断向量
+    cpu.interrupt(&interrupt::BRK);
+}
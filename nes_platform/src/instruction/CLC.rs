@@ -0,0 +1,6 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+pub fn clc(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    cpu.clear_carry_flag();
+}
\ No newline at end of file
@@ -0,0 +1,6 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+pub fn clv(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    cpu.clear_overflow_flag();
+}
\ No newline at end of file
@@ -2,5 +2,5 @@ use crate::cpu::CPU;
 use crate::instruction::addressing::AddressingMode;
 
 pub fn cmp(cpu: &mut CPU, addressing_mode: &AddressingMode) {
-
+    cpu.compare(addressing_mode, cpu.register_a);
 }
\ No newline at end of file
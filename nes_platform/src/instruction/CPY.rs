@@ -2,5 +2,5 @@ use crate::cpu::CPU;
 use crate::instruction::addressing::AddressingMode;
 
 pub fn cpy(cpu: &mut CPU, addressing_mode: &AddressingMode) {
-
+    cpu.compare(addressing_mode, cpu.register_y);
 }
\ No newline at end of file
@@ -0,0 +1,13 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+非官方指令: 内存减一之后和 A 比较, 相当于 DEC + CMP
+ */
+pub fn dcp(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address(addressing_mode);
+    let data = cpu.memory_read(address).wrapping_sub(1);
+    cpu.memory_write(address, data);
+    cpu.set_carry_flag_from(cpu.register_a >= data);
+    cpu.update_zero_and_negative_flags(cpu.register_a.wrapping_sub(data));
+}
\ No newline at end of file
@@ -2,5 +2,13 @@ use crate::cpu::CPU;
 use crate::instruction::addressing::AddressingMode;
 
 pub fn dec(cpu: &mut CPU, addressing_mode: &AddressingMode) {
-
+    // 65C02 的 DEC A
+    if let AddressingMode::Accumulator = addressing_mode {
+        cpu.set_register_a(cpu.register_a.wrapping_sub(1));
+        return;
+    }
+    let address = cpu.get_operand_address(addressing_mode);
+    let data = cpu.memory_read(address).wrapping_sub(1);
+    cpu.memory_write(address, data);
+    cpu.update_zero_and_negative_flags(data);
 }
\ No newline at end of file
@@ -2,5 +2,5 @@ use crate::cpu::CPU;
 use crate::instruction::addressing::AddressingMode;
 
 pub fn dex(cpu: &mut CPU, addressing_mode: &AddressingMode) {
-
+    cpu.set_register_x(cpu.register_x.wrapping_sub(1));
 }
\ No newline at end of file
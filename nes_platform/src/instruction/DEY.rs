@@ -2,5 +2,5 @@ use crate::cpu::CPU;
 use crate::instruction::addressing::AddressingMode;
 
 pub fn dey(cpu: &mut CPU, addressing_mode: &AddressingMode) {
-
+    cpu.set_register_y(cpu.register_y.wrapping_sub(1));
 }
\ No newline at end of file
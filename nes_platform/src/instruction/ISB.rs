@@ -0,0 +1,12 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+非官方指令: 内存加一之后从 A 中减去, 相当于 INC + SBC
+ */
+pub fn isb(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address(addressing_mode);
+    let data = cpu.memory_read(address).wrapping_add(1);
+    cpu.memory_write(address, data);
+    cpu.subtract_with_carry(data);
+}
\ No newline at end of file
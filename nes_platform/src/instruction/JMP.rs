@@ -2,5 +2,6 @@ use crate::cpu::CPU;
 use crate::instruction::addressing::AddressingMode;
 
 pub fn jmp(cpu: &mut CPU, addressing_mode: &AddressingMode) {
-
+    let target = cpu.get_operand_address(addressing_mode);
+    cpu.jump(target);
 }
\ No newline at end of file
@@ -1,6 +1,12 @@
 use crate::cpu::CPU;
 use crate::instruction::addressing::AddressingMode;
 
+/*
+JSR 把返回地址减一(即本条指令最后一个字节的地址)压栈, 然后跳转到目标地址
+ */
 pub fn jsr(cpu: &mut CPU, addressing_mode: &AddressingMode) {
-
+    // 此时程序计数器指向操作数, +2 为下一条指令, 再 -1
+    cpu.stack_push_u16(cpu.program_counter.wrapping_add(2 - 1));
+    let target = cpu.get_operand_address(addressing_mode);
+    cpu.jump(target);
 }
\ No newline at end of file
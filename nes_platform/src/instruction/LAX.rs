@@ -0,0 +1,12 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+非官方指令: 同时加载 A 和 X, 相当于 LDA + TAX
+ */
+pub fn lax(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address_for_read(addressing_mode);
+    let data = cpu.memory_read(address);
+    cpu.set_register_a(data);
+    cpu.set_register_x(data);
+}
\ No newline at end of file
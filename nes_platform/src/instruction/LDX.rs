@@ -2,6 +2,7 @@ use crate::cpu::CPU;
 use crate::instruction::addressing::AddressingMode;
 
 pub(crate) fn ldx(cpu: &mut CPU, addressing_mode: &AddressingMode) {
-    let address = cpu.get_operand_address(addressing_mode);
-    cpu.set_register_x(cpu.memory_read(address));
+    let address = cpu.get_operand_address_for_read(addressing_mode);
+    let data = cpu.memory_read(address);
+    cpu.set_register_x(data);
 }
\ No newline at end of file
@@ -2,5 +2,16 @@ use crate::cpu::CPU;
 use crate::instruction::addressing::AddressingMode;
 
 pub fn lsr(cpu: &mut CPU, addressing_mode: &AddressingMode) {
-
+    if let AddressingMode::Accumulator = addressing_mode {
+        let data = cpu.register_a;
+        cpu.set_carry_flag_from(data & 1 == 1);
+        cpu.set_register_a(data >> 1);
+        return;
+    }
+    let address = cpu.get_operand_address(addressing_mode);
+    let data = cpu.memory_read(address);
+    cpu.set_carry_flag_from(data & 1 == 1);
+    let carry_data = data >> 1;
+    cpu.memory_write(address, carry_data);
+    cpu.update_zero_and_negative_flags(carry_data);
 }
\ No newline at end of file
@@ -0,0 +1,12 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+pub fn nop(cpu: &mut CPU, addressing_mode: &AddressingMode) {}
+
+/*
+非官方的多字节 NOP 和读内存的指令一样读取操作数, 只是丢掉读到的值, 跨页时同样多消耗一个周期
+ */
+pub fn nop_read(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address_for_read(addressing_mode);
+    cpu.memory_read(address);
+}
\ No newline at end of file
@@ -2,7 +2,7 @@ use crate::cpu::CPU;
 use crate::instruction::addressing::AddressingMode;
 
 pub(crate) fn ora(cpu: &mut CPU, addressing_mode: &AddressingMode) {
-    let address = cpu.get_operand_address(addressing_mode);
+    let address = cpu.get_operand_address_for_read(addressing_mode);
     let data = cpu.memory_read(address);
-    cpu.set_register_x(data | cpu.register_a);
+    cpu.set_register_a(data | cpu.register_a);
 }
\ No newline at end of file
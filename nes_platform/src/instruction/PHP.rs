@@ -0,0 +1,13 @@
+use crate::cpu::{CPU, CPUFlags};
+use crate::instruction::addressing::AddressingMode;
+
+/*
+PHP 压栈的状态寄存器副本总是带上 B 标志位(位4和位5)
+https://www.nesdev.org/wiki/Status_flags#The_B_flag
+ */
+pub fn php(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let mut flags = cpu.status;
+    flags.insert(CPUFlags::BREAK);
+    flags.insert(CPUFlags::BREAK2);
+    cpu.stack_push(flags.bits());
+}
\ No newline at end of file
@@ -0,0 +1,6 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+pub fn phx(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    cpu.stack_push(cpu.register_x);
+}
\ No newline at end of file
@@ -0,0 +1,7 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+pub fn pla(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let data = cpu.stack_pop();
+    cpu.set_register_a(data);
+}
\ No newline at end of file
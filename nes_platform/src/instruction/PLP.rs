@@ -0,0 +1,11 @@
+use crate::cpu::{CPU, CPUFlags};
+use crate::instruction::addressing::AddressingMode;
+
+/*
+PLP 从栈中恢复状态寄存器, B 标志位在真实的寄存器中并不存在, 所以忽略弹出值中的位4
+ */
+pub fn plp(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    cpu.status = CPUFlags::from_bits_truncate(cpu.stack_pop());
+    cpu.status.remove(CPUFlags::BREAK);
+    cpu.status.insert(CPUFlags::BREAK2);
+}
\ No newline at end of file
@@ -1,6 +0,0 @@
-use crate::cpu::CPU;
-use crate::instruction::addressing::AddressingMode;
-
-pub fn ris(cpu: &mut CPU, addressing_mode: &AddressingMode) {
-
-}
\ No newline at end of file
@@ -0,0 +1,15 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+非官方指令: 内存循环左移一位之后和 A 按位与, 相当于 ROL + AND
+ */
+pub fn rla(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let old_carry = cpu.carry_bit();
+    let address = cpu.get_operand_address(addressing_mode);
+    let data = cpu.memory_read(address);
+    cpu.set_carry_flag_from(data >> 7 == 1);
+    let rotated = data << 1 | old_carry;
+    cpu.memory_write(address, rotated);
+    cpu.set_register_a(cpu.register_a & rotated);
+}
\ No newline at end of file
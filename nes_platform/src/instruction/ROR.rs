@@ -1,6 +1,21 @@
 use crate::cpu::CPU;
 use crate::instruction::addressing::AddressingMode;
 
+/*
+循环右移: 旧的进位标志移入位7, 位0移入进位标志
+ */
 pub fn ror(cpu: &mut CPU, addressing_mode: &AddressingMode) {
-
+    let old_carry = cpu.carry_bit() << 7;
+    if let AddressingMode::Accumulator = addressing_mode {
+        let data = cpu.register_a;
+        cpu.set_carry_flag_from(data & 1 == 1);
+        cpu.set_register_a(data >> 1 | old_carry);
+        return;
+    }
+    let address = cpu.get_operand_address(addressing_mode);
+    let data = cpu.memory_read(address);
+    cpu.set_carry_flag_from(data & 1 == 1);
+    let carry_data = data >> 1 | old_carry;
+    cpu.memory_write(address, carry_data);
+    cpu.update_zero_and_negative_flags(carry_data);
 }
\ No newline at end of file
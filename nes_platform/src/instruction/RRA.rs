@@ -0,0 +1,15 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+非官方指令: 内存循环右移一位之后加到 A 上, 加法使用移出来的进位, 相当于 ROR + ADC
+ */
+pub fn rra(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let old_carry = cpu.carry_bit() << 7;
+    let address = cpu.get_operand_address(addressing_mode);
+    let data = cpu.memory_read(address);
+    cpu.set_carry_flag_from(data & 1 == 1);
+    let rotated = data >> 1 | old_carry;
+    cpu.memory_write(address, rotated);
+    cpu.add_with_carry(rotated);
+}
\ No newline at end of file
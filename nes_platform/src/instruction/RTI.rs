@@ -1,6 +1,13 @@
-use crate::cpu::CPU;
+use crate::cpu::{CPU, CPUFlags};
 use crate::instruction::addressing::AddressingMode;
 
+/*
+RTI 从中断返回: 依次弹出状态寄存器和程序计数器
+ */
 pub fn rti(cpu: &mut CPU, addressing_mode: &AddressingMode) {
-
+    cpu.status = CPUFlags::from_bits_truncate(cpu.stack_pop());
+    cpu.status.remove(CPUFlags::BREAK);
+    cpu.status.insert(CPUFlags::BREAK2);
+    let return_address = cpu.stack_pop_u16();
+    cpu.jump(return_address);
 }
\ No newline at end of file
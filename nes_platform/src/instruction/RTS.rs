@@ -0,0 +1,10 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+RTS 从栈中弹出 JSR 保存的返回地址, +1 之后就是 JSR 的下一条指令
+ */
+pub fn rts(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let return_address = cpu.stack_pop_u16().wrapping_add(1);
+    cpu.jump(return_address);
+}
\ No newline at end of file
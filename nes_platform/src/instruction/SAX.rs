@@ -0,0 +1,10 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+非官方指令: 把 A & X 写入内存, 不影响标志位
+ */
+pub fn sax(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address(addressing_mode);
+    cpu.memory_write(address, cpu.register_a & cpu.register_x);
+}
\ No newline at end of file
@@ -2,9 +2,7 @@ use crate::cpu::CPU;
 use crate::instruction::addressing::AddressingMode;
 
 pub(crate) fn sbc(cpu: &mut CPU, addressing_mode: &AddressingMode) {
-    let address = cpu.get_operand_address(addressing_mode);
+    let address = cpu.get_operand_address_for_read(addressing_mode);
     let data = cpu.memory_read(address);
-    cpu.add_to_register_a_address(
-        data.wrapping_neg().wrapping_sub(1)
-    );
+    cpu.subtract_with_carry(data);
 }
\ No newline at end of file
@@ -0,0 +1,6 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+pub fn sec(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    cpu.set_carry_flag();
+}
\ No newline at end of file
@@ -0,0 +1,6 @@
+use crate::cpu::{CPU, CPUFlags};
+use crate::instruction::addressing::AddressingMode;
+
+pub fn sed(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    cpu.status.insert(CPUFlags::DECIMAL_MODE);
+}
\ No newline at end of file
@@ -0,0 +1,6 @@
+use crate::cpu::{CPU, CPUFlags};
+use crate::instruction::addressing::AddressingMode;
+
+pub fn sei(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    cpu.status.insert(CPUFlags::INTERRUPT_DISABLE);
+}
\ No newline at end of file
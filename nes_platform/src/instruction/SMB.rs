@@ -0,0 +1,11 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+65C02 的位操作指令: 把零页内存的第 BIT 位置 1
+ */
+pub fn smb<const BIT: u8>(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address(addressing_mode);
+    let data = cpu.memory_read(address);
+    cpu.memory_write(address, data | 1 << BIT);
+}
\ No newline at end of file
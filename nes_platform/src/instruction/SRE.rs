@@ -0,0 +1,14 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+非官方指令: 内存右移一位之后和 A 按位异或, 相当于 LSR + EOR
+ */
+pub fn sre(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address(addressing_mode);
+    let data = cpu.memory_read(address);
+    cpu.set_carry_flag_from(data & 1 == 1);
+    let shifted = data >> 1;
+    cpu.memory_write(address, shifted);
+    cpu.set_register_a(cpu.register_a ^ shifted);
+}
\ No newline at end of file
@@ -0,0 +1,7 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+pub(crate) fn stx(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address(addressing_mode);
+    cpu.memory_write(address, cpu.register_x);
+}
\ No newline at end of file
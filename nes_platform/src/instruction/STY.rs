@@ -0,0 +1,7 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+pub(crate) fn sty(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address(addressing_mode);
+    cpu.memory_write(address, cpu.register_y);
+}
\ No newline at end of file
@@ -0,0 +1,10 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+65C02: 往内存写 0, 不影响标志位
+ */
+pub fn stz(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address(addressing_mode);
+    cpu.memory_write(address, 0);
+}
\ No newline at end of file
@@ -0,0 +1,6 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+pub fn tay(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    cpu.set_register_y(cpu.register_a);
+}
\ No newline at end of file
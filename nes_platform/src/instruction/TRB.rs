@@ -0,0 +1,12 @@
+use crate::cpu::{CPU, CPUFlags};
+use crate::instruction::addressing::AddressingMode;
+
+/*
+65C02: 清除内存中累加器为 1 的那些位, 零标志和 BIT 一样来自 A & M
+ */
+pub fn trb(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    let address = cpu.get_operand_address(addressing_mode);
+    let data = cpu.memory_read(address);
+    cpu.status.set(CPUFlags::ZERO, cpu.register_a & data == 0);
+    cpu.memory_write(address, data & !cpu.register_a);
+}
\ No newline at end of file
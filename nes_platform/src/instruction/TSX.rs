@@ -0,0 +1,6 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+pub fn tsx(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    cpu.set_register_x(cpu.stack_pointer);
+}
\ No newline at end of file
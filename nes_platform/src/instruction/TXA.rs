@@ -0,0 +1,6 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+pub fn txa(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    cpu.set_register_a(cpu.register_x);
+}
\ No newline at end of file
@@ -0,0 +1,9 @@
+use crate::cpu::CPU;
+use crate::instruction::addressing::AddressingMode;
+
+/*
+TXS 是唯一一个不影响标志位的传送指令
+ */
+pub fn txs(cpu: &mut CPU, addressing_mode: &AddressingMode) {
+    cpu.stack_pointer = cpu.register_x;
+}
\ No newline at end of file
@@ -20,9 +20,21 @@ Indirect_X: 间接X变址寻址模式。操作数的地址通过一个间接寻
 
 Indirect_Y: 间接Y变址寻址模式。操作数的地址通过一个间接寻址的方式计算得到。首先，使用一个地址作为间接寻址的目标地址，然后将这个地址与Y寄存器相加得到最终的地址。例如：STA ($30),Y，表示将累加器寄存器的值存储到以地址0x30为间接寻址目标，再加上Y寄存器的值得到的最终地址所指向的内存单元中。
 
+Accumulator: 累加器寻址模式。指令直接作用于累加器，例如：ASL A，表示将累加器的值左移一位。
+
+Relative: 相对寻址模式。只用于分支指令，操作数是一个有符号的字节偏移量，跳转目标为下一条指令的地址加上这个偏移量。例如：BNE $FB。
+
+Indirect: 间接寻址模式。只用于JMP，操作数是一个指向目标地址的指针。例如：JMP ($1234)，表示跳转到地址0x1234和0x1235处存储的地址。
+
+Indirect_ZeroPage: 65C02 的零页间接寻址模式。和 Indirect_Y 一样通过零页上的指针取地址，但是不加 Y。例如：LDA ($30)。
+
+Indirect_Absolute_X: 65C02 的绝对变址间接寻址模式。只用于JMP，指针的地址是操作数加上 X。例如：JMP ($1234,X)。
+
+ZeroPage_Relative: 65C02 的 BBR/BBS 使用。第一个操作数字节是要测试的零页地址，第二个字节是相对跳转的偏移量。例如：BBR0 $12,$FB。
+
 NoneAddressing: 无寻址模式。表示该指令没有操作数，或者操作数不需要通过寻址方式获取。
  */
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(non_camel_case_types)]
 pub enum AddressingMode {
     Immediate,
@@ -34,6 +46,12 @@ pub enum AddressingMode {
     Absolute_Y,
     Indirect_X,
     Indirect_Y,
+    Accumulator,
+    Relative,
+    Indirect,
+    Indirect_ZeroPage,
+    Indirect_Absolute_X,
+    ZeroPage_Relative,
     NoneAddressing,
 }
 
@@ -58,11 +76,18 @@ pub struct OpCode {
      */
     pub cycles: u8,
     pub mode: AddressingMode,
+    // 非官方指令, 跟踪日志中和 nestest.log 一样在助记符前面加 *
+    pub official: bool,
 }
 
 impl OpCode {
     pub(crate) fn new(code: u8, mnemonic: &'static str, len: u8, cycles: u8, mode: AddressingMode) -> Self {
-        return OpCode { code, mnemonic, len, operand_len: len - 1, cycles, mode };
+        OpCode { code, mnemonic, len, operand_len: len - 1, cycles, mode, official: true }
+    }
+
+    #[cfg(feature = "unofficial-opcodes")]
+    pub(crate) fn unofficial(code: u8, mnemonic: &'static str, len: u8, cycles: u8, mode: AddressingMode) -> Self {
+        OpCode { official: false, ..OpCode::new(code, mnemonic, len, cycles, mode) }
     }
 }
 
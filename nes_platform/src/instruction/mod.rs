@@ -4,7 +4,7 @@ use std::collections::HashMap;
 
 use lazy_static::lazy_static;
 
-use crate::cpu::CPU;
+use crate::cpu::{CpuVariant, CPU};
 use crate::instruction::addressing::{AddressingMode, OpCode};
 
 pub struct InstructionBuiltin {
@@ -14,52 +14,132 @@ pub struct InstructionBuiltin {
 
 impl InstructionBuiltin {
     fn new(op: OpCode, execute: fn(&mut CPU, &AddressingMode)) -> Self {
-        return InstructionBuiltin { op, execute };
+        InstructionBuiltin { op, execute }
     }
 }
 lazy_static! {
         pub static ref CPU_INSTRUCTION_BUILTIN:Vec<InstructionBuiltin>=vec![
-        InstructionBuiltin::new(OpCode::new(0x00, "BRK", 1, 7, AddressingMode::NoneAddressing),|cpu, mode|{}),
-        InstructionBuiltin::new(OpCode::new(0xAA, "TAX", 1, 2, AddressingMode::NoneAddressing),TAX::tax),
+        InstructionBuiltin::new(OpCode::new(0x00, "BRK", 1, 7, AddressingMode::NoneAddressing),BRK::brk),
+        InstructionBuiltin::new(OpCode::new(0xEA, "NOP", 1, 2, AddressingMode::NoneAddressing),NOP::nop),
+
+        // 算术运算
+        InstructionBuiltin::new(OpCode::new(0x69, "ADC", 2, 2, AddressingMode::Immediate),ADC::adc),
+        InstructionBuiltin::new(OpCode::new(0x65, "ADC", 2, 3, AddressingMode::ZeroPage),ADC::adc),
+        InstructionBuiltin::new(OpCode::new(0x75, "ADC", 2, 4, AddressingMode::ZeroPage_X),ADC::adc),
+        InstructionBuiltin::new(OpCode::new(0x6D, "ADC", 3, 4, AddressingMode::Absolute),ADC::adc),
+        InstructionBuiltin::new(OpCode::new(0x7D, "ADC", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_X),ADC::adc),
+        InstructionBuiltin::new(OpCode::new(0x79, "ADC", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_Y),ADC::adc),
+        InstructionBuiltin::new(OpCode::new(0x61, "ADC", 2, 6, AddressingMode::Indirect_X),ADC::adc),
+        InstructionBuiltin::new(OpCode::new(0x71, "ADC", 2, 5/*+1 if page crossed*/, AddressingMode::Indirect_Y),ADC::adc),
+        InstructionBuiltin::new(OpCode::new(0xE9, "SBC", 2, 2, AddressingMode::Immediate),SBC::sbc),
+        InstructionBuiltin::new(OpCode::new(0xE5, "SBC", 2, 3, AddressingMode::ZeroPage),SBC::sbc),
+        InstructionBuiltin::new(OpCode::new(0xF5, "SBC", 2, 4, AddressingMode::ZeroPage_X),SBC::sbc),
+        InstructionBuiltin::new(OpCode::new(0xED, "SBC", 3, 4, AddressingMode::Absolute),SBC::sbc),
+        InstructionBuiltin::new(OpCode::new(0xFD, "SBC", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_X),SBC::sbc),
+        InstructionBuiltin::new(OpCode::new(0xF9, "SBC", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_Y),SBC::sbc),
+        InstructionBuiltin::new(OpCode::new(0xE1, "SBC", 2, 6, AddressingMode::Indirect_X),SBC::sbc),
+        InstructionBuiltin::new(OpCode::new(0xF1, "SBC", 2, 5/*+1 if page crossed*/, AddressingMode::Indirect_Y),SBC::sbc),
+
+        // 逻辑运算
+        InstructionBuiltin::new(OpCode::new(0x29, "AND", 2, 2, AddressingMode::Immediate),AND::and),
+        InstructionBuiltin::new(OpCode::new(0x25, "AND", 2, 3, AddressingMode::ZeroPage),AND::and),
+        InstructionBuiltin::new(OpCode::new(0x35, "AND", 2, 4, AddressingMode::ZeroPage_X),AND::and),
+        InstructionBuiltin::new(OpCode::new(0x2D, "AND", 3, 4, AddressingMode::Absolute),AND::and),
+        InstructionBuiltin::new(OpCode::new(0x3D, "AND", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_X),AND::and),
+        InstructionBuiltin::new(OpCode::new(0x39, "AND", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_Y),AND::and),
+        InstructionBuiltin::new(OpCode::new(0x21, "AND", 2, 6, AddressingMode::Indirect_X),AND::and),
+        InstructionBuiltin::new(OpCode::new(0x31, "AND", 2, 5/*+1 if page crossed*/, AddressingMode::Indirect_Y),AND::and),
+        InstructionBuiltin::new(OpCode::new(0x49, "EOR", 2, 2, AddressingMode::Immediate),EOR::eor),
+        InstructionBuiltin::new(OpCode::new(0x45, "EOR", 2, 3, AddressingMode::ZeroPage),EOR::eor),
+        InstructionBuiltin::new(OpCode::new(0x55, "EOR", 2, 4, AddressingMode::ZeroPage_X),EOR::eor),
+        InstructionBuiltin::new(OpCode::new(0x4D, "EOR", 3, 4, AddressingMode::Absolute),EOR::eor),
+        InstructionBuiltin::new(OpCode::new(0x5D, "EOR", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_X),EOR::eor),
+        InstructionBuiltin::new(OpCode::new(0x59, "EOR", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_Y),EOR::eor),
+        InstructionBuiltin::new(OpCode::new(0x41, "EOR", 2, 6, AddressingMode::Indirect_X),EOR::eor),
+        InstructionBuiltin::new(OpCode::new(0x51, "EOR", 2, 5/*+1 if page crossed*/, AddressingMode::Indirect_Y),EOR::eor),
+        InstructionBuiltin::new(OpCode::new(0x09, "ORA", 2, 2, AddressingMode::Immediate),ORA::ora),
+        InstructionBuiltin::new(OpCode::new(0x05, "ORA", 2, 3, AddressingMode::ZeroPage),ORA::ora),
+        InstructionBuiltin::new(OpCode::new(0x15, "ORA", 2, 4, AddressingMode::ZeroPage_X),ORA::ora),
+        InstructionBuiltin::new(OpCode::new(0x0D, "ORA", 3, 4, AddressingMode::Absolute),ORA::ora),
+        InstructionBuiltin::new(OpCode::new(0x1D, "ORA", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_X),ORA::ora),
+        InstructionBuiltin::new(OpCode::new(0x19, "ORA", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_Y),ORA::ora),
+        InstructionBuiltin::new(OpCode::new(0x01, "ORA", 2, 6, AddressingMode::Indirect_X),ORA::ora),
+        InstructionBuiltin::new(OpCode::new(0x11, "ORA", 2, 5/*+1 if page crossed*/, AddressingMode::Indirect_Y),ORA::ora),
+
+        // 移位
+        InstructionBuiltin::new(OpCode::new(0x0A, "ASL", 1, 2, AddressingMode::Accumulator),ASL::asl),
+        InstructionBuiltin::new(OpCode::new(0x06, "ASL", 2, 5, AddressingMode::ZeroPage),ASL::asl),
+        InstructionBuiltin::new(OpCode::new(0x16, "ASL", 2, 6, AddressingMode::ZeroPage_X),ASL::asl),
+        InstructionBuiltin::new(OpCode::new(0x0E, "ASL", 3, 6, AddressingMode::Absolute),ASL::asl),
+        InstructionBuiltin::new(OpCode::new(0x1E, "ASL", 3, 7, AddressingMode::Absolute_X),ASL::asl),
+        InstructionBuiltin::new(OpCode::new(0x4A, "LSR", 1, 2, AddressingMode::Accumulator),LSR::lsr),
+        InstructionBuiltin::new(OpCode::new(0x46, "LSR", 2, 5, AddressingMode::ZeroPage),LSR::lsr),
+        InstructionBuiltin::new(OpCode::new(0x56, "LSR", 2, 6, AddressingMode::ZeroPage_X),LSR::lsr),
+        InstructionBuiltin::new(OpCode::new(0x4E, "LSR", 3, 6, AddressingMode::Absolute),LSR::lsr),
+        InstructionBuiltin::new(OpCode::new(0x5E, "LSR", 3, 7, AddressingMode::Absolute_X),LSR::lsr),
+        InstructionBuiltin::new(OpCode::new(0x2A, "ROL", 1, 2, AddressingMode::Accumulator),ROL::rol),
+        InstructionBuiltin::new(OpCode::new(0x26, "ROL", 2, 5, AddressingMode::ZeroPage),ROL::rol),
+        InstructionBuiltin::new(OpCode::new(0x36, "ROL", 2, 6, AddressingMode::ZeroPage_X),ROL::rol),
+        InstructionBuiltin::new(OpCode::new(0x2E, "ROL", 3, 6, AddressingMode::Absolute),ROL::rol),
+        InstructionBuiltin::new(OpCode::new(0x3E, "ROL", 3, 7, AddressingMode::Absolute_X),ROL::rol),
+        InstructionBuiltin::new(OpCode::new(0x6A, "ROR", 1, 2, AddressingMode::Accumulator),ROR::ror),
+        InstructionBuiltin::new(OpCode::new(0x66, "ROR", 2, 5, AddressingMode::ZeroPage),ROR::ror),
+        InstructionBuiltin::new(OpCode::new(0x76, "ROR", 2, 6, AddressingMode::ZeroPage_X),ROR::ror),
+        InstructionBuiltin::new(OpCode::new(0x6E, "ROR", 3, 6, AddressingMode::Absolute),ROR::ror),
+        InstructionBuiltin::new(OpCode::new(0x7E, "ROR", 3, 7, AddressingMode::Absolute_X),ROR::ror),
+
+        // 自增自减
+        InstructionBuiltin::new(OpCode::new(0xE6, "INC", 2, 5, AddressingMode::ZeroPage),INC::inc),
+        InstructionBuiltin::new(OpCode::new(0xF6, "INC", 2, 6, AddressingMode::ZeroPage_X),INC::inc),
+        InstructionBuiltin::new(OpCode::new(0xEE, "INC", 3, 6, AddressingMode::Absolute),INC::inc),
+        InstructionBuiltin::new(OpCode::new(0xFE, "INC", 3, 7, AddressingMode::Absolute_X),INC::inc),
         InstructionBuiltin::new(OpCode::new(0xE8, "INX", 1, 2, AddressingMode::NoneAddressing),INX::inx),
         InstructionBuiltin::new(OpCode::new(0xC8, "INY", 1, 2, AddressingMode::NoneAddressing),INY::iny),
-        InstructionBuiltin::new(OpCode::new(0xe6, "INC", 2, 5, AddressingMode::ZeroPage),INC::inc),
-        InstructionBuiltin::new(OpCode::new(0xf6, "INC", 2, 6, AddressingMode::ZeroPage_X),INC::inc),
-        InstructionBuiltin::new(OpCode::new(0xee, "INC", 3, 6, AddressingMode::Absolute),INC::inc),
-        InstructionBuiltin::new(OpCode::new(0xfe, "INC", 3, 7, AddressingMode::Absolute_X),INC::inc),
-        InstructionBuiltin::new(OpCode::new(0xe6, "INC", 2, 5, AddressingMode::ZeroPage),INC::inc),
-        InstructionBuiltin::new(OpCode::new(0xf6, "INC", 2, 6, AddressingMode::ZeroPage_X),INC::inc),
-        InstructionBuiltin::new(OpCode::new(0xee, "INC", 3, 6, AddressingMode::Absolute),INC::inc),
-        InstructionBuiltin::new(OpCode::new(0xfe, "INC", 3, 7, AddressingMode::Absolute_X),INC::inc),
-
-        InstructionBuiltin::new(OpCode::new(0xc6, "DEC", 2, 5, AddressingMode::ZeroPage),DEC::dec),
-        InstructionBuiltin::new(OpCode::new(0xd6, "DEC", 2, 6, AddressingMode::ZeroPage_X),DEC::dec),
-        InstructionBuiltin::new(OpCode::new(0xce, "DEC", 3, 6, AddressingMode::Absolute),DEC::dec),
-        InstructionBuiltin::new(OpCode::new(0xde, "DEC", 3, 7, AddressingMode::Absolute_X),DEC::dec),
-
-        InstructionBuiltin::new(OpCode::new(0xca, "DEX", 1, 2, AddressingMode::NoneAddressing),DEX::dex),
+        InstructionBuiltin::new(OpCode::new(0xC6, "DEC", 2, 5, AddressingMode::ZeroPage),DEC::dec),
+        InstructionBuiltin::new(OpCode::new(0xD6, "DEC", 2, 6, AddressingMode::ZeroPage_X),DEC::dec),
+        InstructionBuiltin::new(OpCode::new(0xCE, "DEC", 3, 6, AddressingMode::Absolute),DEC::dec),
+        InstructionBuiltin::new(OpCode::new(0xDE, "DEC", 3, 7, AddressingMode::Absolute_X),DEC::dec),
+        InstructionBuiltin::new(OpCode::new(0xCA, "DEX", 1, 2, AddressingMode::NoneAddressing),DEX::dex),
         InstructionBuiltin::new(OpCode::new(0x88, "DEY", 1, 2, AddressingMode::NoneAddressing),DEY::dey),
-        InstructionBuiltin::new(OpCode::new(0xc9, "CMP", 2, 2, AddressingMode::Immediate),CMP::cmp),
-        InstructionBuiltin::new(OpCode::new(0xc5, "CMP", 2, 3, AddressingMode::ZeroPage),CMP::cmp),
-        InstructionBuiltin::new(OpCode::new(0xd5, "CMP", 2, 4, AddressingMode::ZeroPage_X),CMP::cmp),
-        InstructionBuiltin::new(OpCode::new(0xcd, "CMP", 3, 4, AddressingMode::Absolute),CMP::cmp),
-        InstructionBuiltin::new(OpCode::new(0xdd, "CMP", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_X),CMP::cmp),
-        InstructionBuiltin::new(OpCode::new(0xd9, "CMP", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_Y),CMP::cmp),
-        InstructionBuiltin::new(OpCode::new(0xc1, "CMP", 2, 6, AddressingMode::Indirect_X),CMP::cmp),
-        InstructionBuiltin::new(OpCode::new(0xd1, "CMP", 2, 5/*+1 if page crossed*/, AddressingMode::Indirect_Y),CMP::cmp),
-        InstructionBuiltin::new(OpCode::new(0xc0, "CPY", 2, 2, AddressingMode::Immediate),CPY::cpy),
-        InstructionBuiltin::new(OpCode::new(0xc4, "CPY", 2, 3, AddressingMode::ZeroPage),CPY::cpy),
-        InstructionBuiltin::new(OpCode::new(0xcc, "CPY", 3, 4, AddressingMode::Absolute),CPY::cpy),
-        InstructionBuiltin::new(OpCode::new(0xe0, "CPX", 2, 2, AddressingMode::Immediate),CPX::cpx),
-        InstructionBuiltin::new(OpCode::new(0xe4, "CPX", 2, 3, AddressingMode::ZeroPage),CPX::cpx),
-        InstructionBuiltin::new(OpCode::new(0xec, "CPX", 3, 4, AddressingMode::Absolute),CPX::cpx),
-        //AddressingMode that acts as Immediate
-        InstructionBuiltin::new(OpCode::new(0x4c, "JMP", 3, 3, AddressingMode::NoneAddressing), JMP::jmp),
+
+        // 比较
+        InstructionBuiltin::new(OpCode::new(0xC9, "CMP", 2, 2, AddressingMode::Immediate),CMP::cmp),
+        InstructionBuiltin::new(OpCode::new(0xC5, "CMP", 2, 3, AddressingMode::ZeroPage),CMP::cmp),
+        InstructionBuiltin::new(OpCode::new(0xD5, "CMP", 2, 4, AddressingMode::ZeroPage_X),CMP::cmp),
+        InstructionBuiltin::new(OpCode::new(0xCD, "CMP", 3, 4, AddressingMode::Absolute),CMP::cmp),
+        InstructionBuiltin::new(OpCode::new(0xDD, "CMP", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_X),CMP::cmp),
+        InstructionBuiltin::new(OpCode::new(0xD9, "CMP", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_Y),CMP::cmp),
+        InstructionBuiltin::new(OpCode::new(0xC1, "CMP", 2, 6, AddressingMode::Indirect_X),CMP::cmp),
+        InstructionBuiltin::new(OpCode::new(0xD1, "CMP", 2, 5/*+1 if page crossed*/, AddressingMode::Indirect_Y),CMP::cmp),
+        InstructionBuiltin::new(OpCode::new(0xC0, "CPY", 2, 2, AddressingMode::Immediate),CPY::cpy),
+        InstructionBuiltin::new(OpCode::new(0xC4, "CPY", 2, 3, AddressingMode::ZeroPage),CPY::cpy),
+        InstructionBuiltin::new(OpCode::new(0xCC, "CPY", 3, 4, AddressingMode::Absolute),CPY::cpy),
+        InstructionBuiltin::new(OpCode::new(0xE0, "CPX", 2, 2, AddressingMode::Immediate),CPX::cpx),
+        InstructionBuiltin::new(OpCode::new(0xE4, "CPX", 2, 3, AddressingMode::ZeroPage),CPX::cpx),
+        InstructionBuiltin::new(OpCode::new(0xEC, "CPX", 3, 4, AddressingMode::Absolute),CPX::cpx),
+
+        // 跳转
+        InstructionBuiltin::new(OpCode::new(0x4C, "JMP", 3, 3, AddressingMode::Absolute),JMP::jmp),
         //AddressingMode:Indirect with 6502 bug
-        InstructionBuiltin::new(OpCode::new(0x6c, "JMP", 3, 5, AddressingMode::NoneAddressing), JMP::jmp),
-        InstructionBuiltin::new(OpCode::new(0x20, "JSR", 3, 6, AddressingMode::NoneAddressing),JSR::jsr),
-        InstructionBuiltin::new(OpCode::new(0x60, "RTS", 1, 6, AddressingMode::NoneAddressing),RIS::ris),
+        InstructionBuiltin::new(OpCode::new(0x6C, "JMP", 3, 5, AddressingMode::Indirect),JMP::jmp),
+        InstructionBuiltin::new(OpCode::new(0x20, "JSR", 3, 6, AddressingMode::Absolute),JSR::jsr),
+        InstructionBuiltin::new(OpCode::new(0x60, "RTS", 1, 6, AddressingMode::NoneAddressing),RTS::rts),
         InstructionBuiltin::new(OpCode::new(0x40, "RTI", 1, 6, AddressingMode::NoneAddressing),RTI::rti),
+
+        // 分支
+        InstructionBuiltin::new(OpCode::new(0xD0, "BNE", 2, 2/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::Relative),BNE::bne),
+        InstructionBuiltin::new(OpCode::new(0x70, "BVS", 2, 2/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::Relative),BVS::bvs),
+        InstructionBuiltin::new(OpCode::new(0x50, "BVC", 2, 2/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::Relative),BVC::bvc),
+        InstructionBuiltin::new(OpCode::new(0x30, "BMI", 2, 2/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::Relative),BMI::bmi),
+        InstructionBuiltin::new(OpCode::new(0xF0, "BEQ", 2, 2/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::Relative),BEQ::beq),
+        InstructionBuiltin::new(OpCode::new(0xB0, "BCS", 2, 2/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::Relative),BCS::bcs),
+        InstructionBuiltin::new(OpCode::new(0x90, "BCC", 2, 2/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::Relative),BCC::bcc),
+        InstructionBuiltin::new(OpCode::new(0x10, "BPL", 2, 2/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::Relative),BPL::bpl),
+
+        InstructionBuiltin::new(OpCode::new(0x24, "BIT", 2, 3, AddressingMode::ZeroPage),BIT::bit),
+        InstructionBuiltin::new(OpCode::new(0x2C, "BIT", 3, 4, AddressingMode::Absolute),BIT::bit),
+
+        // 加载与存储
         InstructionBuiltin::new(OpCode::new(0xA9, "LDA", 2, 2, AddressingMode::Immediate),LDA::lda),
         InstructionBuiltin::new(OpCode::new(0xA5, "LDA", 2, 3, AddressingMode::ZeroPage),LDA::lda),
         InstructionBuiltin::new(OpCode::new(0xB5, "LDA", 2, 4, AddressingMode::ZeroPage_X),LDA::lda),
@@ -67,49 +147,25 @@ lazy_static! {
         /*
         "Absolute_X"：使用绝对地址（16 位）和 X 寄存器的值来计算内存地址。X 寄存器的值会与绝对地址相加，得到最终的内存地址。
 
-        "+1 if page crossed"：如果在计算得到的内存地址跨越了页（page），则将最终的内存地址再加上 1。
+        "+1 if page crossed"：如果在计算得到的内存地址跨越了页（page），则这条指令多消耗 1 个周期。
 
         在 6502 微处理器中，内存被划分为多个页，每个页的大小为 256 字节。当使用 "Absolute_X" 寻址模式时，如果计算得到的内存地址跨越了当前页的边界，就会发生页跨越（page crossing）。
-        为了处理页跨越情况，需要将最终的内存地址再加上 1。
+        CPU 需要额外一个周期来修正地址的高字节, 见 CPU::get_operand_address_for_read。
         */
         InstructionBuiltin::new(OpCode::new(0xBD, "LDA", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_X),LDA::lda),
         InstructionBuiltin::new(OpCode::new(0xB9, "LDA", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_Y),LDA::lda),
         InstructionBuiltin::new(OpCode::new(0xA1, "LDA", 2, 6, AddressingMode::Indirect_X),LDA::lda),
         InstructionBuiltin::new(OpCode::new(0xB1, "LDA", 2, 5/*+1 if page crossed*/, AddressingMode::Indirect_Y),LDA::lda),
-        InstructionBuiltin::new(OpCode::new(0xA0,"LDY",2,2,AddressingMode::Immediate),LDY::ldy),
-        InstructionBuiltin::new(OpCode::new(0xA4,"LDY",2,3,AddressingMode::ZeroPage),LDY::ldy),
-        InstructionBuiltin::new(OpCode::new(0xB4,"LDY",2,4,AddressingMode::ZeroPage_X),LDY::ldy),
-        InstructionBuiltin::new(OpCode::new(0xAC,"LDY",3,4,AddressingMode::Absolute),LDY::ldy),
-        InstructionBuiltin::new(OpCode::new(0xBC,"LDY",3,4,AddressingMode::Absolute_X),LDY::ldy),
-        InstructionBuiltin::new(OpCode::new(0xA2,"LDX",2,2,AddressingMode::Immediate),LDX::ldx),
-        InstructionBuiltin::new(OpCode::new(0xA6,"LDX",2,3,AddressingMode::ZeroPage),LDX::ldx),
-        InstructionBuiltin::new(OpCode::new(0xB6,"LDX",2,4,AddressingMode::ZeroPage_Y),LDX::ldx),
-        InstructionBuiltin::new(OpCode::new(0xAE,"LDX",3,4,AddressingMode::Absolute),LDX::ldx),
-        InstructionBuiltin::new(OpCode::new(0xBE,"LDX",3,4,AddressingMode::Absolute_Y),LDX::ldx),
-        InstructionBuiltin::new(OpCode::new(0x29,"AND",2,2,AddressingMode::Immediate),AND::and),
-        InstructionBuiltin::new(OpCode::new(0x25,"AND",2,3,AddressingMode::ZeroPage),AND::and),
-        InstructionBuiltin::new(OpCode::new(0x35,"AND",2,4,AddressingMode::ZeroPage_X),AND::and),
-        InstructionBuiltin::new(OpCode::new(0x2D,"AND",3,4,AddressingMode::Absolute),AND::and),
-        InstructionBuiltin::new(OpCode::new(0x3D,"AND",3,4,AddressingMode::Absolute_X),AND::and),
-        InstructionBuiltin::new(OpCode::new(0x39,"AND",3,4,AddressingMode::Absolute_Y),AND::and),
-        InstructionBuiltin::new(OpCode::new(0x21,"AND",2,6,AddressingMode::Indirect_X),AND::and),
-        InstructionBuiltin::new(OpCode::new(0x31,"AND",2,5,AddressingMode::Indirect_Y),AND::and),
-        InstructionBuiltin::new(OpCode::new(0x09,"ORA",2,2,AddressingMode::Immediate),ORA::ora),
-        InstructionBuiltin::new(OpCode::new(0x05,"ORA",2,3,AddressingMode::ZeroPage),ORA::ora),
-        InstructionBuiltin::new(OpCode::new(0x15,"ORA",2,4,AddressingMode::ZeroPage_X),ORA::ora),
-        InstructionBuiltin::new(OpCode::new(0x0D,"ORA",3,4,AddressingMode::Absolute),ORA::ora),
-        InstructionBuiltin::new(OpCode::new(0x1D,"ORA",3,4,AddressingMode::Absolute_X),ORA::ora),
-        InstructionBuiltin::new(OpCode::new(0x19,"ORA",3,4,AddressingMode::Absolute_Y),ORA::ora),
-        InstructionBuiltin::new(OpCode::new(0x01,"ORA",2,6,AddressingMode::Indirect_X),ORA::ora),
-        InstructionBuiltin::new(OpCode::new(0x11,"ORA",2,5,AddressingMode::Indirect_Y),ORA::ora),
-        InstructionBuiltin::new(OpCode::new(0x49,"EOR",2,2,AddressingMode::Immediate),EOR::eor),
-        InstructionBuiltin::new(OpCode::new(0x45,"EOR",2,3,AddressingMode::ZeroPage),EOR::eor),
-        InstructionBuiltin::new(OpCode::new(0x55,"EOR",2,4,AddressingMode::ZeroPage_Y),EOR::eor),
-        InstructionBuiltin::new(OpCode::new(0x4D,"EOR",3,4,AddressingMode::Absolute),EOR::eor),
-        InstructionBuiltin::new(OpCode::new(0x5D,"EOR",3,4,AddressingMode::Absolute_X),EOR::eor),
-        InstructionBuiltin::new(OpCode::new(0x59,"EOR",3,4,AddressingMode::Absolute_Y),EOR::eor),
-        InstructionBuiltin::new(OpCode::new(0x41,"EOR",2,6,AddressingMode::Indirect_X),EOR::eor),
-        InstructionBuiltin::new(OpCode::new(0x51,"EOR",2,5,AddressingMode::Indirect_Y),EOR::eor),
+        InstructionBuiltin::new(OpCode::new(0xA2, "LDX", 2, 2, AddressingMode::Immediate),LDX::ldx),
+        InstructionBuiltin::new(OpCode::new(0xA6, "LDX", 2, 3, AddressingMode::ZeroPage),LDX::ldx),
+        InstructionBuiltin::new(OpCode::new(0xB6, "LDX", 2, 4, AddressingMode::ZeroPage_Y),LDX::ldx),
+        InstructionBuiltin::new(OpCode::new(0xAE, "LDX", 3, 4, AddressingMode::Absolute),LDX::ldx),
+        InstructionBuiltin::new(OpCode::new(0xBE, "LDX", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_Y),LDX::ldx),
+        InstructionBuiltin::new(OpCode::new(0xA0, "LDY", 2, 2, AddressingMode::Immediate),LDY::ldy),
+        InstructionBuiltin::new(OpCode::new(0xA4, "LDY", 2, 3, AddressingMode::ZeroPage),LDY::ldy),
+        InstructionBuiltin::new(OpCode::new(0xB4, "LDY", 2, 4, AddressingMode::ZeroPage_X),LDY::ldy),
+        InstructionBuiltin::new(OpCode::new(0xAC, "LDY", 3, 4, AddressingMode::Absolute),LDY::ldy),
+        InstructionBuiltin::new(OpCode::new(0xBC, "LDY", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_X),LDY::ldy),
         InstructionBuiltin::new(OpCode::new(0x85, "STA", 2, 3, AddressingMode::ZeroPage),STA::sta),
         InstructionBuiltin::new(OpCode::new(0x95, "STA", 2, 4, AddressingMode::ZeroPage_X),STA::sta),
         InstructionBuiltin::new(OpCode::new(0x8D, "STA", 3, 4, AddressingMode::Absolute),STA::sta),
@@ -117,52 +173,291 @@ lazy_static! {
         InstructionBuiltin::new(OpCode::new(0x99, "STA", 3, 5, AddressingMode::Absolute_Y),STA::sta),
         InstructionBuiltin::new(OpCode::new(0x81, "STA", 2, 6, AddressingMode::Indirect_X),STA::sta),
         InstructionBuiltin::new(OpCode::new(0x91, "STA", 2, 6, AddressingMode::Indirect_Y),STA::sta),
-        InstructionBuiltin::new(OpCode::new(0x69, "ADC", 2, 2, AddressingMode::Immediate),ADC::adc),
-        InstructionBuiltin::new(OpCode::new(0x65, "ADC", 2, 3, AddressingMode::ZeroPage),ADC::adc),
-        InstructionBuiltin::new(OpCode::new(0x75, "ADC", 2, 4, AddressingMode::ZeroPage_X),ADC::adc),
-        InstructionBuiltin::new(OpCode::new(0x6D, "ADC", 3, 4, AddressingMode::Absolute),ADC::adc),
-        InstructionBuiltin::new(OpCode::new(0x7D, "ADC", 3, 4, AddressingMode::Absolute_X),ADC::adc),
-        InstructionBuiltin::new(OpCode::new(0x79, "ADC", 3, 4, AddressingMode::Absolute_Y),ADC::adc),
-        InstructionBuiltin::new(OpCode::new(0x61, "ADC", 2, 6, AddressingMode::Indirect_X),ADC::adc),
-        InstructionBuiltin::new(OpCode::new(0x71, "ADC", 2, 5, AddressingMode::Indirect_Y),ADC::adc),
-        InstructionBuiltin::new(OpCode::new(0x2a, "ROL", 1, 2, AddressingMode::NoneAddressing),ROL::rol),
-        InstructionBuiltin::new(OpCode::new(0x26, "ROL", 2, 5, AddressingMode::ZeroPage),ROL::rol),
-        InstructionBuiltin::new(OpCode::new(0x36, "ROL", 2, 6, AddressingMode::ZeroPage_X),ROL::rol),
-        InstructionBuiltin::new(OpCode::new(0x2e, "ROL", 3, 6, AddressingMode::Absolute),ROL::rol),
-        InstructionBuiltin::new(OpCode::new(0x3e, "ROL", 3, 7, AddressingMode::Absolute_X),ROL::rol),
-        InstructionBuiltin::new(OpCode::new(0x6a, "ROR", 1, 2, AddressingMode::NoneAddressing),ROR::ror),
-        InstructionBuiltin::new(OpCode::new(0x66, "ROR", 2, 5, AddressingMode::ZeroPage),ROR::ror),
-        InstructionBuiltin::new(OpCode::new(0x76, "ROR", 2, 6, AddressingMode::ZeroPage_X),ROR::ror),
-        InstructionBuiltin::new(OpCode::new(0x6e, "ROR", 3, 6, AddressingMode::Absolute),ROR::ror),
-        InstructionBuiltin::new(OpCode::new(0x7e, "ROR", 3, 7, AddressingMode::Absolute_X),ROR::ror),
-        InstructionBuiltin::new(OpCode::new(0x0A,"ASL",1,2,AddressingMode::NoneAddressing),ASL::asl),
-        InstructionBuiltin::new(OpCode::new(0x06,"ASL",2,5,AddressingMode::ZeroPage),ASL::asl),
-        InstructionBuiltin::new(OpCode::new(0x16,"ASL",2,6,AddressingMode::ZeroPage_X),ASL::asl),
-        InstructionBuiltin::new(OpCode::new(0x0E,"ASL",3,6,AddressingMode::Absolute),ASL::asl),
-        InstructionBuiltin::new(OpCode::new(0x1E,"ASL",3,7,AddressingMode::Absolute_X),ASL::asl),
-        InstructionBuiltin::new(OpCode::new(0x4A,"LSR",1,2,AddressingMode::NoneAddressing),LSR::lsr),
-        InstructionBuiltin::new(OpCode::new(0x46,"LSR",2,5,AddressingMode::ZeroPage),LSR::lsr),
-        InstructionBuiltin::new(OpCode::new(0x56,"LSR",2,6,AddressingMode::ZeroPage_X),LSR::lsr),
-        InstructionBuiltin::new(OpCode::new(0x4E,"LSR",3,6,AddressingMode::Absolute),LSR::lsr),
-        InstructionBuiltin::new(OpCode::new(0x5E,"LSR",3,7,AddressingMode::Absolute_X),LSR::lsr),
-        InstructionBuiltin::new(OpCode::new(0xE9,"SBC",2,2,AddressingMode::Immediate),SBC::sbc),
-        InstructionBuiltin::new(OpCode::new(0xE5,"SBC",2,3,AddressingMode::ZeroPage),SBC::sbc),
-        InstructionBuiltin::new(OpCode::new(0xF5,"SBC",2,4,AddressingMode::ZeroPage_X),SBC::sbc),
-        InstructionBuiltin::new(OpCode::new(0xED,"SBC",3,4,AddressingMode::Absolute),SBC::sbc),
-        InstructionBuiltin::new(OpCode::new(0xFD,"SBC",3,4,AddressingMode::Absolute_X),SBC::sbc),
-        InstructionBuiltin::new(OpCode::new(0xF9,"SBC",3,4,AddressingMode::Absolute_Y),SBC::sbc),
-        InstructionBuiltin::new(OpCode::new(0xE1,"SBC",2,6,AddressingMode::Indirect_X),SBC::sbc),
-        InstructionBuiltin::new(OpCode::new(0xF1,"SBC",2,5,AddressingMode::Indirect_Y),SBC::sbc),
+        InstructionBuiltin::new(OpCode::new(0x86, "STX", 2, 3, AddressingMode::ZeroPage),STX::stx),
+        InstructionBuiltin::new(OpCode::new(0x96, "STX", 2, 4, AddressingMode::ZeroPage_Y),STX::stx),
+        InstructionBuiltin::new(OpCode::new(0x8E, "STX", 3, 4, AddressingMode::Absolute),STX::stx),
+        InstructionBuiltin::new(OpCode::new(0x84, "STY", 2, 3, AddressingMode::ZeroPage),STY::sty),
+        InstructionBuiltin::new(OpCode::new(0x94, "STY", 2, 4, AddressingMode::ZeroPage_X),STY::sty),
+        InstructionBuiltin::new(OpCode::new(0x8C, "STY", 3, 4, AddressingMode::Absolute),STY::sty),
+
+        // 标志位
+        InstructionBuiltin::new(OpCode::new(0xD8, "CLD", 1, 2, AddressingMode::NoneAddressing),CLD::cld),
+        InstructionBuiltin::new(OpCode::new(0x58, "CLI", 1, 2, AddressingMode::NoneAddressing),CLI::cli),
+        InstructionBuiltin::new(OpCode::new(0xB8, "CLV", 1, 2, AddressingMode::NoneAddressing),CLV::clv),
+        InstructionBuiltin::new(OpCode::new(0x18, "CLC", 1, 2, AddressingMode::NoneAddressing),CLC::clc),
+        InstructionBuiltin::new(OpCode::new(0x38, "SEC", 1, 2, AddressingMode::NoneAddressing),SEC::sec),
+        InstructionBuiltin::new(OpCode::new(0x78, "SEI", 1, 2, AddressingMode::NoneAddressing),SEI::sei),
+        InstructionBuiltin::new(OpCode::new(0xF8, "SED", 1, 2, AddressingMode::NoneAddressing),SED::sed),
+
+        // 寄存器传送
+        InstructionBuiltin::new(OpCode::new(0xAA, "TAX", 1, 2, AddressingMode::NoneAddressing),TAX::tax),
+        InstructionBuiltin::new(OpCode::new(0xA8, "TAY", 1, 2, AddressingMode::NoneAddressing),TAY::tay),
+        InstructionBuiltin::new(OpCode::new(0xBA, "TSX", 1, 2, AddressingMode::NoneAddressing),TSX::tsx),
+        InstructionBuiltin::new(OpCode::new(0x8A, "TXA", 1, 2, AddressingMode::NoneAddressing),TXA::txa),
+        InstructionBuiltin::new(OpCode::new(0x9A, "TXS", 1, 2, AddressingMode::NoneAddressing),TXS::txs),
+        InstructionBuiltin::new(OpCode::new(0x98, "TYA", 1, 2, AddressingMode::NoneAddressing),TYA::tya),
+
+        // 栈操作
+        InstructionBuiltin::new(OpCode::new(0x48, "PHA", 1, 3, AddressingMode::NoneAddressing),PHA::pha),
+        InstructionBuiltin::new(OpCode::new(0x68, "PLA", 1, 4, AddressingMode::NoneAddressing),PLA::pla),
+        InstructionBuiltin::new(OpCode::new(0x08, "PHP", 1, 3, AddressingMode::NoneAddressing),PHP::php),
+        InstructionBuiltin::new(OpCode::new(0x28, "PLP", 1, 4, AddressingMode::NoneAddressing),PLP::plp),
     ];
         pub static ref CPU_INSTRUCTION_BUILTIN_MAP:HashMap<u8,&'static InstructionBuiltin>={
       let mut map:HashMap<u8,&'static InstructionBuiltin>=HashMap::new();
         for builtin in &*CPU_INSTRUCTION_BUILTIN{
             map.insert(builtin.op.code,builtin);
         }
-        return map;
+        #[cfg(feature = "unofficial-opcodes")]
+        for builtin in &*UNOFFICIAL_INSTRUCTION_BUILTIN{
+            map.insert(builtin.op.code,builtin);
+        }
+        map
     };
     }
 
+/*
+65C02 (WDC W65C02S) 新增和修改的指令, 覆盖 NMOS 指令表中同一个操作码
+ NMOS 的非官方指令在 65C02 上不存在, 对应的操作码大多变成了不同长度的 NOP
+ STP (0xDB) 让 CPU 停机直到复位, 和 KIL 一样报告 CpuError::Jammed, WAI (0xCB) 没有实现
+http://www.6502.org/tutorials/65c02opcodes.html
+ */
+lazy_static! {
+    pub static ref CMOS_INSTRUCTION_BUILTIN:Vec<InstructionBuiltin>=vec![
+        // 新指令
+        InstructionBuiltin::new(OpCode::new(0x80, "BRA", 2, 2/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::Relative),BRA::bra),
+        InstructionBuiltin::new(OpCode::new(0xDA, "PHX", 1, 3, AddressingMode::NoneAddressing),PHX::phx),
+        InstructionBuiltin::new(OpCode::new(0x5A, "PHY", 1, 3, AddressingMode::NoneAddressing),PHY::phy),
+        InstructionBuiltin::new(OpCode::new(0xFA, "PLX", 1, 4, AddressingMode::NoneAddressing),PLX::plx),
+        InstructionBuiltin::new(OpCode::new(0x7A, "PLY", 1, 4, AddressingMode::NoneAddressing),PLY::ply),
+        InstructionBuiltin::new(OpCode::new(0x64, "STZ", 2, 3, AddressingMode::ZeroPage),STZ::stz),
+        InstructionBuiltin::new(OpCode::new(0x74, "STZ", 2, 4, AddressingMode::ZeroPage_X),STZ::stz),
+        InstructionBuiltin::new(OpCode::new(0x9C, "STZ", 3, 4, AddressingMode::Absolute),STZ::stz),
+        InstructionBuiltin::new(OpCode::new(0x9E, "STZ", 3, 5, AddressingMode::Absolute_X),STZ::stz),
+        InstructionBuiltin::new(OpCode::new(0x14, "TRB", 2, 5, AddressingMode::ZeroPage),TRB::trb),
+        InstructionBuiltin::new(OpCode::new(0x1C, "TRB", 3, 6, AddressingMode::Absolute),TRB::trb),
+        InstructionBuiltin::new(OpCode::new(0x04, "TSB", 2, 5, AddressingMode::ZeroPage),TSB::tsb),
+        InstructionBuiltin::new(OpCode::new(0x0C, "TSB", 3, 6, AddressingMode::Absolute),TSB::tsb),
+
+        // 已有指令的新寻址方式
+        InstructionBuiltin::new(OpCode::new(0x12, "ORA", 2, 5, AddressingMode::Indirect_ZeroPage),ORA::ora),
+        InstructionBuiltin::new(OpCode::new(0x32, "AND", 2, 5, AddressingMode::Indirect_ZeroPage),AND::and),
+        InstructionBuiltin::new(OpCode::new(0x52, "EOR", 2, 5, AddressingMode::Indirect_ZeroPage),EOR::eor),
+        InstructionBuiltin::new(OpCode::new(0x72, "ADC", 2, 5, AddressingMode::Indirect_ZeroPage),ADC::adc),
+        InstructionBuiltin::new(OpCode::new(0x92, "STA", 2, 5, AddressingMode::Indirect_ZeroPage),STA::sta),
+        InstructionBuiltin::new(OpCode::new(0xB2, "LDA", 2, 5, AddressingMode::Indirect_ZeroPage),LDA::lda),
+        InstructionBuiltin::new(OpCode::new(0xD2, "CMP", 2, 5, AddressingMode::Indirect_ZeroPage),CMP::cmp),
+        InstructionBuiltin::new(OpCode::new(0xF2, "SBC", 2, 5, AddressingMode::Indirect_ZeroPage),SBC::sbc),
+        InstructionBuiltin::new(OpCode::new(0x89, "BIT", 2, 2, AddressingMode::Immediate),BIT::bit),
+        InstructionBuiltin::new(OpCode::new(0x34, "BIT", 2, 4, AddressingMode::ZeroPage_X),BIT::bit),
+        InstructionBuiltin::new(OpCode::new(0x3C, "BIT", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_X),BIT::bit),
+        InstructionBuiltin::new(OpCode::new(0x1A, "INC", 1, 2, AddressingMode::Accumulator),INC::inc),
+        InstructionBuiltin::new(OpCode::new(0x3A, "DEC", 1, 2, AddressingMode::Accumulator),DEC::dec),
+        // 修正了指针在页尾时的 bug, 多一个周期
+        InstructionBuiltin::new(OpCode::new(0x6C, "JMP", 3, 6, AddressingMode::Indirect),JMP::jmp),
+        InstructionBuiltin::new(OpCode::new(0x7C, "JMP", 3, 6, AddressingMode::Indirect_Absolute_X),JMP::jmp),
+
+        // 位操作, 操作码的高 3 位是位号
+        InstructionBuiltin::new(OpCode::new(0x07, "RMB0", 2, 5, AddressingMode::ZeroPage),RMB::rmb::<0>),
+        InstructionBuiltin::new(OpCode::new(0x17, "RMB1", 2, 5, AddressingMode::ZeroPage),RMB::rmb::<1>),
+        InstructionBuiltin::new(OpCode::new(0x27, "RMB2", 2, 5, AddressingMode::ZeroPage),RMB::rmb::<2>),
+        InstructionBuiltin::new(OpCode::new(0x37, "RMB3", 2, 5, AddressingMode::ZeroPage),RMB::rmb::<3>),
+        InstructionBuiltin::new(OpCode::new(0x47, "RMB4", 2, 5, AddressingMode::ZeroPage),RMB::rmb::<4>),
+        InstructionBuiltin::new(OpCode::new(0x57, "RMB5", 2, 5, AddressingMode::ZeroPage),RMB::rmb::<5>),
+        InstructionBuiltin::new(OpCode::new(0x67, "RMB6", 2, 5, AddressingMode::ZeroPage),RMB::rmb::<6>),
+        InstructionBuiltin::new(OpCode::new(0x77, "RMB7", 2, 5, AddressingMode::ZeroPage),RMB::rmb::<7>),
+        InstructionBuiltin::new(OpCode::new(0x87, "SMB0", 2, 5, AddressingMode::ZeroPage),SMB::smb::<0>),
+        InstructionBuiltin::new(OpCode::new(0x97, "SMB1", 2, 5, AddressingMode::ZeroPage),SMB::smb::<1>),
+        InstructionBuiltin::new(OpCode::new(0xA7, "SMB2", 2, 5, AddressingMode::ZeroPage),SMB::smb::<2>),
+        InstructionBuiltin::new(OpCode::new(0xB7, "SMB3", 2, 5, AddressingMode::ZeroPage),SMB::smb::<3>),
+        InstructionBuiltin::new(OpCode::new(0xC7, "SMB4", 2, 5, AddressingMode::ZeroPage),SMB::smb::<4>),
+        InstructionBuiltin::new(OpCode::new(0xD7, "SMB5", 2, 5, AddressingMode::ZeroPage),SMB::smb::<5>),
+        InstructionBuiltin::new(OpCode::new(0xE7, "SMB6", 2, 5, AddressingMode::ZeroPage),SMB::smb::<6>),
+        InstructionBuiltin::new(OpCode::new(0xF7, "SMB7", 2, 5, AddressingMode::ZeroPage),SMB::smb::<7>),
+        InstructionBuiltin::new(OpCode::new(0x0F, "BBR0", 3, 5/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::ZeroPage_Relative),BBR::bbr::<0>),
+        InstructionBuiltin::new(OpCode::new(0x1F, "BBR1", 3, 5/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::ZeroPage_Relative),BBR::bbr::<1>),
+        InstructionBuiltin::new(OpCode::new(0x2F, "BBR2", 3, 5/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::ZeroPage_Relative),BBR::bbr::<2>),
+        InstructionBuiltin::new(OpCode::new(0x3F, "BBR3", 3, 5/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::ZeroPage_Relative),BBR::bbr::<3>),
+        InstructionBuiltin::new(OpCode::new(0x4F, "BBR4", 3, 5/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::ZeroPage_Relative),BBR::bbr::<4>),
+        InstructionBuiltin::new(OpCode::new(0x5F, "BBR5", 3, 5/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::ZeroPage_Relative),BBR::bbr::<5>),
+        InstructionBuiltin::new(OpCode::new(0x6F, "BBR6", 3, 5/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::ZeroPage_Relative),BBR::bbr::<6>),
+        InstructionBuiltin::new(OpCode::new(0x7F, "BBR7", 3, 5/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::ZeroPage_Relative),BBR::bbr::<7>),
+        InstructionBuiltin::new(OpCode::new(0x8F, "BBS0", 3, 5/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::ZeroPage_Relative),BBS::bbs::<0>),
+        InstructionBuiltin::new(OpCode::new(0x9F, "BBS1", 3, 5/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::ZeroPage_Relative),BBS::bbs::<1>),
+        InstructionBuiltin::new(OpCode::new(0xAF, "BBS2", 3, 5/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::ZeroPage_Relative),BBS::bbs::<2>),
+        InstructionBuiltin::new(OpCode::new(0xBF, "BBS3", 3, 5/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::ZeroPage_Relative),BBS::bbs::<3>),
+        InstructionBuiltin::new(OpCode::new(0xCF, "BBS4", 3, 5/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::ZeroPage_Relative),BBS::bbs::<4>),
+        InstructionBuiltin::new(OpCode::new(0xDF, "BBS5", 3, 5/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::ZeroPage_Relative),BBS::bbs::<5>),
+        InstructionBuiltin::new(OpCode::new(0xEF, "BBS6", 3, 5/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::ZeroPage_Relative),BBS::bbs::<6>),
+        InstructionBuiltin::new(OpCode::new(0xFF, "BBS7", 3, 5/*+1 if branch succeeds +2 if to a new page*/, AddressingMode::ZeroPage_Relative),BBS::bbs::<7>),
+
+        // 其余未定义的操作码都是 NOP
+        InstructionBuiltin::new(OpCode::new(0x03, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x13, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x23, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x33, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x43, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x53, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x63, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x73, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x83, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x93, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0xA3, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0xB3, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0xC3, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0xD3, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0xE3, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0xF3, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x0B, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x1B, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x2B, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x3B, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x4B, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x5B, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x6B, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x7B, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x8B, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x9B, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0xAB, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0xBB, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0xEB, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0xFB, "NOP", 1, 1, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x02, "NOP", 2, 2, AddressingMode::Immediate),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x22, "NOP", 2, 2, AddressingMode::Immediate),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x42, "NOP", 2, 2, AddressingMode::Immediate),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x62, "NOP", 2, 2, AddressingMode::Immediate),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x82, "NOP", 2, 2, AddressingMode::Immediate),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0xC2, "NOP", 2, 2, AddressingMode::Immediate),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0xE2, "NOP", 2, 2, AddressingMode::Immediate),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0x44, "NOP", 2, 3, AddressingMode::ZeroPage),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::new(0x54, "NOP", 2, 4, AddressingMode::ZeroPage_X),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::new(0xD4, "NOP", 2, 4, AddressingMode::ZeroPage_X),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::new(0xF4, "NOP", 2, 4, AddressingMode::ZeroPage_X),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::new(0x5C, "NOP", 3, 8, AddressingMode::Absolute),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0xDC, "NOP", 3, 4, AddressingMode::Absolute),NOP::nop),
+        InstructionBuiltin::new(OpCode::new(0xFC, "NOP", 3, 4, AddressingMode::Absolute),NOP::nop),
+    ];
+    pub static ref CMOS_INSTRUCTION_BUILTIN_MAP:HashMap<u8,&'static InstructionBuiltin>={
+        let mut map:HashMap<u8,&'static InstructionBuiltin>=HashMap::new();
+        for builtin in CPU_INSTRUCTION_BUILTIN.iter().chain(CMOS_INSTRUCTION_BUILTIN.iter()){
+            map.insert(builtin.op.code,builtin);
+        }
+        map
+    };
+}
+
+// CPU 型号对应的指令表
+pub fn instruction_set(variant: CpuVariant) -> &'static HashMap<u8, &'static InstructionBuiltin> {
+    match variant {
+        CpuVariant::Nes2A03 | CpuVariant::Nmos6502 => &CPU_INSTRUCTION_BUILTIN_MAP,
+        CpuVariant::Cmos65C02 => &CMOS_INSTRUCTION_BUILTIN_MAP,
+    }
+}
+
+/*
+非官方指令: 官方文档没有列出, 但是 NMOS 6502 (包括 NES 的 2A03) 的译码电路实际会执行的操作码
+不少商业游戏和测试 ROM 用到了它们, 打开 unofficial-opcodes 特性才会加入指令表
+ 不稳定的 (XAA, AHX, TAS...) 和让 CPU 卡死的 (KIL) 操作码没有实现
+https://www.nesdev.org/wiki/CPU_unofficial_opcodes
+ */
+#[cfg(feature = "unofficial-opcodes")]
+lazy_static! {
+    pub static ref UNOFFICIAL_INSTRUCTION_BUILTIN:Vec<InstructionBuiltin>=vec![
+        // 加载与存储
+        InstructionBuiltin::new(OpCode::unofficial(0xA7, "LAX", 2, 3, AddressingMode::ZeroPage),LAX::lax),
+        InstructionBuiltin::new(OpCode::unofficial(0xB7, "LAX", 2, 4, AddressingMode::ZeroPage_Y),LAX::lax),
+        InstructionBuiltin::new(OpCode::unofficial(0xAF, "LAX", 3, 4, AddressingMode::Absolute),LAX::lax),
+        InstructionBuiltin::new(OpCode::unofficial(0xBF, "LAX", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_Y),LAX::lax),
+        InstructionBuiltin::new(OpCode::unofficial(0xA3, "LAX", 2, 6, AddressingMode::Indirect_X),LAX::lax),
+        InstructionBuiltin::new(OpCode::unofficial(0xB3, "LAX", 2, 5/*+1 if page crossed*/, AddressingMode::Indirect_Y),LAX::lax),
+        InstructionBuiltin::new(OpCode::unofficial(0x87, "SAX", 2, 3, AddressingMode::ZeroPage),SAX::sax),
+        InstructionBuiltin::new(OpCode::unofficial(0x97, "SAX", 2, 4, AddressingMode::ZeroPage_Y),SAX::sax),
+        InstructionBuiltin::new(OpCode::unofficial(0x8F, "SAX", 3, 4, AddressingMode::Absolute),SAX::sax),
+        InstructionBuiltin::new(OpCode::unofficial(0x83, "SAX", 2, 6, AddressingMode::Indirect_X),SAX::sax),
+
+        // 读-改-写之后再做一次算术或逻辑运算, 周期数和同样寻址方式的 INC/ASL 一样, 另外 Absolute_Y 和 Indirect_Y 也不区分是否跨页
+        InstructionBuiltin::new(OpCode::unofficial(0xC7, "DCP", 2, 5, AddressingMode::ZeroPage),DCP::dcp),
+        InstructionBuiltin::new(OpCode::unofficial(0xD7, "DCP", 2, 6, AddressingMode::ZeroPage_X),DCP::dcp),
+        InstructionBuiltin::new(OpCode::unofficial(0xCF, "DCP", 3, 6, AddressingMode::Absolute),DCP::dcp),
+        InstructionBuiltin::new(OpCode::unofficial(0xDF, "DCP", 3, 7, AddressingMode::Absolute_X),DCP::dcp),
+        InstructionBuiltin::new(OpCode::unofficial(0xDB, "DCP", 3, 7, AddressingMode::Absolute_Y),DCP::dcp),
+        InstructionBuiltin::new(OpCode::unofficial(0xC3, "DCP", 2, 8, AddressingMode::Indirect_X),DCP::dcp),
+        InstructionBuiltin::new(OpCode::unofficial(0xD3, "DCP", 2, 8, AddressingMode::Indirect_Y),DCP::dcp),
+        InstructionBuiltin::new(OpCode::unofficial(0xE7, "ISB", 2, 5, AddressingMode::ZeroPage),ISB::isb),
+        InstructionBuiltin::new(OpCode::unofficial(0xF7, "ISB", 2, 6, AddressingMode::ZeroPage_X),ISB::isb),
+        InstructionBuiltin::new(OpCode::unofficial(0xEF, "ISB", 3, 6, AddressingMode::Absolute),ISB::isb),
+        InstructionBuiltin::new(OpCode::unofficial(0xFF, "ISB", 3, 7, AddressingMode::Absolute_X),ISB::isb),
+        InstructionBuiltin::new(OpCode::unofficial(0xFB, "ISB", 3, 7, AddressingMode::Absolute_Y),ISB::isb),
+        InstructionBuiltin::new(OpCode::unofficial(0xE3, "ISB", 2, 8, AddressingMode::Indirect_X),ISB::isb),
+        InstructionBuiltin::new(OpCode::unofficial(0xF3, "ISB", 2, 8, AddressingMode::Indirect_Y),ISB::isb),
+        InstructionBuiltin::new(OpCode::unofficial(0x07, "SLO", 2, 5, AddressingMode::ZeroPage),SLO::slo),
+        InstructionBuiltin::new(OpCode::unofficial(0x17, "SLO", 2, 6, AddressingMode::ZeroPage_X),SLO::slo),
+        InstructionBuiltin::new(OpCode::unofficial(0x0F, "SLO", 3, 6, AddressingMode::Absolute),SLO::slo),
+        InstructionBuiltin::new(OpCode::unofficial(0x1F, "SLO", 3, 7, AddressingMode::Absolute_X),SLO::slo),
+        InstructionBuiltin::new(OpCode::unofficial(0x1B, "SLO", 3, 7, AddressingMode::Absolute_Y),SLO::slo),
+        InstructionBuiltin::new(OpCode::unofficial(0x03, "SLO", 2, 8, AddressingMode::Indirect_X),SLO::slo),
+        InstructionBuiltin::new(OpCode::unofficial(0x13, "SLO", 2, 8, AddressingMode::Indirect_Y),SLO::slo),
+        InstructionBuiltin::new(OpCode::unofficial(0x27, "RLA", 2, 5, AddressingMode::ZeroPage),RLA::rla),
+        InstructionBuiltin::new(OpCode::unofficial(0x37, "RLA", 2, 6, AddressingMode::ZeroPage_X),RLA::rla),
+        InstructionBuiltin::new(OpCode::unofficial(0x2F, "RLA", 3, 6, AddressingMode::Absolute),RLA::rla),
+        InstructionBuiltin::new(OpCode::unofficial(0x3F, "RLA", 3, 7, AddressingMode::Absolute_X),RLA::rla),
+        InstructionBuiltin::new(OpCode::unofficial(0x3B, "RLA", 3, 7, AddressingMode::Absolute_Y),RLA::rla),
+        InstructionBuiltin::new(OpCode::unofficial(0x23, "RLA", 2, 8, AddressingMode::Indirect_X),RLA::rla),
+        InstructionBuiltin::new(OpCode::unofficial(0x33, "RLA", 2, 8, AddressingMode::Indirect_Y),RLA::rla),
+        InstructionBuiltin::new(OpCode::unofficial(0x47, "SRE", 2, 5, AddressingMode::ZeroPage),SRE::sre),
+        InstructionBuiltin::new(OpCode::unofficial(0x57, "SRE", 2, 6, AddressingMode::ZeroPage_X),SRE::sre),
+        InstructionBuiltin::new(OpCode::unofficial(0x4F, "SRE", 3, 6, AddressingMode::Absolute),SRE::sre),
+        InstructionBuiltin::new(OpCode::unofficial(0x5F, "SRE", 3, 7, AddressingMode::Absolute_X),SRE::sre),
+        InstructionBuiltin::new(OpCode::unofficial(0x5B, "SRE", 3, 7, AddressingMode::Absolute_Y),SRE::sre),
+        InstructionBuiltin::new(OpCode::unofficial(0x43, "SRE", 2, 8, AddressingMode::Indirect_X),SRE::sre),
+        InstructionBuiltin::new(OpCode::unofficial(0x53, "SRE", 2, 8, AddressingMode::Indirect_Y),SRE::sre),
+        InstructionBuiltin::new(OpCode::unofficial(0x67, "RRA", 2, 5, AddressingMode::ZeroPage),RRA::rra),
+        InstructionBuiltin::new(OpCode::unofficial(0x77, "RRA", 2, 6, AddressingMode::ZeroPage_X),RRA::rra),
+        InstructionBuiltin::new(OpCode::unofficial(0x6F, "RRA", 3, 6, AddressingMode::Absolute),RRA::rra),
+        InstructionBuiltin::new(OpCode::unofficial(0x7F, "RRA", 3, 7, AddressingMode::Absolute_X),RRA::rra),
+        InstructionBuiltin::new(OpCode::unofficial(0x7B, "RRA", 3, 7, AddressingMode::Absolute_Y),RRA::rra),
+        InstructionBuiltin::new(OpCode::unofficial(0x63, "RRA", 2, 8, AddressingMode::Indirect_X),RRA::rra),
+        InstructionBuiltin::new(OpCode::unofficial(0x73, "RRA", 2, 8, AddressingMode::Indirect_Y),RRA::rra),
+
+        // 立即数
+        InstructionBuiltin::new(OpCode::unofficial(0xEB, "SBC", 2, 2, AddressingMode::Immediate),SBC::sbc),
+        InstructionBuiltin::new(OpCode::unofficial(0x0B, "ANC", 2, 2, AddressingMode::Immediate),ANC::anc),
+        InstructionBuiltin::new(OpCode::unofficial(0x2B, "ANC", 2, 2, AddressingMode::Immediate),ANC::anc),
+        InstructionBuiltin::new(OpCode::unofficial(0x4B, "ALR", 2, 2, AddressingMode::Immediate),ALR::alr),
+        InstructionBuiltin::new(OpCode::unofficial(0x6B, "ARR", 2, 2, AddressingMode::Immediate),ARR::arr),
+        InstructionBuiltin::new(OpCode::unofficial(0xCB, "AXS", 2, 2, AddressingMode::Immediate),AXS::axs),
+
+        // 各种长度的 NOP
+        InstructionBuiltin::new(OpCode::unofficial(0x1A, "NOP", 1, 2, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::unofficial(0x3A, "NOP", 1, 2, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::unofficial(0x5A, "NOP", 1, 2, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::unofficial(0x7A, "NOP", 1, 2, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::unofficial(0xDA, "NOP", 1, 2, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::unofficial(0xFA, "NOP", 1, 2, AddressingMode::NoneAddressing),NOP::nop),
+        InstructionBuiltin::new(OpCode::unofficial(0x80, "NOP", 2, 2, AddressingMode::Immediate),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0x82, "NOP", 2, 2, AddressingMode::Immediate),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0x89, "NOP", 2, 2, AddressingMode::Immediate),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0xC2, "NOP", 2, 2, AddressingMode::Immediate),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0xE2, "NOP", 2, 2, AddressingMode::Immediate),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0x04, "NOP", 2, 3, AddressingMode::ZeroPage),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0x44, "NOP", 2, 3, AddressingMode::ZeroPage),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0x64, "NOP", 2, 3, AddressingMode::ZeroPage),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0x14, "NOP", 2, 4, AddressingMode::ZeroPage_X),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0x34, "NOP", 2, 4, AddressingMode::ZeroPage_X),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0x54, "NOP", 2, 4, AddressingMode::ZeroPage_X),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0x74, "NOP", 2, 4, AddressingMode::ZeroPage_X),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0xD4, "NOP", 2, 4, AddressingMode::ZeroPage_X),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0xF4, "NOP", 2, 4, AddressingMode::ZeroPage_X),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0x0C, "NOP", 3, 4, AddressingMode::Absolute),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0x1C, "NOP", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_X),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0x3C, "NOP", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_X),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0x5C, "NOP", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_X),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0x7C, "NOP", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_X),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0xDC, "NOP", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_X),NOP::nop_read),
+        InstructionBuiltin::new(OpCode::unofficial(0xFC, "NOP", 3, 4/*+1 if page crossed*/, AddressingMode::Absolute_X),NOP::nop_read),
+    ];
+}
+
 pub mod addressing;
 
 pub mod TAX;
@@ -182,7 +477,8 @@ mod LSR;
 mod ROR;
 mod ROL;
 mod RTI;
-mod RIS;
+mod BRK;
+mod RTS;
 mod JSR;
 mod JMP;
 mod CPX;
@@ -192,3 +488,67 @@ mod DEC;
 mod DEX;
 mod DEY;
 mod CMP;
+mod BCC;
+mod BCS;
+mod BEQ;
+mod BNE;
+mod BMI;
+mod BPL;
+mod BVC;
+mod BVS;
+mod BIT;
+mod CLC;
+mod CLD;
+mod CLI;
+mod CLV;
+mod SEC;
+mod SED;
+mod SEI;
+mod NOP;
+mod PHA;
+mod PHP;
+mod PLA;
+mod PLP;
+mod STX;
+mod STY;
+mod TAY;
+mod TSX;
+mod TXA;
+mod TXS;
+mod TYA;
+#[cfg(feature = "unofficial-opcodes")]
+mod LAX;
+#[cfg(feature = "unofficial-opcodes")]
+mod SAX;
+#[cfg(feature = "unofficial-opcodes")]
+mod DCP;
+#[cfg(feature = "unofficial-opcodes")]
+mod ISB;
+#[cfg(feature = "unofficial-opcodes")]
+mod SLO;
+#[cfg(feature = "unofficial-opcodes")]
+mod RLA;
+#[cfg(feature = "unofficial-opcodes")]
+mod SRE;
+#[cfg(feature = "unofficial-opcodes")]
+mod RRA;
+#[cfg(feature = "unofficial-opcodes")]
+mod ANC;
+#[cfg(feature = "unofficial-opcodes")]
+mod ALR;
+#[cfg(feature = "unofficial-opcodes")]
+mod ARR;
+#[cfg(feature = "unofficial-opcodes")]
+mod AXS;
+mod BRA;
+mod PHX;
+mod PHY;
+mod PLX;
+mod PLY;
+mod STZ;
+mod TRB;
+mod TSB;
+mod RMB;
+mod SMB;
+mod BBR;
+mod BBS;
@@ -0,0 +1,47 @@
+/**
+6502 的三种中断, 处理过程相同, 区别在于向量地址和压栈的状态寄存器中 B 标志的值
+https://www.nesdev.org/wiki/CPU_interrupts
+https://www.nesdev.org/wiki/Status_flags#The_B_flag
+
+ 中断   向量地址   压栈的 B 标志
+ NMI    0xFFFA     0
+ IRQ    0xFFFE     0
+ BRK    0xFFFE     1
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptType {
+    Nmi,
+    Irq,
+    Brk,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interrupt {
+    pub itype: InterruptType,
+    pub vector_addr: u16,
+    // 压栈时状态寄存器的第 4, 5 位
+    pub b_flag_mask: u8,
+    // 硬件中断的响应过程消耗的周期数, BRK 的周期数已经算在指令里了
+    pub cpu_cycles: u8,
+}
+
+pub const NMI: Interrupt = Interrupt {
+    itype: InterruptType::Nmi,
+    vector_addr: 0xFFFA,
+    b_flag_mask: 0b0010_0000,
+    cpu_cycles: 7,
+};
+
+pub const IRQ: Interrupt = Interrupt {
+    itype: InterruptType::Irq,
+    vector_addr: 0xFFFE,
+    b_flag_mask: 0b0010_0000,
+    cpu_cycles: 7,
+};
+
+pub const BRK: Interrupt = Interrupt {
+    itype: InterruptType::Brk,
+    vector_addr: 0xFFFE,
+    b_flag_mask: 0b0011_0000,
+    cpu_cycles: 0,
+};
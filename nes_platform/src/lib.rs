@@ -0,0 +1,27 @@
+#![allow(unused_variables)]
+
+/*
+模拟器的核心部分, 不依赖 SDL, 可以在没有显示器的机器上运行
+窗口前端见 main.rs, 无界面的运行器见 bin/headless.rs
+ */
+pub mod cpu;
+pub mod memory;
+pub mod bus;
+pub mod cartridge;
+pub mod mapper;
+pub mod joypad;
+pub mod instruction;
+pub mod timing;
+pub mod ppu;
+pub mod interrupt;
+pub mod apu;
+pub mod checksum;
+pub mod savestate;
+pub mod rewind;
+pub mod movie;
+pub mod trace;
+pub mod snake;
+pub mod disasm;
+pub mod asm;
+pub mod debugger;
+pub mod headless;
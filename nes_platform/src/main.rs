@@ -1,24 +1,35 @@
-#![allow(unused_variables)]
-
-#[allow(dead_code)]
-mod cpu;
-mod memory;
 mod input;
-mod instruction;
-
 
+use std::fs;
+use std::path::Path;
 use std::thread;
 use std::time::Duration;
 use rand::Rng;
+use sdl2::audio::AudioSpecDesired;
 use sdl2::pixels::{Color, PixelFormatEnum};
-use cpu::*;
-use crate::input::handle_user_input;
+use nes_platform::cpu::*;
+use nes_platform::bus::NesBus;
+use nes_platform::cartridge::Cartridge;
+use nes_platform::checksum::crc32;
+use nes_platform::debugger::{DebugHook, DebugState, Debugger};
+use nes_platform::debugger::gdbstub::GdbStub;
+use nes_platform::joypad::Joypad;
+use nes_platform::movie::Movie;
+use nes_platform::ppu::frame::Frame;
+use nes_platform::rewind::Rewind;
+use nes_platform::savestate;
+use nes_platform::snake::{Snake, SNAKE_GAME_CODE, SNAKE_SLOWDOWN};
+use nes_platform::timing::{Pacer, Region};
+use crate::input::{Hotkey, Input, KeyBindings};
 
-/**
+/*
 https://bugzmanov.github.io/nes_ebook/chapter_1.html
 3.4 链接依赖失败  https://crates.io/crates/sdl2/0.36.0
  */
 
+// 倒带缓冲区默认最多使用的内存 (MB), 每帧保存一次时大约可以倒回几分钟, 可以用 --rewind-budget=MB 修改
+const DEFAULT_REWIND_BUDGET_MB: usize = 64;
+
 fn color(byte: u8) -> Color {
     match byte {
         0 => Color::BLACK,
@@ -55,11 +66,272 @@ fn read_screen_state(cpu: &mut CPU, frame: &mut [u8; 32 * 3 * 32]) -> bool {
         frame_idx += 3;
     }
 
-    return update;
+    update
+}
+
+/*
+存档失败只打印错误, 不影响游戏继续运行, 读档成功时返回 true
+ */
+fn handle_hotkey(cpu: &mut CPU, hotkey: Hotkey, state_path: &Path) -> bool {
+    let result = match hotkey {
+        Hotkey::SaveState => savestate::save_file(cpu, state_path),
+        Hotkey::LoadState => savestate::load_file(cpu, state_path),
+        // 倒带看的是按住的状态, 见 rewind_frame
+        Hotkey::Rewind => return false,
+    };
+    match result {
+        Ok(()) => {
+            println!("{:?}: {}", hotkey, state_path.display());
+            hotkey == Hotkey::LoadState
+        }
+        Err(e) => {
+            eprintln!("{:?} failed: {}", hotkey, e);
+            false
+        }
+    }
+}
+
+/*
+每帧处理一次这一帧按下的存档和读档键, 返回是否读了档
+ */
+fn handle_hotkeys(input: &mut Input, session: &MovieSession, cpu: &mut CPU, state_path: &Path) -> bool {
+    let mut loaded = false;
+    for hotkey in input.take_hotkeys() {
+        // 存档会打乱录像, 录像时不能使用
+        if session.is_active() {
+            eprintln!("{:?} is disabled while recording or playing a movie", hotkey);
+            continue;
+        }
+        loaded |= handle_hotkey(cpu, hotkey, state_path);
+    }
+    loaded
+}
+
+enum MovieMode {
+    Off,
+    Record(String),
+    Play,
+}
+
+/**
+录像: 录制时每帧记录手柄的按键, 回放时用录像里的按键代替键盘, 放完之后恢复键盘输入
+不录像时也使用确定的随机数种子, 打印出来的种子可以用 --seed 复现同一局贪吃蛇
+ */
+struct MovieSession {
+    movie: Movie,
+    mode: MovieMode,
+}
+
+impl MovieSession {
+    /*
+    --seed=N 指定随机数种子, --record=FILE 录制, 退出时保存, --play=FILE 回放, 使用录像里的种子
+     */
+    fn from_args(args: &[String], rom_crc32: u32) -> Self {
+        if let Some(path) = option_value(args, "--play") {
+            let movie = Movie::load(path).unwrap_or_else(|e| panic!("failed to load {}: {}", path, e));
+            if movie.rom_crc32.is_some_and(|crc| crc != rom_crc32) {
+                eprintln!("warning: {} was recorded with a different ROM", path);
+            }
+            return MovieSession { movie, mode: MovieMode::Play };
+        }
+        let seed = match option_value(args, "--seed") {
+            Some(seed) => seed.parse().unwrap_or_else(|_| panic!("bad seed `{}`", seed)),
+            None => {
+                let seed = rand::thread_rng().gen();
+                println!("seed: {}", seed);
+                seed
+            }
+        };
+        let mode = match option_value(args, "--record") {
+            Some(path) => MovieMode::Record(path.to_string()),
+            None => MovieMode::Off,
+        };
+        MovieSession { movie: Movie::new(seed, Some(rom_crc32)), mode }
+    }
+
+    fn is_active(&self) -> bool {
+        !matches!(self.mode, MovieMode::Off)
+    }
+
+    // 每帧开始之前调用一次
+    fn start_frame(&mut self, frame: u64, joypads: &mut [Joypad; 2]) {
+        match self.mode {
+            MovieMode::Off => {}
+            MovieMode::Record(_) => self.movie.record(joypads),
+            MovieMode::Play => {
+                if !self.movie.apply(frame, joypads) && frame == self.movie.len() as u64 {
+                    println!("movie finished at frame {}", frame);
+                }
+            }
+        }
+    }
+
+    fn finish(&self) {
+        if let MovieMode::Record(path) = &self.mode {
+            match self.movie.save(path) {
+                Ok(()) => println!("recorded {} frames to {}", self.movie.len(), path),
+                Err(e) => eprintln!("failed to save {}: {}", path, e),
+            }
+        }
+    }
+}
+
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().find_map(|arg| arg.strip_prefix(name)?.strip_prefix('='))
+}
+
+// --rewind-budget=MB 指定倒带缓冲区的内存预算, 0 表示不能倒带
+fn rewind_from_args(args: &[String]) -> Rewind {
+    let budget_mb = match option_value(args, "--rewind-budget") {
+        Some(budget) => budget.parse().unwrap_or_else(|_| panic!("bad rewind budget `{}`", budget)),
+        None => DEFAULT_REWIND_BUDGET_MB,
+    };
+    Rewind::new(budget_mb * 1024 * 1024, 1)
+}
+
+/*
+每帧结束时调用: 按住倒带键时退回一个保存的状态, 返回 true, 否则保存这一帧的状态
+退回之后再向前运行一帧把它画出来, 看起来就是画面在倒放
+ */
+fn rewind_frame(rewind: &mut Rewind, rewinding: bool, cpu: &mut CPU) -> bool {
+    if rewinding {
+        rewind.rewind(cpu)
+    } else {
+        rewind.record_frame(cpu);
+        false
+    }
+}
+
+/*
+调试器暂停时不执行指令, 但是继续处理窗口事件, 让窗口保持响应, 关闭窗口同样可以退出
+ */
+fn wait_for_debugger<F>(debugger: &mut Option<Box<dyn DebugHook>>, cpu: &mut CPU, mut handle_events: F) -> RunControl
+    where F: FnMut(&mut CPU) -> RunControl {
+    let Some(debugger) = debugger else {
+        return RunControl::Continue;
+    };
+    loop {
+        match debugger.before_instruction(cpu) {
+            DebugState::Running => return RunControl::Continue,
+            DebugState::Quit => return RunControl::Stop,
+            DebugState::Paused => {
+                if handle_events(cpu) == RunControl::Stop {
+                    return RunControl::Stop;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+}
+
+/*
+运行 iNES 格式的卡带, 每完成一帧就把 PPU 的画面画到窗口上, 并把这一帧的声音送给 SDL 播放
+ */
+fn run_rom(sdl: sdl2::Sdl, path: &str, mut debugger: Option<Box<dyn DebugHook>>, mut movie: MovieSession, mut rewind: Rewind) {
+    let cartridge = Cartridge::load(path).unwrap_or_else(|e| panic!("failed to load {}: {}", path, e));
+    let mut bus = NesBus::new(cartridge).unwrap_or_else(|e| panic!("failed to load {}: {}", path, e));
+
+    let video_subsystem = sdl.video().unwrap();
+    let window = video_subsystem.window("NES", (Frame::WIDTH * 3) as u32, (Frame::HEIGHT * 3) as u32)
+        .position_centered()
+        .build()
+        .unwrap();
+    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
+    let mut event_pump = sdl.event_pump().unwrap();
+    canvas.set_scale(3f32, 3f32).unwrap();
+    let creator = canvas.texture_creator();
+    let mut texture = creator
+        .create_texture_target(PixelFormatEnum::RGB24, Frame::WIDTH as u32, Frame::HEIGHT as u32)
+        .unwrap();
+
+    let audio_subsystem = sdl.audio().unwrap();
+    let spec = AudioSpecDesired { freq: Some(bus.apu.sample_rate() as i32), channels: Some(1), samples: None };
+    let audio = audio_subsystem.open_queue::<f32, _>(None, &spec).unwrap();
+    audio.resume();
+    // 最多缓存大约 0.1 秒的声音, 模拟落后时丢掉多余的采样, 避免声音越来越延迟
+    let max_queued_bytes = bus.apu.sample_rate() / 10 * std::mem::size_of::<f32>() as u32;
+
+    let mut input = Input::new(KeyBindings::default(), sdl.game_controller().ok());
+    // 存档放在卡带旁边, 文件名相同, 扩展名为 .state
+    let state_path = Path::new(path).with_extension("state");
+
+    let mut frame = 0;
+    movie.start_frame(frame, &mut bus.joypads);
+    let session = &mut movie;
+
+    let mut cpu = CPU::with_bus(Box::new(bus));
+    cpu.reset();
+    let mut pacer = Pacer::for_region(Region::Ntsc, cpu.cycles);
+    let result = cpu.run_with_callback(move |cpu| {
+        let control = wait_for_debugger(&mut debugger, cpu, |cpu| {
+            input.handle_events(&mut event_pump, &mut cpu.bus_mut::<NesBus>().unwrap().joypads)
+        });
+        if control == RunControl::Stop {
+            return RunControl::Stop;
+        }
+        let bus = cpu.bus_mut::<NesBus>().unwrap();
+        if !bus.ppu.frame_complete() {
+            return RunControl::Continue;
+        }
+        let samples = bus.apu.take_samples();
+        // 存档和倒带会打乱录像, 录像时不能使用
+        let rewinding = !session.is_active() && input.hotkey_held(Hotkey::Rewind);
+        // 倒带时不播放声音
+        if !rewinding && audio.size() < max_queued_bytes {
+            audio.queue_audio(&samples).unwrap();
+        }
+        texture.update(None, &bus.ppu.frame.data, Frame::WIDTH * 3).unwrap();
+        canvas.copy(&texture, None, None).unwrap();
+        canvas.present();
+        if input.handle_events(&mut event_pump, &mut bus.joypads) == RunControl::Stop {
+            return RunControl::Stop;
+        }
+        frame += 1;
+        session.start_frame(frame, &mut bus.joypads);
+        handle_hotkeys(&mut input, session, cpu, &state_path);
+        rewind_frame(&mut rewind, rewinding, cpu);
+        pacer.pace(cpu.cycles);
+        RunControl::Continue
+    });
+    if let Err(e) = result {
+        eprintln!("cpu error: {}", e);
+    }
+    movie.finish();
+}
+
+// GDB 远程调试默认监听的端口
+const GDB_DEFAULT_PORT: u16 = 6502;
+
+/*
+--debug 时从终端读取调试命令, --gdb[=端口] 时等待 GDB 客户端连接, 程序一开始都处于暂停状态
+ */
+fn debugger_from_args(args: &[String]) -> Option<Box<dyn DebugHook>> {
+    if args.iter().any(|arg| arg == "--debug") {
+        return Some(Box::new(Debugger::stdin()));
+    }
+    let port = args.iter().find_map(|arg| arg.strip_prefix("--gdb"))?;
+    let port = match port.strip_prefix('=') {
+        Some(port) => port.parse().unwrap_or_else(|_| panic!("bad gdb port `{}`", port)),
+        None if port.is_empty() => GDB_DEFAULT_PORT,
+        None => panic!("unknown option `--gdb{}`", port),
+    };
+    println!("waiting for gdb on 127.0.0.1:{}", port);
+    let stub = GdbStub::listen(("127.0.0.1", port)).unwrap_or_else(|e| panic!("gdb stub failed: {}", e));
+    Some(Box::new(stub))
 }
 
 fn main() {
     let sdl = sdl2::init().unwrap();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut debugger = debugger_from_args(&args);
+    // 命令行给出卡带路径时运行卡带, 否则运行贪吃蛇
+    if let Some(path) = args.iter().find(|arg| !arg.starts_with("--")) {
+        let rom = fs::read(path).unwrap_or_else(|e| panic!("failed to load {}: {}", path, e));
+        let movie = MovieSession::from_args(&args, crc32(&rom));
+        run_rom(sdl, path, debugger, movie, rewind_from_args(&args));
+        return;
+    }
+    let mut movie = MovieSession::from_args(&args, crc32(&SNAKE_GAME_CODE));
     let video_subsystem = sdl.video().unwrap();
     // 由于我们的游戏屏幕很小（32x32 像素），因此我们将比例因子设置为 10。
     let window = video_subsystem.window("Snake game", (32.0 * 10.0) as u32, (32.0 * 10.0) as u32)
@@ -76,47 +348,43 @@ fn main() {
     let creator = canvas.texture_creator();
     let mut texture = creator.create_texture_target(PixelFormatEnum::RGB24, 32, 32).unwrap();
 
-    // 游戏代码
-    let game_code = vec![
-        0x20, 0x06, 0x06, 0x20, 0x38, 0x06, 0x20, 0x0d, 0x06, 0x20, 0x2a, 0x06, 0x60, 0xa9, 0x02,
-        0x85, 0x02, 0xa9, 0x04, 0x85, 0x03, 0xa9, 0x11, 0x85, 0x10, 0xa9, 0x10, 0x85, 0x12, 0xa9,
-        0x0f, 0x85, 0x14, 0xa9, 0x04, 0x85, 0x11, 0x85, 0x13, 0x85, 0x15, 0x60, 0xa5, 0xfe, 0x85,
-        0x00, 0xa5, 0xfe, 0x29, 0x03, 0x18, 0x69, 0x02, 0x85, 0x01, 0x60, 0x20, 0x4d, 0x06, 0x20,
-        0x8d, 0x06, 0x20, 0xc3, 0x06, 0x20, 0x19, 0x07, 0x20, 0x20, 0x07, 0x20, 0x2d, 0x07, 0x4c,
-        0x38, 0x06, 0xa5, 0xff, 0xc9, 0x77, 0xf0, 0x0d, 0xc9, 0x64, 0xf0, 0x14, 0xc9, 0x73, 0xf0,
-        0x1b, 0xc9, 0x61, 0xf0, 0x22, 0x60, 0xa9, 0x04, 0x24, 0x02, 0xd0, 0x26, 0xa9, 0x01, 0x85,
-        0x02, 0x60, 0xa9, 0x08, 0x24, 0x02, 0xd0, 0x1b, 0xa9, 0x02, 0x85, 0x02, 0x60, 0xa9, 0x01,
-        0x24, 0x02, 0xd0, 0x10, 0xa9, 0x04, 0x85, 0x02, 0x60, 0xa9, 0x02, 0x24, 0x02, 0xd0, 0x05,
-        0xa9, 0x08, 0x85, 0x02, 0x60, 0x60, 0x20, 0x94, 0x06, 0x20, 0xa8, 0x06, 0x60, 0xa5, 0x00,
-        0xc5, 0x10, 0xd0, 0x0d, 0xa5, 0x01, 0xc5, 0x11, 0xd0, 0x07, 0xe6, 0x03, 0xe6, 0x03, 0x20,
-        0x2a, 0x06, 0x60, 0xa2, 0x02, 0xb5, 0x10, 0xc5, 0x10, 0xd0, 0x06, 0xb5, 0x11, 0xc5, 0x11,
-        0xf0, 0x09, 0xe8, 0xe8, 0xe4, 0x03, 0xf0, 0x06, 0x4c, 0xaa, 0x06, 0x4c, 0x35, 0x07, 0x60,
-        0xa6, 0x03, 0xca, 0x8a, 0xb5, 0x10, 0x95, 0x12, 0xca, 0x10, 0xf9, 0xa5, 0x02, 0x4a, 0xb0,
-        0x09, 0x4a, 0xb0, 0x19, 0x4a, 0xb0, 0x1f, 0x4a, 0xb0, 0x2f, 0xa5, 0x10, 0x38, 0xe9, 0x20,
-        0x85, 0x10, 0x90, 0x01, 0x60, 0xc6, 0x11, 0xa9, 0x01, 0xc5, 0x11, 0xf0, 0x28, 0x60, 0xe6,
-        0x10, 0xa9, 0x1f, 0x24, 0x10, 0xf0, 0x1f, 0x60, 0xa5, 0x10, 0x18, 0x69, 0x20, 0x85, 0x10,
-        0xb0, 0x01, 0x60, 0xe6, 0x11, 0xa9, 0x06, 0xc5, 0x11, 0xf0, 0x0c, 0x60, 0xc6, 0x10, 0xa5,
-        0x10, 0x29, 0x1f, 0xc9, 0x1f, 0xf0, 0x01, 0x60, 0x4c, 0x35, 0x07, 0xa0, 0x00, 0xa5, 0xfe,
-        0x91, 0x00, 0x60, 0xa6, 0x03, 0xa9, 0x00, 0x81, 0x10, 0xa2, 0x00, 0xa9, 0x01, 0x81, 0x10,
-        0x60, 0xa6, 0xff, 0xea, 0xea, 0xca, 0xd0, 0xfb, 0x60,
-    ];
-
-    let mut cpu = CPU::new();
-    cpu.memory_load_program(game_code);
-    cpu.reset();
-    let mut screen_state=[0u8;32*3*32];
-    let mut rng=rand::thread_rng();
-    cpu.run_with_callback(move |cpu|{
-       handle_user_input(cpu,&mut event_pump);
-        cpu.memory_write(0xfe,rng.gen_range(1,16));
-        if read_screen_state(cpu,&mut screen_state) {
-            texture.update(None,&mut screen_state,32*3).unwrap();
-            canvas.copy(&texture,None,None).unwrap();
+
+    let mut snake = Snake::new(movie.movie.seed);
+    let mut screen_state = [0u8; 32 * 3 * 32];
+    let mut input = Input::new(KeyBindings::default(), sdl.game_controller().ok());
+    let mut joypads = [Joypad::new(), Joypad::new()];
+    // 贪吃蛇没有卡带文件, 存档放在当前目录
+    let state_path = Path::new("snake.state");
+    let mut rewind = rewind_from_args(&args);
+    let mut pacer = Pacer::new(Region::Ntsc.cpu_clock_hz() / SNAKE_SLOWDOWN as f64, snake.cpu.cycles);
+    loop {
+        if input.handle_events(&mut event_pump, &mut joypads) == RunControl::Stop {
+            break;
+        }
+        movie.start_frame(snake.frames, &mut joypads);
+        let buttons = joypads[0].button_status;
+        let control = snake.run_frame(buttons, |cpu| {
+            wait_for_debugger(&mut debugger, cpu, |_| input.handle_events(&mut event_pump, &mut joypads))
+        });
+        let control = control.unwrap_or_else(|e| {
+            eprintln!("cpu error: {}", e);
+            RunControl::Stop
+        });
+        if read_screen_state(&mut snake.cpu, &mut screen_state) {
+            texture.update(None, &screen_state, 32 * 3).unwrap();
+            canvas.copy(&texture, None, None).unwrap();
             canvas.present();
         }
-        thread::sleep(Duration::new(0,70_000));
-    });
+        if control == RunControl::Stop {
+            break;
+        }
+        let loaded = handle_hotkeys(&mut input, &movie, &mut snake.cpu, state_path);
+        let rewinding = !movie.is_active() && input.hotkey_held(Hotkey::Rewind);
+        // 读档和倒带都会让周期数变小, 贪吃蛇要从新的周期数开始计算下一帧
+        if rewind_frame(&mut rewind, rewinding, &mut snake.cpu) || loaded {
+            snake.state_restored();
+        }
+        pacer.pace(snake.cpu.cycles);
+    }
+    movie.finish();
 }
-
-
-
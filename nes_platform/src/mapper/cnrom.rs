@@ -0,0 +1,88 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::mapper::{CartridgeMemory, Mapper};
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
+
+const CHR_BANK_SIZE: usize = 0x2000;
+
+/**
+Mapper 3: CNROM
+PRG-ROM 和 NROM 一样固定不变, 向 0x8000-0xFFFF 写入的值选择 8KB 的 CHR bank
+https://www.nesdev.org/wiki/INES_Mapper_003
+ */
+pub struct CnRom {
+    memory: CartridgeMemory,
+    mirroring: Mirroring,
+    chr_bank: usize,
+}
+
+impl CnRom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let mirroring = cartridge.mirroring;
+        CnRom { memory: CartridgeMemory::new(cartridge), mirroring, chr_bank: 0 }
+    }
+}
+
+impl Mapper for CnRom {
+    fn cpu_read(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => self.memory.read_prg_ram(addr),
+            0x8000..=0xFFFF => self.memory.read_prg(0, 0x8000, (addr - 0x8000) as usize),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF => self.memory.write_prg_ram(addr, data),
+            0x8000..=0xFFFF => self.chr_bank = data as usize,
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.memory.read_chr(self.chr_bank, CHR_BANK_SIZE, addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        self.memory.write_chr(self.chr_bank, CHR_BANK_SIZE, addr as usize, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+impl SaveState for CnRom {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_tag(b"CNRM");
+        self.memory.save_state(state);
+        state.write_u32(self.chr_bank as u32);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.read_tag(b"CNRM")?;
+        self.memory.load_state(state)?;
+        self.chr_bank = state.read_u32()? as usize;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mapper::test::numbered_cartridge;
+
+    #[test]
+    fn test_cnrom_chr_bank_switch() {
+        // 4 个 8KB CHR bank
+        let mut mapper = CnRom::new(numbered_cartridge(3, 4, 32));
+        assert_eq!(mapper.ppu_read(0x0000), 0);
+        mapper.cpu_write(0x8000, 2);
+        assert_eq!(mapper.ppu_read(0x0000), 16);
+        assert_eq!(mapper.ppu_read(0x1c00), 23);
+        // 超出范围的 bank 编号取模
+        mapper.cpu_write(0xffff, 5);
+        assert_eq!(mapper.ppu_read(0x0000), 8);
+        assert_eq!(mapper.cpu_read(0xe000), 3);
+    }
+}
@@ -0,0 +1,240 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::mapper::{CartridgeMemory, Mapper};
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
+
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x1000;
+
+/**
+Mapper 1: MMC1
+CPU 通过一个 5 位的串行移位寄存器写入 mapper 的寄存器: 每次写 0x8000-0xFFFF 把数据的位0移入,
+第 5 次写入时根据地址的位13-14 把移位寄存器的值写入 4 个内部寄存器之一. 写入的数据位7为1时复位移位寄存器.
+https://www.nesdev.org/wiki/MMC1
+
+ 0x8000-0x9FFF 控制寄存器
+   4bit0
+   CPPMM
+   |||++- 镜像: 0 单屏(低), 1 单屏(高), 2 垂直, 3 水平
+   |++--- PRG bank 模式: 0/1 切换整个 32KB; 2 固定 0x8000 为第一个 bank; 3 固定 0xC000 为最后一个 bank
+   +----- CHR bank 模式: 0 切换整个 8KB; 1 两个独立的 4KB bank
+ 0xA000-0xBFFF CHR bank 0
+ 0xC000-0xDFFF CHR bank 1
+ 0xE000-0xFFFF PRG bank (位4为1时禁用 PRG-RAM)
+ */
+pub struct Mmc1 {
+    memory: CartridgeMemory,
+    shift_register: u8,
+    shift_count: u8,
+    control: u8,
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    prg_bank: u8,
+}
+
+impl Mmc1 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Mmc1 {
+            memory: CartridgeMemory::new(cartridge),
+            shift_register: 0,
+            shift_count: 0,
+            // 上电时固定最后一个 bank 到 0xC000, 保证复位向量可用
+            control: 0x0C,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x8000..=0x9FFF => self.control = data,
+            0xA000..=0xBFFF => self.chr_bank_0 = data,
+            0xC000..=0xDFFF => self.chr_bank_1 = data,
+            _ => self.prg_bank = data,
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & 0b1_0000 == 0
+    }
+
+    // 把 PPU 地址映射为 (4KB bank 编号, bank 内偏移)
+    fn chr_bank(&self, addr: u16) -> (usize, usize) {
+        let offset = (addr & 0x0FFF) as usize;
+        if self.control & 0b1_0000 == 0 {
+            // 8KB 模式忽略最低位
+            let bank = (self.chr_bank_0 & 0b1_1110) as usize;
+            (bank + (addr >> 12) as usize, offset)
+        } else if addr < 0x1000 {
+            (self.chr_bank_0 as usize, offset)
+        } else {
+            (self.chr_bank_1 as usize, offset)
+        }
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn cpu_read(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.memory.read_prg_ram(addr),
+            0x8000..=0xFFFF => {
+                let bank = (self.prg_bank & 0x0F) as usize;
+                let last = self.memory.prg_bank_count(PRG_BANK_SIZE) - 1;
+                let offset = (addr & 0x3FFF) as usize;
+                let upper = addr >= 0xC000;
+                let bank = match (self.control >> 2) & 0b11 {
+                    0 | 1 => (bank & !1) + upper as usize,
+                    2 => if upper { bank } else { 0 },
+                    _ => if upper { last } else { bank },
+                };
+                self.memory.read_prg(bank, PRG_BANK_SIZE, offset)
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.memory.write_prg_ram(addr, data),
+            0x8000..=0xFFFF => {
+                if data & 0b1000_0000 != 0 {
+                    self.shift_register = 0;
+                    self.shift_count = 0;
+                    self.control |= 0x0C;
+                    return;
+                }
+                self.shift_register |= (data & 1) << self.shift_count;
+                self.shift_count += 1;
+                if self.shift_count == 5 {
+                    self.write_register(addr, self.shift_register);
+                    self.shift_register = 0;
+                    self.shift_count = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        let (bank, offset) = self.chr_bank(addr);
+        self.memory.read_chr(bank, CHR_BANK_SIZE, offset)
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        let (bank, offset) = self.chr_bank(addr);
+        self.memory.write_chr(bank, CHR_BANK_SIZE, offset, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0b11 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+}
+
+impl SaveState for Mmc1 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_tag(b"MMC1");
+        self.memory.save_state(state);
+        state.write_u8(self.shift_register);
+        state.write_u8(self.shift_count);
+        state.write_u8(self.control);
+        state.write_u8(self.chr_bank_0);
+        state.write_u8(self.chr_bank_1);
+        state.write_u8(self.prg_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.read_tag(b"MMC1")?;
+        self.memory.load_state(state)?;
+        self.shift_register = state.read_u8()?;
+        self.shift_count = state.read_u8()?;
+        self.control = state.read_u8()?;
+        self.chr_bank_0 = state.read_u8()?;
+        self.chr_bank_1 = state.read_u8()?;
+        self.prg_bank = state.read_u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mapper::test::numbered_cartridge;
+
+    // 按 MMC1 的串行协议写入一个 5 位的值
+    fn serial_write(mapper: &mut Mmc1, addr: u16, value: u8) {
+        for i in 0..5 {
+            mapper.cpu_write(addr, (value >> i) & 1);
+        }
+    }
+
+    #[test]
+    fn test_mmc1_power_on_fixes_last_bank() {
+        // 8 个 16KB bank
+        let mapper = Mmc1::new(numbered_cartridge(1, 16, 32));
+        assert_eq!(mapper.cpu_read(0x8000), 0);
+        assert_eq!(mapper.cpu_read(0xc000), 14);
+    }
+
+    #[test]
+    fn test_mmc1_prg_bank_modes() {
+        let mut mapper = Mmc1::new(numbered_cartridge(1, 16, 32));
+        serial_write(&mut mapper, 0xe000, 3);
+        assert_eq!(mapper.cpu_read(0x8000), 6);
+        assert_eq!(mapper.cpu_read(0xc000), 14);
+
+        // 模式 2: 0x8000 固定为第一个 bank, 0xC000 可切换
+        serial_write(&mut mapper, 0x8000, 0b0_10_00);
+        assert_eq!(mapper.cpu_read(0x8000), 0);
+        assert_eq!(mapper.cpu_read(0xc000), 6);
+
+        // 模式 0: 32KB 切换, 忽略最低位
+        serial_write(&mut mapper, 0x8000, 0b0_00_00);
+        assert_eq!(mapper.cpu_read(0x8000), 4);
+        assert_eq!(mapper.cpu_read(0xc000), 6);
+    }
+
+    #[test]
+    fn test_mmc1_reset_bit_restores_mode_3() {
+        let mut mapper = Mmc1::new(numbered_cartridge(1, 16, 32));
+        serial_write(&mut mapper, 0x8000, 0b0_00_00);
+        // 写到一半被复位, 之前移入的位被丢弃
+        mapper.cpu_write(0x8000, 1);
+        mapper.cpu_write(0x8000, 0x80);
+        serial_write(&mut mapper, 0xe000, 2);
+        assert_eq!(mapper.cpu_read(0x8000), 4);
+        assert_eq!(mapper.cpu_read(0xc000), 14);
+    }
+
+    #[test]
+    fn test_mmc1_chr_banks_and_mirroring() {
+        let mut mapper = Mmc1::new(numbered_cartridge(1, 16, 32));
+        // 4KB CHR 模式, 垂直镜像
+        serial_write(&mut mapper, 0x8000, 0b1_11_10);
+        assert_eq!(mapper.mirroring(), Mirroring::Vertical);
+        serial_write(&mut mapper, 0xa000, 3);
+        serial_write(&mut mapper, 0xc000, 5);
+        // 4KB bank 3 起始于 1KB bank 12
+        assert_eq!(mapper.ppu_read(0x0000), 12);
+        assert_eq!(mapper.ppu_read(0x1000), 20);
+
+        // 8KB CHR 模式, 单屏镜像
+        serial_write(&mut mapper, 0x8000, 0b0_11_01);
+        assert_eq!(mapper.mirroring(), Mirroring::SingleScreenUpper);
+        assert_eq!(mapper.ppu_read(0x0000), 8);
+        assert_eq!(mapper.ppu_read(0x1000), 12);
+    }
+
+    #[test]
+    fn test_mmc1_prg_ram_disable() {
+        let mut mapper = Mmc1::new(numbered_cartridge(1, 16, 32));
+        mapper.cpu_write(0x6000, 0x42);
+        assert_eq!(mapper.cpu_read(0x6000), 0x42);
+        serial_write(&mut mapper, 0xe000, 0b1_0000);
+        assert_eq!(mapper.cpu_read(0x6000), 0);
+    }
+}
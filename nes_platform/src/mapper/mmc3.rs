@@ -0,0 +1,311 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::mapper::{CartridgeMemory, Mapper};
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x0400;
+
+/**
+Mapper 4: MMC3
+https://www.nesdev.org/wiki/MMC3
+
+ 0x8000 偶地址 bank 选择: 位0-2 选择下一次写入的 bank 寄存器 R0-R7, 位6 PRG 模式, 位7 CHR A12 反转
+ 0x8001 奇地址 bank 数据
+ 0xA000 偶地址 镜像: 0 垂直, 1 水平
+ 0xA001 奇地址 PRG-RAM 保护
+ 0xC000 偶地址 IRQ 计数器的重载值
+ 0xC001 奇地址 在下一条扫描线重载 IRQ 计数器
+ 0xE000 偶地址 禁用 IRQ 并清除挂起的 IRQ
+ 0xE001 奇地址 启用 IRQ
+
+PRG bank (8KB):
+          0x8000  0xA000  0xC000  0xE000
+ 模式 0:   R6      R7      -2      -1
+ 模式 1:   -2      R7      R6      -1
+CHR bank (1KB), R0/R1 是 2KB bank:
+          0x0000  0x0400  0x0800  0x0C00  0x1000  0x1400  0x1800  0x1C00
+ 反转 0:   R0      R0+1    R1      R1+1    R2      R3      R4      R5
+ 反转 1:   R2      R3      R4      R5      R0      R0+1    R1      R1+1
+ */
+pub struct Mmc3 {
+    memory: CartridgeMemory,
+    bank_select: u8,
+    registers: [u8; 8],
+    mirroring: Mirroring,
+    prg_ram_enabled: bool,
+    prg_ram_write_protect: bool,
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+}
+
+impl Mmc3 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let mirroring = cartridge.mirroring;
+        Mmc3 {
+            memory: CartridgeMemory::new(cartridge),
+            bank_select: 0,
+            registers: [0, 2, 4, 5, 6, 7, 0, 1],
+            mirroring,
+            prg_ram_enabled: true,
+            prg_ram_write_protect: false,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+        }
+    }
+
+    fn prg_bank(&self, addr: u16) -> usize {
+        // NES 2.0 可以声明只有一个 8KB bank 的 PRG-ROM, 这时倒数第二个 bank 不存在, read_prg 按取模映射到仅有的 bank
+        let second_last = self.memory.prg_bank_count(PRG_BANK_SIZE).saturating_sub(2);
+        let swap = self.bank_select & 0b0100_0000 != 0;
+        match (addr - 0x8000) / 0x2000 {
+            0 => if swap { second_last } else { self.registers[6] as usize },
+            1 => self.registers[7] as usize,
+            2 => if swap { self.registers[6] as usize } else { second_last },
+            _ => second_last + 1,
+        }
+    }
+
+    fn chr_bank(&self, addr: u16) -> usize {
+        // A12 反转时两个 4KB 区域互换
+        let addr = if self.bank_select & 0b1000_0000 != 0 { addr ^ 0x1000 } else { addr };
+        let slot = (addr / 0x0400) as usize;
+        match slot {
+            0 | 1 => (self.registers[0] & 0xFE) as usize + slot,
+            2 | 3 => (self.registers[1] & 0xFE) as usize + slot - 2,
+            _ => self.registers[slot - 2] as usize,
+        }
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn cpu_read(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled => self.memory.read_prg_ram(addr),
+            0x8000..=0xFFFF => self.memory.read_prg(self.prg_bank(addr), PRG_BANK_SIZE, (addr & 0x1FFF) as usize),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        let even = addr & 1 == 0;
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled && !self.prg_ram_write_protect => self.memory.write_prg_ram(addr, data),
+            0x8000..=0x9FFF => {
+                if even {
+                    self.bank_select = data;
+                } else {
+                    self.registers[(self.bank_select & 0b111) as usize] = data;
+                }
+            }
+            0xA000..=0xBFFF => {
+                if even {
+                    // 四屏镜像的卡带忽略这个寄存器
+                    if self.mirroring != Mirroring::FourScreen {
+                        self.mirroring = if data & 1 == 0 { Mirroring::Vertical } else { Mirroring::Horizontal };
+                    }
+                } else {
+                    self.prg_ram_enabled = data & 0b1000_0000 != 0;
+                    self.prg_ram_write_protect = data & 0b0100_0000 != 0;
+                }
+            }
+            0xC000..=0xDFFF => {
+                if even {
+                    self.irq_latch = data;
+                } else {
+                    self.irq_counter = 0;
+                    self.irq_reload = true;
+                }
+            }
+            0xE000..=0xFFFF => {
+                if even {
+                    self.irq_enabled = false;
+                    self.irq_pending = false;
+                } else {
+                    self.irq_enabled = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.memory.read_chr(self.chr_bank(addr), CHR_BANK_SIZE, (addr & 0x03FF) as usize)
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        let bank = self.chr_bank(addr);
+        self.memory.write_chr(bank, CHR_BANK_SIZE, (addr & 0x03FF) as usize, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    /*
+    真实硬件上计数器由 PPU 地址线 A12 的上升沿驱动, 正常渲染时每条扫描线恰好一次
+    计数器为 0 或者请求了重载时装入重载值, 否则减一; 减到 0 并且 IRQ 启用时向 CPU 请求中断
+     */
+    fn scanline(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+}
+
+impl SaveState for Mmc3 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_tag(b"MMC3");
+        self.memory.save_state(state);
+        state.write_u8(self.bank_select);
+        state.write_bytes(&self.registers);
+        self.mirroring.save_state(state);
+        state.write_bool(self.prg_ram_enabled);
+        state.write_bool(self.prg_ram_write_protect);
+        state.write_u8(self.irq_latch);
+        state.write_u8(self.irq_counter);
+        state.write_bool(self.irq_reload);
+        state.write_bool(self.irq_enabled);
+        state.write_bool(self.irq_pending);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.read_tag(b"MMC3")?;
+        self.memory.load_state(state)?;
+        self.bank_select = state.read_u8()?;
+        state.read_bytes(&mut self.registers)?;
+        self.mirroring.load_state(state)?;
+        self.prg_ram_enabled = state.read_bool()?;
+        self.prg_ram_write_protect = state.read_bool()?;
+        self.irq_latch = state.read_u8()?;
+        self.irq_counter = state.read_u8()?;
+        self.irq_reload = state.read_bool()?;
+        self.irq_enabled = state.read_bool()?;
+        self.irq_pending = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mapper::test::numbered_cartridge;
+
+    #[test]
+    fn test_mmc3_prg_banks() {
+        // 16 个 8KB bank
+        let mut mapper = Mmc3::new(numbered_cartridge(4, 16, 256));
+        mapper.cpu_write(0x8000, 6);
+        mapper.cpu_write(0x8001, 3);
+        mapper.cpu_write(0x8000, 7);
+        mapper.cpu_write(0x8001, 5);
+        assert_eq!(mapper.cpu_read(0x8000), 3);
+        assert_eq!(mapper.cpu_read(0xa000), 5);
+        assert_eq!(mapper.cpu_read(0xc000), 14);
+        assert_eq!(mapper.cpu_read(0xe000), 15);
+
+        // PRG 模式 1 交换 0x8000 和 0xC000
+        mapper.cpu_write(0x8000, 0b0100_0000);
+        assert_eq!(mapper.cpu_read(0x8000), 14);
+        assert_eq!(mapper.cpu_read(0xc000), 3);
+        assert_eq!(mapper.cpu_read(0xe000), 15);
+    }
+
+    #[test]
+    fn test_mmc3_single_prg_bank() {
+        let mut mapper = Mmc3::new(numbered_cartridge(4, 1, 8));
+        for addr in [0x8000, 0xa000, 0xc000, 0xe000] {
+            assert_eq!(mapper.cpu_read(addr), 0);
+        }
+        mapper.cpu_write(0x8000, 0b0100_0110);
+        mapper.cpu_write(0x8001, 5);
+        for addr in [0x8000, 0xa000, 0xc000, 0xe000] {
+            assert_eq!(mapper.cpu_read(addr), 0);
+        }
+    }
+
+    #[test]
+    fn test_mmc3_chr_banks() {
+        let mut mapper = Mmc3::new(numbered_cartridge(4, 16, 256));
+        for (register, bank) in [(0, 9), (1, 20), (2, 30), (3, 31), (4, 32), (5, 33)] {
+            mapper.cpu_write(0x8000, register);
+            mapper.cpu_write(0x8001, bank);
+        }
+        // 2KB bank 忽略最低位
+        assert_eq!(mapper.ppu_read(0x0000), 8);
+        assert_eq!(mapper.ppu_read(0x0400), 9);
+        assert_eq!(mapper.ppu_read(0x0800), 20);
+        assert_eq!(mapper.ppu_read(0x0c00), 21);
+        assert_eq!(mapper.ppu_read(0x1000), 30);
+        assert_eq!(mapper.ppu_read(0x1c00), 33);
+
+        // A12 反转
+        mapper.cpu_write(0x8000, 0b1000_0000);
+        assert_eq!(mapper.ppu_read(0x0000), 30);
+        assert_eq!(mapper.ppu_read(0x1000), 8);
+        assert_eq!(mapper.ppu_read(0x1c00), 21);
+    }
+
+    #[test]
+    fn test_mmc3_mirroring_and_prg_ram_protect() {
+        let mut mapper = Mmc3::new(numbered_cartridge(4, 16, 256));
+        mapper.cpu_write(0xa000, 1);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+        mapper.cpu_write(0xa000, 0);
+        assert_eq!(mapper.mirroring(), Mirroring::Vertical);
+
+        mapper.cpu_write(0xa001, 0b1000_0000);
+        mapper.cpu_write(0x6000, 0x12);
+        mapper.cpu_write(0xa001, 0b1100_0000);
+        mapper.cpu_write(0x6000, 0x34);
+        assert_eq!(mapper.cpu_read(0x6000), 0x12);
+    }
+
+    #[test]
+    fn test_mmc3_scanline_irq() {
+        let mut mapper = Mmc3::new(numbered_cartridge(4, 16, 256));
+        mapper.cpu_write(0xc000, 3);
+        mapper.cpu_write(0xc001, 0);
+        mapper.cpu_write(0xe001, 0);
+
+        // 第一条扫描线装入 3, 之后 2, 1, 0
+        for _ in 0..3 {
+            mapper.scanline();
+            assert!(!mapper.irq_pending());
+        }
+        mapper.scanline();
+        assert!(mapper.irq_pending());
+
+        // 写 0xE000 应答并禁用 IRQ
+        mapper.cpu_write(0xe000, 0);
+        assert!(!mapper.irq_pending());
+        for _ in 0..8 {
+            mapper.scanline();
+        }
+        assert!(!mapper.irq_pending());
+
+        // 重新启用后计数器自动重载
+        mapper.cpu_write(0xe001, 0);
+        for _ in 0..3 {
+            mapper.scanline();
+        }
+        assert!(!mapper.irq_pending());
+        mapper.scanline();
+        assert!(mapper.irq_pending());
+    }
+}
@@ -0,0 +1,175 @@
+use crate::cartridge::{Cartridge, CartridgeError, Mirroring};
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
+use crate::checksum::crc32;
+
+pub mod nrom;
+pub mod mmc1;
+pub mod uxrom;
+pub mod cnrom;
+pub mod mmc3;
+
+/**
+Mapper 是卡带上的地址映射电路, 负责把 CPU 和 PPU 的地址映射到卡带上的 ROM/RAM 上
+大部分游戏的程序和图形数据都超过了 CPU/PPU 能直接寻址的范围, 需要 mapper 在运行时切换 bank
+https://www.nesdev.org/wiki/Mapper
+
+CPU 侧: 0x6000-0x7FFF 为 PRG-RAM, 0x8000-0xFFFF 为 PRG-ROM, 写 PRG-ROM 的地址通常是在写 mapper 的寄存器
+PPU 侧: 0x0000-0x1FFF 为 CHR-ROM/CHR-RAM (图案表)
+存档时 mapper 保存 bank 寄存器和 PRG-RAM/CHR-RAM
+ */
+pub trait Mapper: SaveState {
+    fn cpu_read(&self, addr: u16) -> u8;
+    fn cpu_write(&mut self, addr: u16, data: u8);
+    fn ppu_read(&self, addr: u16) -> u8;
+    fn ppu_write(&mut self, addr: u16, data: u8);
+    // 当前的命名表镜像方式, 有的 mapper 可以在运行时修改
+    fn mirroring(&self) -> Mirroring;
+
+    // PPU 每渲染完一条扫描线通知一次, MMC3 用它来驱动扫描线计数器
+    fn scanline(&mut self) {}
+
+    // mapper 是否在向 CPU 请求 IRQ
+    fn irq_pending(&self) -> bool {
+        false
+    }
+}
+
+/*
+根据卡带的 mapper 编号创建对应的 mapper
+ */
+pub fn create(cartridge: Cartridge) -> Result<Box<dyn Mapper>, CartridgeError> {
+    match cartridge.mapper {
+        0 => Ok(Box::new(nrom::Nrom::new(cartridge))),
+        1 => Ok(Box::new(mmc1::Mmc1::new(cartridge))),
+        2 => Ok(Box::new(uxrom::UxRom::new(cartridge))),
+        3 => Ok(Box::new(cnrom::CnRom::new(cartridge))),
+        4 => Ok(Box::new(mmc3::Mmc3::new(cartridge))),
+        mapper => Err(CartridgeError::UnsupportedMapper(mapper)),
+    }
+}
+
+/**
+各个 mapper 共用的存储器: PRG-ROM, PRG-RAM 和 CHR-ROM/CHR-RAM
+bank 的编号超过实际数量时按取模处理, 和只连接了低位地址线的硬件行为一致
+ */
+pub struct CartridgeMemory {
+    pub prg_rom: Vec<u8>,
+    pub prg_ram: Vec<u8>,
+    pub chr: Vec<u8>,
+    // CHR 为 RAM 时可写
+    pub chr_is_ram: bool,
+}
+
+impl CartridgeMemory {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let chr_is_ram = cartridge.chr_rom.is_empty();
+        let chr = if chr_is_ram {
+            vec![0; cartridge.chr_ram_size.max(0x2000)]
+        } else {
+            cartridge.chr_rom
+        };
+        let mut prg_ram = vec![0; cartridge.prg_ram_size.clamp(0x2000, 0x8000)];
+        // trainer 会被加载到 0x7000
+        if let Some(trainer) = &cartridge.trainer {
+            prg_ram[0x1000..0x1000 + trainer.len()].copy_from_slice(trainer);
+        }
+        CartridgeMemory { prg_rom: cartridge.prg_rom, prg_ram, chr, chr_is_ram }
+    }
+
+    pub fn prg_bank_count(&self, bank_size: usize) -> usize {
+        (self.prg_rom.len() / bank_size).max(1)
+    }
+
+    pub fn chr_bank_count(&self, bank_size: usize) -> usize {
+        (self.chr.len() / bank_size).max(1)
+    }
+
+    // 读取 PRG-ROM 中第 bank 个大小为 bank_size 的 bank 的第 offset 个字节
+    pub fn read_prg(&self, bank: usize, bank_size: usize, offset: usize) -> u8 {
+        if self.prg_rom.is_empty() {
+            return 0;
+        }
+        let bank = bank % self.prg_bank_count(bank_size);
+        self.prg_rom[(bank * bank_size + offset) % self.prg_rom.len()]
+    }
+
+    pub fn read_chr(&self, bank: usize, bank_size: usize, offset: usize) -> u8 {
+        let bank = bank % self.chr_bank_count(bank_size);
+        self.chr[(bank * bank_size + offset) % self.chr.len()]
+    }
+
+    pub fn write_chr(&mut self, bank: usize, bank_size: usize, offset: usize, data: u8) {
+        if self.chr_is_ram {
+            let bank = bank % self.chr_bank_count(bank_size);
+            let len = self.chr.len();
+            self.chr[(bank * bank_size + offset) % len] = data;
+        }
+    }
+
+    pub fn read_prg_ram(&self, addr: u16) -> u8 {
+        self.prg_ram[(addr - 0x6000) as usize % self.prg_ram.len()]
+    }
+
+    pub fn write_prg_ram(&mut self, addr: u16, data: u8) {
+        let len = self.prg_ram.len();
+        self.prg_ram[(addr - 0x6000) as usize % len] = data;
+    }
+}
+
+/*
+PRG-ROM 和 CHR-ROM 不保存, 只保存它们的 CRC-32, 用来拒绝其他卡带的存档
+ */
+impl SaveState for CartridgeMemory {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u32(crc32(&self.prg_rom));
+        state.write_block(&self.prg_ram);
+        if self.chr_is_ram {
+            state.write_block(&self.chr);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        if state.read_u32()? != crc32(&self.prg_rom) {
+            return Err(StateError::Mismatch("state was saved with a different cartridge".to_string()));
+        }
+        state.read_block(&mut self.prg_ram)?;
+        if self.chr_is_ram {
+            state.read_block(&mut self.chr)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::cartridge::RomFormat;
+
+    /*
+    构造一个测试用的卡带, 每个 PRG bank (8KB) 和 CHR bank (1KB) 都填充为自己的编号, 读出来的值就是当前映射的 bank
+     */
+    pub fn numbered_cartridge(mapper: u16, prg_8k_banks: usize, chr_1k_banks: usize) -> Cartridge {
+        let prg_rom = (0..prg_8k_banks).flat_map(|bank| vec![bank as u8; 0x2000]).collect();
+        let chr_rom = (0..chr_1k_banks).flat_map(|bank| vec![bank as u8; 0x0400]).collect();
+        Cartridge {
+            format: RomFormat::INes,
+            prg_rom,
+            chr_rom,
+            trainer: None,
+            mapper,
+            submapper: 0,
+            mirroring: Mirroring::Horizontal,
+            battery: false,
+            prg_ram_size: 0x2000,
+            chr_ram_size: if chr_1k_banks == 0 { 0x2000 } else { 0 },
+        }
+    }
+
+    #[test]
+    fn test_create_known_mappers() {
+        for mapper in 0..=4 {
+            assert!(create(numbered_cartridge(mapper, 4, 8)).is_ok());
+        }
+        assert!(matches!(create(numbered_cartridge(5, 4, 8)), Err(CartridgeError::UnsupportedMapper(5))));
+    }
+}
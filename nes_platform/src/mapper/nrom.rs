@@ -0,0 +1,83 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::mapper::{CartridgeMemory, Mapper};
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
+
+/**
+Mapper 0: NROM, 没有 bank 切换
+16KB 的 PRG-ROM 会在 0xC000 再镜像一次, 32KB 的 PRG-ROM 直接映射到 0x8000-0xFFFF
+https://www.nesdev.org/wiki/NROM
+ */
+pub struct Nrom {
+    memory: CartridgeMemory,
+    mirroring: Mirroring,
+}
+
+impl Nrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let mirroring = cartridge.mirroring;
+        Nrom { memory: CartridgeMemory::new(cartridge), mirroring }
+    }
+}
+
+impl Mapper for Nrom {
+    fn cpu_read(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => self.memory.read_prg_ram(addr),
+            0x8000..=0xFFFF => self.memory.read_prg(0, 0x8000, (addr - 0x8000) as usize),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        if let 0x6000..=0x7FFF = addr {
+            self.memory.write_prg_ram(addr, data);
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.memory.read_chr(0, 0x2000, addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        self.memory.write_chr(0, 0x2000, addr as usize, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+impl SaveState for Nrom {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_tag(b"NROM");
+        self.memory.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.read_tag(b"NROM")?;
+        self.memory.load_state(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mapper::test::numbered_cartridge;
+
+    #[test]
+    fn test_nrom_128_mirrors_prg() {
+        let mapper = Nrom::new(numbered_cartridge(0, 2, 8));
+        assert_eq!(mapper.cpu_read(0x8000), 0);
+        assert_eq!(mapper.cpu_read(0xa000), 1);
+        assert_eq!(mapper.cpu_read(0xc000), 0);
+        assert_eq!(mapper.cpu_read(0xe000), 1);
+    }
+
+    #[test]
+    fn test_nrom_256_and_chr_rom_is_read_only() {
+        let mut mapper = Nrom::new(numbered_cartridge(0, 4, 8));
+        assert_eq!(mapper.cpu_read(0xe000), 3);
+        mapper.ppu_write(0x0400, 0xff);
+        assert_eq!(mapper.ppu_read(0x0400), 1);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::mapper::{CartridgeMemory, Mapper};
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
+
+const PRG_BANK_SIZE: usize = 0x4000;
+
+/**
+Mapper 2: UxROM
+0x8000-0xBFFF 是可切换的 16KB PRG bank, 0xC000-0xFFFF 固定为最后一个 bank
+向 0x8000-0xFFFF 写入的值就是 bank 编号, CHR 通常是 8KB 的 RAM
+https://www.nesdev.org/wiki/UxROM
+ */
+pub struct UxRom {
+    memory: CartridgeMemory,
+    mirroring: Mirroring,
+    prg_bank: usize,
+}
+
+impl UxRom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let mirroring = cartridge.mirroring;
+        UxRom { memory: CartridgeMemory::new(cartridge), mirroring, prg_bank: 0 }
+    }
+}
+
+impl Mapper for UxRom {
+    fn cpu_read(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => self.memory.read_prg_ram(addr),
+            0x8000..=0xBFFF => self.memory.read_prg(self.prg_bank, PRG_BANK_SIZE, (addr - 0x8000) as usize),
+            0xC000..=0xFFFF => {
+                let last = self.memory.prg_bank_count(PRG_BANK_SIZE) - 1;
+                self.memory.read_prg(last, PRG_BANK_SIZE, (addr - 0xC000) as usize)
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF => self.memory.write_prg_ram(addr, data),
+            0x8000..=0xFFFF => self.prg_bank = data as usize,
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.memory.read_chr(0, 0x2000, addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        self.memory.write_chr(0, 0x2000, addr as usize, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+impl SaveState for UxRom {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_tag(b"UXRM");
+        self.memory.save_state(state);
+        state.write_u32(self.prg_bank as u32);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.read_tag(b"UXRM")?;
+        self.memory.load_state(state)?;
+        self.prg_bank = state.read_u32()? as usize;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mapper::test::numbered_cartridge;
+
+    #[test]
+    fn test_uxrom_bank_switch() {
+        // 8 个 16KB bank
+        let mut mapper = UxRom::new(numbered_cartridge(2, 16, 0));
+        assert_eq!(mapper.cpu_read(0x8000), 0);
+        assert_eq!(mapper.cpu_read(0xc000), 14);
+        assert_eq!(mapper.cpu_read(0xe000), 15);
+
+        mapper.cpu_write(0x8000, 3);
+        assert_eq!(mapper.cpu_read(0x8000), 6);
+        assert_eq!(mapper.cpu_read(0xa000), 7);
+        assert_eq!(mapper.cpu_read(0xc000), 14);
+    }
+
+    #[test]
+    fn test_uxrom_chr_ram() {
+        let mut mapper = UxRom::new(numbered_cartridge(2, 16, 0));
+        mapper.ppu_write(0x1234, 0x56);
+        assert_eq!(mapper.ppu_read(0x1234), 0x56);
+    }
+}
@@ -1,26 +1,92 @@
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
+
+use crate::bus::Bus;
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
+
+/**
+平坦的 64KB 内存, 没有任何地址映射, 整个地址空间都可以读写
+贪吃蛇这类为通用 6502 写的程序以及单元测试都使用它
+ */
 pub struct Memory {
-    // 65535个u8类型的元素, 相当于64KB的内存
-    pub bytes: [u8; 0xffff],
+    // 65536个u8类型的元素, 相当于64KB的内存
+    pub bytes: [u8; 0x10000],
+}
+
+/**
+程序从加载地址开始放不进 64KB 的地址空间
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramTooLarge {
+    pub addr: u16,
+    pub len: usize,
+}
+
+impl fmt::Display for ProgramTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "program of {} bytes loaded at {:04X} does not fit below 0x10000", self.len, self.addr)
+    }
+}
+
+impl Error for ProgramTooLarge {}
+
+/*
+检查从 addr 开始的 len 个字节是否都在地址空间内, 返回结束位置 (不包含)
+ */
+pub fn program_end(addr: u16, len: usize) -> Result<usize, ProgramTooLarge> {
+    let end = addr as usize + len;
+    if end > 0x10000 {
+        return Err(ProgramTooLarge { addr, len });
+    }
+    Ok(end)
 }
 
 impl Default for Memory {
     fn default() -> Self {
-        return Memory { bytes: [0; 0xffff] };
+        Memory { bytes: [0; 0x10000] }
     }
 }
 
 impl Memory {
     pub fn read(&self, addr: u16) -> u8 {
-        return self.bytes[addr as usize];
+        self.bytes[addr as usize]
     }
 
     pub fn write(&mut self, addr: u16, data: u8) {
         self.bytes[addr as usize] = data;
     }
 
-    // 加载程序到内存位置
-    pub fn load_program(&mut self, addr: u16, program: Vec<u8>) {
-        let offset = (addr + program.len() as u16) as usize;
+    // 加载程序到内存位置, 超出地址空间时不写入任何字节
+    pub fn load_program(&mut self, addr: u16, program: Vec<u8>) -> Result<(), ProgramTooLarge> {
+        let offset = program_end(addr, program.len())?;
         self.bytes[addr as usize..offset].copy_from_slice(&program[..]);
+        Ok(())
+    }
+}
+
+impl Bus for Memory {
+    fn read(&mut self, addr: u16) -> u8 {
+        Memory::read(self, addr)
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        Memory::write(self, addr, data);
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for Memory {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_tag(b"MEM ");
+        state.write_bytes(&self.bytes);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.read_tag(b"MEM ")?;
+        state.read_bytes(&mut self.bytes)
+    }
+}
@@ -0,0 +1,260 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::joypad::{Joypad, JoypadButton};
+
+const MAGIC: &str = "nes_platform movie";
+const VERSION: u32 = 1;
+
+// 每帧一行时按键的书写顺序, 从最高位的右到最低位的 A
+const BUTTON_CHARS: [(JoypadButton, char); 8] = [
+    (JoypadButton::RIGHT, 'R'),
+    (JoypadButton::LEFT, 'L'),
+    (JoypadButton::DOWN, 'D'),
+    (JoypadButton::UP, 'U'),
+    (JoypadButton::START, 'T'),
+    (JoypadButton::SELECT, 'S'),
+    (JoypadButton::BUTTON_B, 'B'),
+    (JoypadButton::BUTTON_A, 'A'),
+];
+
+#[derive(Debug)]
+pub enum MovieError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::Io(e) => write!(f, "{}", e),
+            MovieError::Parse { line, message } => write!(f, "movie line {}: {}", line, message),
+        }
+    }
+}
+
+impl Error for MovieError {}
+
+impl From<io::Error> for MovieError {
+    fn from(e: io::Error) -> Self {
+        MovieError::Io(e)
+    }
+}
+
+/**
+输入录像: 随机数种子加上每一帧两个手柄的按键状态, 用同样的种子和输入重新运行就能得到完全相同的结果
+文本格式, 方便对比和提交到仓库里做回归测试:
+ nes_platform movie 1
+ seed 42
+ rom 1A2B3C4D
+ # 每行一帧, 玩家 1|玩家 2, 按键顺序 RLDUTSBA, 没有按下的是 .
+ ........|........
+ ...U....|........
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub seed: u64,
+    // 录制时卡带文件的 CRC32, 回放时用来提醒换了卡带
+    pub rom_crc32: Option<u32>,
+    frames: Vec<[JoypadButton; 2]>,
+}
+
+impl Movie {
+    pub fn new(seed: u64, rom_crc32: Option<u32>) -> Self {
+        Movie { seed, rom_crc32, frames: Vec::new() }
+    }
+
+    // 录制的帧数
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    // 每帧开始之前调用一次, 记录这一帧使用的按键
+    pub fn record(&mut self, joypads: &[Joypad; 2]) {
+        self.frames.push([joypads[0].button_status, joypads[1].button_status]);
+    }
+
+    pub fn input(&self, frame: u64) -> Option<[JoypadButton; 2]> {
+        self.frames.get(frame as usize).copied()
+    }
+
+    /*
+    把第 frame 帧录制的按键设置到手柄上, 录像已经放完时返回 false, 手柄保持不变
+     */
+    pub fn apply(&self, frame: u64, joypads: &mut [Joypad; 2]) -> bool {
+        let Some(input) = self.input(frame) else {
+            return false;
+        };
+        for (joypad, buttons) in joypads.iter_mut().zip(input) {
+            joypad.button_status = buttons;
+        }
+        true
+    }
+
+    pub fn parse(text: &str) -> Result<Self, MovieError> {
+        let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line.trim()));
+        let error = |line: usize, message: String| MovieError::Parse { line, message };
+        match lines.next() {
+            Some((_, header)) if header.strip_prefix(MAGIC).map(str::trim) == Some(&VERSION.to_string()) => {}
+            Some((_, header)) if header.starts_with(MAGIC) => {
+                return Err(error(1, format!("unsupported movie version `{}`", header)));
+            }
+            _ => return Err(error(1, format!("expected `{} {}`", MAGIC, VERSION))),
+        }
+
+        let mut seed = None;
+        let mut movie = Movie::new(0, None);
+        for (line, text) in lines {
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+            if let Some((player_1, player_2)) = text.split_once('|') {
+                let player_1 = parse_buttons(player_1).ok_or_else(|| error(line, format!("bad buttons `{}`", player_1)))?;
+                let player_2 = parse_buttons(player_2).ok_or_else(|| error(line, format!("bad buttons `{}`", player_2)))?;
+                movie.frames.push([player_1, player_2]);
+                continue;
+            }
+            if !movie.frames.is_empty() {
+                return Err(error(line, format!("expected `<player 1>|<player 2>`, got `{}`", text)));
+            }
+            match text.split_once(' ') {
+                Some(("seed", value)) => {
+                    seed = Some(value.trim().parse().map_err(|e| error(line, format!("bad seed `{}`: {}", value, e)))?);
+                }
+                Some(("rom", value)) => {
+                    let crc = u32::from_str_radix(value.trim(), 16).map_err(|e| error(line, format!("bad rom crc `{}`: {}", value, e)))?;
+                    movie.rom_crc32 = Some(crc);
+                }
+                _ => return Err(error(line, format!("unknown header `{}`", text))),
+            }
+        }
+        movie.seed = seed.ok_or_else(|| error(1, "missing `seed` header".to_string()))?;
+        Ok(movie)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{} {}\nseed {}\n", MAGIC, VERSION, self.seed);
+        if let Some(crc) = self.rom_crc32 {
+            text += &format!("rom {:08X}\n", crc);
+        }
+        text += "# player 1|player 2, buttons RLDUTSBA\n";
+        for [player_1, player_2] in &self.frames {
+            text += &format!("{}|{}\n", format_buttons(*player_1), format_buttons(*player_2));
+        }
+        text
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, MovieError> {
+        Movie::parse(&fs::read_to_string(path)?)
+    }
+}
+
+fn format_buttons(buttons: JoypadButton) -> String {
+    BUTTON_CHARS
+        .iter()
+        .map(|(button, c)| if buttons.contains(*button) { *c } else { '.' })
+        .collect()
+}
+
+fn parse_buttons(text: &str) -> Option<JoypadButton> {
+    let text = text.trim();
+    if text.chars().count() != BUTTON_CHARS.len() {
+        return None;
+    }
+    let mut buttons = JoypadButton::empty();
+    for (c, (button, expected)) in text.chars().zip(BUTTON_CHARS) {
+        if c == expected {
+            buttons |= button;
+        } else if c != '.' {
+            return None;
+        }
+    }
+    Some(buttons)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::RunControl;
+    use crate::snake::Snake;
+
+    #[test]
+    fn test_text_round_trip() {
+        let mut movie = Movie::new(1234, Some(0xDEADBEEF));
+        let mut joypads = [Joypad::new(), Joypad::new()];
+        movie.record(&joypads);
+        joypads[0].set_button_pressed_status(JoypadButton::UP, true);
+        joypads[0].set_button_pressed_status(JoypadButton::BUTTON_A, true);
+        joypads[1].set_button_pressed_status(JoypadButton::START, true);
+        movie.record(&joypads);
+
+        let text = movie.to_text();
+        assert!(text.contains("...U...A|....T...\n"));
+        assert_eq!(Movie::parse(&text).unwrap(), movie);
+
+        let mut replayed = [Joypad::new(), Joypad::new()];
+        assert!(movie.apply(1, &mut replayed));
+        assert_eq!(replayed[0].button_status, JoypadButton::UP | JoypadButton::BUTTON_A);
+        assert_eq!(replayed[1].button_status, JoypadButton::START);
+        assert!(!movie.apply(2, &mut replayed));
+        assert_eq!(replayed[1].button_status, JoypadButton::START);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let line = |text: &str| match Movie::parse(text) {
+            Err(MovieError::Parse { line, .. }) => line,
+            other => panic!("expected parse error, got {:?}", other),
+        };
+        assert_eq!(line("nes_platform movie 2\nseed 1\n"), 1);
+        assert_eq!(line("hello\n"), 1);
+        assert_eq!(line("nes_platform movie 1\nrom 00000000\n"), 1);
+        assert_eq!(line("nes_platform movie 1\nseed x\n"), 2);
+        assert_eq!(line("nes_platform movie 1\nseed 1\n........|........\n....X...|........\n"), 4);
+        assert_eq!(line("nes_platform movie 1\nseed 1\n........|........\nseed 2\n"), 4);
+        assert!(Movie::parse("nes_platform movie 1\n# comment\nseed 7\n").unwrap().is_empty());
+    }
+
+    /*
+    录制一局贪吃蛇, 存成文本再读回来回放, 结果必须和录制时完全一样
+     */
+    #[test]
+    fn test_replay_snake_movie() {
+        let directions = [JoypadButton::DOWN, JoypadButton::RIGHT, JoypadButton::UP, JoypadButton::LEFT];
+        let mut snake = Snake::new(2024);
+        let mut movie = Movie::new(2024, None);
+        let mut joypads = [Joypad::new(), Joypad::new()];
+        for frame in 0..200 {
+            joypads[0].button_status = directions[frame / 50];
+            movie.record(&joypads);
+            if snake.run_frame(joypads[0].button_status, |_| RunControl::Continue).unwrap() == RunControl::Stop {
+                break;
+            }
+        }
+        let recorded: Vec<u8> = (0..0x0600).map(|addr| snake.cpu.memory_read(addr)).collect();
+
+        let movie = Movie::parse(&movie.to_text()).unwrap();
+        let mut replay = Snake::new(movie.seed);
+        let mut joypads = [Joypad::new(), Joypad::new()];
+        while movie.apply(replay.frames, &mut joypads) {
+            if replay.run_frame(joypads[0].button_status, |_| RunControl::Continue).unwrap() == RunControl::Stop {
+                break;
+            }
+        }
+        let replayed: Vec<u8> = (0..0x0600).map(|addr| replay.cpu.memory_read(addr)).collect();
+        assert_eq!(replay.frames, snake.frames);
+        assert_eq!(replay.cpu.cycles, snake.cpu.cycles);
+        assert_eq!(replayed, recorded);
+    }
+}
@@ -0,0 +1,580 @@
+use crate::cartridge::Mirroring;
+use crate::mapper::Mapper;
+use crate::ppu::frame::Frame;
+use crate::ppu::registers::{ControlRegister, MaskRegister, StatusRegister};
+use crate::savestate::{SaveState, StateError, StateReader, StateWriter};
+
+pub mod frame;
+pub mod palette;
+pub mod registers;
+mod render;
+
+/*
+每条扫描线 341 个 PPU 周期, 每帧 262 条扫描线:
+ 0-239   可见扫描线
+ 240     空闲
+ 241-260 vblank, 241 的第 1 个周期进入 vblank 并产生 NMI
+ 261     预渲染扫描线, 第 1 个周期清除 vblank 和精灵标志
+https://www.nesdev.org/wiki/PPU_rendering
+ */
+const DOTS_PER_SCANLINE: u16 = 341;
+const VISIBLE_SCANLINES: u16 = 240;
+const VBLANK_SCANLINE: u16 = 241;
+const PRE_RENDER_SCANLINE: u16 = 261;
+
+/**
+NES 的图像处理单元 (2C02)
+CPU 通过 0x2000-0x2007 的 8 个寄存器访问它, 它自己还有独立的 14 位地址空间:
+ 0x0000-0x1FFF 图案表 (在卡带上, 经过 mapper)
+ 0x2000-0x2FFF 4 个命名表, 映射到主机内部 2KB 的 VRAM 上 (0x3000-0x3EFF 是镜像)
+ 0x3F00-0x3F1F 调色板
+ */
+pub struct NesPPU {
+    pub ctrl: ControlRegister,
+    pub mask: MaskRegister,
+    pub status: StatusRegister,
+    pub oam_addr: u8,
+    pub oam_data: [u8; 256],
+    pub palette_table: [u8; 32],
+    // 2KB 的命名表, 四屏镜像的卡带额外提供 2KB
+    pub vram: [u8; 0x1000],
+
+    /*
+    滚动和 VRAM 地址使用 loopy 的内部寄存器模型 https://www.nesdev.org/wiki/PPU_scrolling
+     v: 当前 VRAM 地址 (15 位)   yyy NN YYYYY XXXXX
+     t: 临时 VRAM 地址, 也就是屏幕左上角的滚动位置
+     x: 精细 X 滚动 (3 位)
+     w: 写 PPUSCROLL/PPUADDR 时的第一次/第二次写入开关
+     */
+    pub v: u16,
+    pub t: u16,
+    pub fine_x: u8,
+    pub w: bool,
+    // PPUDATA 的读缓冲, 读 VRAM 时返回的是上一次读到的值
+    pub read_buffer: u8,
+    // 最后一次写入寄存器的值, 读只写寄存器时返回它
+    pub io_latch: u8,
+
+    pub scanline: u16,
+    pub cycle: u16,
+    pub frame_count: u64,
+    pub nmi_interrupt: bool,
+    frame_complete: bool,
+    pub frame: Frame,
+}
+
+impl NesPPU {
+    pub fn new() -> Self {
+        NesPPU {
+            ctrl: ControlRegister::empty(),
+            mask: MaskRegister::empty(),
+            status: StatusRegister::empty(),
+            oam_addr: 0,
+            oam_data: [0; 256],
+            palette_table: [0; 32],
+            vram: [0; 0x1000],
+            v: 0,
+            t: 0,
+            fine_x: 0,
+            w: false,
+            read_buffer: 0,
+            io_latch: 0,
+            scanline: 0,
+            cycle: 0,
+            frame_count: 0,
+            nmi_interrupt: false,
+            frame_complete: false,
+            frame: Frame::new(),
+        }
+    }
+
+    /*
+    读 CPU 映射的寄存器, addr 已经去掉了镜像 (0-7)
+     */
+    pub fn read_register(&mut self, addr: u16, mapper: &mut dyn Mapper) -> u8 {
+        match addr {
+            2 => {
+                let data = self.status.bits() | (self.io_latch & 0b0001_1111);
+                self.status.remove(StatusRegister::VBLANK_STARTED);
+                self.w = false;
+                self.io_latch = data;
+                data
+            }
+            4 => self.oam_data[self.oam_addr as usize],
+            7 => {
+                let addr = self.v & 0x3FFF;
+                self.increment_vram_addr();
+                let data = if addr >= 0x3F00 {
+                    // 调色板不经过读缓冲, 但缓冲区会被填入"下面"的命名表数据
+                    self.read_buffer = self.read_vram(addr - 0x1000, mapper);
+                    self.read_palette(addr)
+                } else {
+                    let buffered = self.read_buffer;
+                    self.read_buffer = self.read_vram(addr, mapper);
+                    buffered
+                };
+                self.io_latch = data;
+                data
+            }
+            // 只写寄存器
+            _ => self.io_latch,
+        }
+    }
+
+    pub fn write_register(&mut self, addr: u16, data: u8, mapper: &mut dyn Mapper) {
+        self.io_latch = data;
+        match addr {
+            0 => {
+                let before = self.ctrl.contains(ControlRegister::GENERATE_NMI);
+                self.ctrl = ControlRegister::from_bits_truncate(data);
+                self.t = (self.t & 0xF3FF) | ((data as u16 & 0b11) << 10);
+                // vblank 期间打开 NMI 会立即产生一次 NMI
+                if !before
+                    && self.ctrl.contains(ControlRegister::GENERATE_NMI)
+                    && self.status.contains(StatusRegister::VBLANK_STARTED)
+                {
+                    self.nmi_interrupt = true;
+                }
+            }
+            1 => self.mask = MaskRegister::from_bits_truncate(data),
+            3 => self.oam_addr = data,
+            4 => self.write_oam_data(data),
+            5 => {
+                if !self.w {
+                    self.t = (self.t & 0xFFE0) | (data as u16 >> 3);
+                    self.fine_x = data & 0b111;
+                } else {
+                    self.t = (self.t & 0x8C1F) | ((data as u16 & 0xF8) << 2) | ((data as u16 & 0b111) << 12);
+                }
+                self.w = !self.w;
+            }
+            6 => {
+                if !self.w {
+                    self.t = (self.t & 0x80FF) | ((data as u16 & 0x3F) << 8);
+                } else {
+                    self.t = (self.t & 0xFF00) | data as u16;
+                    self.v = self.t;
+                }
+                self.w = !self.w;
+            }
+            7 => {
+                let addr = self.v & 0x3FFF;
+                self.write_vram(addr, data, mapper);
+                self.increment_vram_addr();
+            }
+            _ => {}
+        }
+    }
+
+    pub fn write_oam_data(&mut self, data: u8) {
+        self.oam_data[self.oam_addr as usize] = data;
+        self.oam_addr = self.oam_addr.wrapping_add(1);
+    }
+
+    fn increment_vram_addr(&mut self) {
+        self.v = self.v.wrapping_add(self.ctrl.vram_addr_increment()) & 0x7FFF;
+    }
+
+    pub fn read_vram(&self, addr: u16, mapper: &dyn Mapper) -> u8 {
+        match addr & 0x3FFF {
+            0x0000..=0x1FFF => mapper.ppu_read(addr),
+            0x2000..=0x3EFF => self.vram[self.mirror_vram_addr(addr, mapper.mirroring())],
+            _ => self.read_palette(addr),
+        }
+    }
+
+    pub fn write_vram(&mut self, addr: u16, data: u8, mapper: &mut dyn Mapper) {
+        match addr & 0x3FFF {
+            0x0000..=0x1FFF => mapper.ppu_write(addr, data),
+            0x2000..=0x3EFF => {
+                let index = self.mirror_vram_addr(addr, mapper.mirroring());
+                self.vram[index] = data;
+            }
+            _ => {
+                let index = palette_index(addr);
+                self.palette_table[index] = data & 0x3F;
+            }
+        }
+    }
+
+    fn read_palette(&self, addr: u16) -> u8 {
+        let data = self.palette_table[palette_index(addr)];
+        if self.mask.contains(MaskRegister::GREYSCALE) {
+            data & 0x30
+        } else {
+            data
+        }
+    }
+
+    /*
+    4 个逻辑命名表 (每个 1KB) 映射到物理 VRAM 上
+     水平镜像:  [A][A]    垂直镜像: [A][B]
+               [B][B]              [A][B]
+     */
+    pub fn mirror_vram_addr(&self, addr: u16, mirroring: Mirroring) -> usize {
+        let index = (addr & 0x0FFF) as usize;
+        let table = index / 0x400;
+        let physical_table = match mirroring {
+            Mirroring::Vertical => table % 2,
+            Mirroring::Horizontal => table / 2,
+            Mirroring::SingleScreenLower => 0,
+            Mirroring::SingleScreenUpper => 1,
+            Mirroring::FourScreen => table,
+        };
+        physical_table * 0x400 + index % 0x400
+    }
+
+    /*
+    PPU 前进 cycles 个 PPU 周期 (CPU 的 1 个周期等于 PPU 的 3 个周期)
+     */
+    pub fn tick(&mut self, cycles: u16, mapper: &mut dyn Mapper) {
+        for _ in 0..cycles {
+            self.step_dot(mapper);
+        }
+    }
+
+    fn step_dot(&mut self, mapper: &mut dyn Mapper) {
+        let rendering = self.mask.rendering_enabled();
+        let visible = self.scanline < VISIBLE_SCANLINES;
+        let pre_render = self.scanline == PRE_RENDER_SCANLINE;
+
+        match self.cycle {
+            1 if self.scanline == VBLANK_SCANLINE => {
+                self.status.insert(StatusRegister::VBLANK_STARTED);
+                self.frame_complete = true;
+                if self.ctrl.contains(ControlRegister::GENERATE_NMI) {
+                    self.nmi_interrupt = true;
+                }
+            }
+            1 if pre_render => {
+                self.status.remove(StatusRegister::VBLANK_STARTED);
+                self.status.remove(StatusRegister::SPRITE_ZERO_HIT);
+                self.status.remove(StatusRegister::SPRITE_OVERFLOW);
+            }
+            256 if visible => {
+                self.render_scanline(mapper);
+                if rendering {
+                    self.increment_y();
+                }
+            }
+            256 if pre_render && rendering => self.increment_y(),
+            257 if (visible || pre_render) && rendering => {
+                // 水平滚动位置在每条扫描线开始前从 t 复制到 v
+                self.v = (self.v & !0x041F) | (self.t & 0x041F);
+            }
+            // MMC3 的扫描线计数器在 A12 上升沿计数, 正常渲染时大约发生在这个位置
+            260 if (visible || pre_render) && rendering => mapper.scanline(),
+            // 垂直滚动位置在预渲染扫描线上从 t 复制到 v
+            280..=304 if pre_render && rendering => {
+                self.v = (self.v & !0x7BE0) | (self.t & 0x7BE0);
+            }
+            _ => {}
+        }
+
+        self.cycle += 1;
+        // 奇数帧的预渲染扫描线少一个周期
+        let skip = pre_render && rendering && self.frame_count % 2 == 1 && self.cycle == DOTS_PER_SCANLINE - 1;
+        if self.cycle >= DOTS_PER_SCANLINE || skip {
+            self.cycle = 0;
+            self.scanline += 1;
+            if self.scanline > PRE_RENDER_SCANLINE {
+                self.scanline = 0;
+                self.frame_count += 1;
+            }
+        }
+    }
+
+    /*
+    v 的垂直滚动位置移到下一行: 先增加精细 Y, 溢出时增加粗略 Y, 粗略 Y 到 29 时切换垂直命名表
+     */
+    fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+        } else {
+            self.v &= !0x7000;
+            let mut y = (self.v & 0x03E0) >> 5;
+            if y == 29 {
+                y = 0;
+                self.v ^= 0x0800;
+            } else if y == 31 {
+                y = 0;
+            } else {
+                y += 1;
+            }
+            self.v = (self.v & !0x03E0) | (y << 5);
+        }
+    }
+
+    // 进入 vblank 之后返回一次 true, 前端用它来判断一帧画面已经完成
+    pub fn frame_complete(&mut self) -> bool {
+        let complete = self.frame_complete;
+        self.frame_complete = false;
+        complete
+    }
+
+    // NMI 请求, 读取之后清除
+    pub fn poll_nmi_interrupt(&mut self) -> bool {
+        let nmi = self.nmi_interrupt;
+        self.nmi_interrupt = false;
+        nmi
+    }
+}
+
+impl Default for NesPPU {
+    fn default() -> Self {
+        NesPPU::new()
+    }
+}
+
+// 0x3F10/0x3F14/0x3F18/0x3F1C 是 0x3F00/0x3F04/0x3F08/0x3F0C 的镜像
+fn palette_index(addr: u16) -> usize {
+    let index = (addr & 0x1F) as usize;
+    if index >= 0x10 && index.is_multiple_of(4) {
+        index - 0x10
+    } else {
+        index
+    }
+}
+
+/*
+画面缓冲区不保存, 恢复之后的下一帧会重新画出来
+ */
+impl SaveState for NesPPU {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_tag(b"PPU ");
+        state.write_u8(self.ctrl.bits());
+        state.write_u8(self.mask.bits());
+        state.write_u8(self.status.bits());
+        state.write_u8(self.oam_addr);
+        state.write_bytes(&self.oam_data);
+        state.write_bytes(&self.palette_table);
+        state.write_bytes(&self.vram);
+        state.write_u16(self.v);
+        state.write_u16(self.t);
+        state.write_u8(self.fine_x);
+        state.write_bool(self.w);
+        state.write_u8(self.read_buffer);
+        state.write_u8(self.io_latch);
+        state.write_u16(self.scanline);
+        state.write_u16(self.cycle);
+        state.write_u64(self.frame_count);
+        state.write_bool(self.nmi_interrupt);
+        state.write_bool(self.frame_complete);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.read_tag(b"PPU ")?;
+        self.ctrl = ControlRegister::from_bits_truncate(state.read_u8()?);
+        self.mask = MaskRegister::from_bits_truncate(state.read_u8()?);
+        self.status = StatusRegister::from_bits_truncate(state.read_u8()?);
+        self.oam_addr = state.read_u8()?;
+        state.read_bytes(&mut self.oam_data)?;
+        state.read_bytes(&mut self.palette_table)?;
+        state.read_bytes(&mut self.vram)?;
+        self.v = state.read_u16()?;
+        self.t = state.read_u16()?;
+        self.fine_x = state.read_u8()?;
+        self.w = state.read_bool()?;
+        self.read_buffer = state.read_u8()?;
+        self.io_latch = state.read_u8()?;
+        self.scanline = state.read_u16()?;
+        self.cycle = state.read_u16()?;
+        self.frame_count = state.read_u64()?;
+        self.nmi_interrupt = state.read_bool()?;
+        self.frame_complete = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::mapper::nrom::Nrom;
+    use crate::ppu::palette::SYSTEM_PALETTE;
+
+    fn chr_ram_mapper(mirroring: Mirroring) -> Nrom {
+        Nrom::new(Cartridge { mirroring, ..Cartridge::default() })
+    }
+
+    fn set_vram_addr(ppu: &mut NesPPU, addr: u16, mapper: &mut dyn Mapper) {
+        ppu.write_register(6, (addr >> 8) as u8, mapper);
+        ppu.write_register(6, (addr & 0xff) as u8, mapper);
+    }
+
+    #[test]
+    fn test_ppudata_read_is_buffered() {
+        let mut mapper = chr_ram_mapper(Mirroring::Horizontal);
+        let mut ppu = NesPPU::new();
+        ppu.vram[0x0305] = 0x66;
+        ppu.vram[0x0306] = 0x77;
+        set_vram_addr(&mut ppu, 0x2305, &mut mapper);
+        // 第一次读到的是缓冲区里的旧值
+        ppu.read_register(7, &mut mapper);
+        assert_eq!(ppu.read_register(7, &mut mapper), 0x66);
+        assert_eq!(ppu.read_register(7, &mut mapper), 0x77);
+        assert_eq!(ppu.v, 0x2308);
+    }
+
+    #[test]
+    fn test_ppudata_increment_32() {
+        let mut mapper = chr_ram_mapper(Mirroring::Horizontal);
+        let mut ppu = NesPPU::new();
+        ppu.write_register(0, 0b0000_0100, &mut mapper);
+        set_vram_addr(&mut ppu, 0x21ff, &mut mapper);
+        ppu.write_register(7, 0x11, &mut mapper);
+        ppu.write_register(7, 0x22, &mut mapper);
+        assert_eq!(ppu.vram[0x01ff], 0x11);
+        assert_eq!(ppu.vram[0x021f], 0x22);
+        assert_eq!(ppu.v, 0x223f);
+    }
+
+    #[test]
+    fn test_status_read_clears_vblank_and_latch() {
+        let mut mapper = chr_ram_mapper(Mirroring::Horizontal);
+        let mut ppu = NesPPU::new();
+        ppu.status.insert(StatusRegister::VBLANK_STARTED);
+        ppu.write_register(6, 0x21, &mut mapper);
+        assert_eq!(ppu.read_register(2, &mut mapper) & 0x80, 0x80);
+        assert_eq!(ppu.read_register(2, &mut mapper) & 0x80, 0);
+        // 读 PPUSTATUS 之后 PPUADDR 又从高字节开始写
+        set_vram_addr(&mut ppu, 0x2305, &mut mapper);
+        assert_eq!(ppu.v, 0x2305);
+    }
+
+    #[test]
+    fn test_nametable_mirroring() {
+        let mut mapper = chr_ram_mapper(Mirroring::Horizontal);
+        let mut ppu = NesPPU::new();
+        ppu.write_vram(0x2005, 0x12, &mut mapper);
+        ppu.write_vram(0x2805, 0x34, &mut mapper);
+        assert_eq!(ppu.read_vram(0x2405, &mapper), 0x12);
+        assert_eq!(ppu.read_vram(0x2c05, &mapper), 0x34);
+        // 0x3000-0x3EFF 是 0x2000-0x2EFF 的镜像
+        assert_eq!(ppu.read_vram(0x3005, &mapper), 0x12);
+
+        let mut mapper = chr_ram_mapper(Mirroring::Vertical);
+        let mut ppu = NesPPU::new();
+        ppu.write_vram(0x2005, 0x12, &mut mapper);
+        ppu.write_vram(0x2405, 0x34, &mut mapper);
+        assert_eq!(ppu.read_vram(0x2805, &mapper), 0x12);
+        assert_eq!(ppu.read_vram(0x2c05, &mapper), 0x34);
+    }
+
+    #[test]
+    fn test_palette_mirroring() {
+        let mut mapper = chr_ram_mapper(Mirroring::Horizontal);
+        let mut ppu = NesPPU::new();
+        ppu.write_vram(0x3f10, 0x2a, &mut mapper);
+        ppu.write_vram(0x3f25, 0x15, &mut mapper);
+        assert_eq!(ppu.read_vram(0x3f00, &mapper), 0x2a);
+        assert_eq!(ppu.read_vram(0x3f05, &mapper), 0x15);
+        // 调色板不经过读缓冲
+        set_vram_addr(&mut ppu, 0x3f00, &mut mapper);
+        assert_eq!(ppu.read_register(7, &mut mapper), 0x2a);
+    }
+
+    #[test]
+    fn test_oam_data() {
+        let mut mapper = chr_ram_mapper(Mirroring::Horizontal);
+        let mut ppu = NesPPU::new();
+        ppu.write_register(3, 0xfe, &mut mapper);
+        ppu.write_register(4, 0x11, &mut mapper);
+        ppu.write_register(4, 0x22, &mut mapper);
+        assert_eq!(ppu.oam_addr, 0x00);
+        ppu.write_register(3, 0xff, &mut mapper);
+        assert_eq!(ppu.read_register(4, &mut mapper), 0x22);
+    }
+
+    #[test]
+    fn test_vblank_nmi() {
+        let mut mapper = chr_ram_mapper(Mirroring::Horizontal);
+        let mut ppu = NesPPU::new();
+        ppu.write_register(0, 0b1000_0000, &mut mapper);
+        for _ in 0..VBLANK_SCANLINE {
+            ppu.tick(DOTS_PER_SCANLINE, &mut mapper);
+        }
+        ppu.tick(1, &mut mapper);
+        assert!(!ppu.poll_nmi_interrupt());
+        ppu.tick(1, &mut mapper);
+        assert!(ppu.status.contains(StatusRegister::VBLANK_STARTED));
+        assert!(ppu.poll_nmi_interrupt());
+        assert!(ppu.frame_complete());
+        assert!(!ppu.frame_complete());
+    }
+
+    /*
+    图块 1 的所有像素颜色编号都是 3, 命名表左上角放图块 1, 背景调色板 0 的颜色 3 设为 0x16 (红色)
+     */
+    fn solid_tile_ppu(mapper: &mut dyn Mapper) -> NesPPU {
+        let mut ppu = NesPPU::new();
+        for row in 0..16 {
+            ppu.write_vram(16 + row, 0xff, mapper);
+        }
+        ppu.write_vram(0x2000, 1, mapper);
+        ppu.write_vram(0x3f00, 0x0f, mapper);
+        ppu.write_vram(0x3f03, 0x16, mapper);
+        ppu.write_vram(0x3f13, 0x2a, mapper);
+        ppu
+    }
+
+    #[test]
+    fn test_background_rendering() {
+        let mut mapper = chr_ram_mapper(Mirroring::Horizontal);
+        let mut ppu = solid_tile_ppu(&mut mapper);
+        ppu.mask = MaskRegister::SHOW_BACKGROUND | MaskRegister::LEFTMOST_8PXL_BACKGROUND;
+        ppu.tick(DOTS_PER_SCANLINE * 8, &mut mapper);
+        assert_eq!(ppu.frame.pixel(0, 0), SYSTEM_PALETTE[0x16]);
+        assert_eq!(ppu.frame.pixel(7, 7), SYSTEM_PALETTE[0x16]);
+        assert_eq!(ppu.frame.pixel(8, 0), SYSTEM_PALETTE[0x0f]);
+
+        // 水平滚动 4 个像素之后图块左移
+        let mut ppu = solid_tile_ppu(&mut mapper);
+        ppu.mask = MaskRegister::SHOW_BACKGROUND | MaskRegister::LEFTMOST_8PXL_BACKGROUND;
+        ppu.write_register(5, 4, &mut mapper);
+        ppu.write_register(5, 0, &mut mapper);
+        ppu.v = ppu.t;
+        ppu.tick(DOTS_PER_SCANLINE, &mut mapper);
+        assert_eq!(ppu.frame.pixel(3, 0), SYSTEM_PALETTE[0x16]);
+        assert_eq!(ppu.frame.pixel(4, 0), SYSTEM_PALETTE[0x0f]);
+    }
+
+    #[test]
+    fn test_sprite_rendering_and_zero_hit() {
+        let mut mapper = chr_ram_mapper(Mirroring::Horizontal);
+        let mut ppu = solid_tile_ppu(&mut mapper);
+        ppu.mask = MaskRegister::SHOW_BACKGROUND | MaskRegister::SHOW_SPRITES;
+        // 精灵 0 放在 (20, 11), 不和背景重叠
+        ppu.oam_data[0..4].copy_from_slice(&[10, 1, 0, 20]);
+        ppu.tick(DOTS_PER_SCANLINE * 20, &mut mapper);
+        assert_eq!(ppu.frame.pixel(20, 11), SYSTEM_PALETTE[0x2a]);
+        assert_eq!(ppu.frame.pixel(20, 10), SYSTEM_PALETTE[0x0f]);
+        assert!(!ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT));
+
+        // 和背景的不透明像素重叠时设置精灵 0 命中
+        let mut ppu = solid_tile_ppu(&mut mapper);
+        ppu.mask = MaskRegister::SHOW_BACKGROUND | MaskRegister::SHOW_SPRITES;
+        ppu.oam_data[0..4].copy_from_slice(&[2, 1, 0, 12]);
+        ppu.write_vram(0x2001, 1, &mut mapper);
+        ppu.tick(DOTS_PER_SCANLINE * 4, &mut mapper);
+        assert!(ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT));
+        // 预渲染扫描线清除命中标志
+        for _ in 4..PRE_RENDER_SCANLINE {
+            ppu.tick(DOTS_PER_SCANLINE, &mut mapper);
+        }
+        ppu.tick(2, &mut mapper);
+        assert!(!ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT));
+    }
+
+    #[test]
+    fn test_sprite_overflow() {
+        let mut mapper = chr_ram_mapper(Mirroring::Horizontal);
+        let mut ppu = NesPPU::new();
+        ppu.mask = MaskRegister::SHOW_SPRITES;
+        for i in 0..9 {
+            ppu.oam_data[i * 4..i * 4 + 4].copy_from_slice(&[0, 1, 0, (i * 10) as u8]);
+        }
+        ppu.tick(DOTS_PER_SCANLINE * 2, &mut mapper);
+        assert!(ppu.status.contains(StatusRegister::SPRITE_OVERFLOW));
+    }
+}
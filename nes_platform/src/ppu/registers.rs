@@ -0,0 +1,107 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// # PPUCTRL 0x2000 https://www.nesdev.org/wiki/PPU_registers#PPUCTRL
+    ///
+    ///  7 6 5 4 3 2 1 0
+    ///  V P H B S I N N
+    ///  | | | | | | +-+--- 基础命名表地址 (0 = $2000; 1 = $2400; 2 = $2800; 3 = $2C00)
+    ///  | | | | | +------- 读写 PPUDATA 之后 VRAM 地址的增量 (0: +1 横向; 1: +32 纵向)
+    ///  | | | | +--------- 8x8 精灵的图案表地址 (0: $0000; 1: $1000)
+    ///  | | | +----------- 背景的图案表地址 (0: $0000; 1: $1000)
+    ///  | | +------------- 精灵大小 (0: 8x8; 1: 8x16)
+    ///  | +--------------- PPU 主从模式, NES 上没有使用
+    ///  +----------------- 进入 vblank 时是否产生 NMI
+    ///
+    pub struct ControlRegister: u8 {
+        const NAMETABLE1              = 0b0000_0001;
+        const NAMETABLE2              = 0b0000_0010;
+        const VRAM_ADD_INCREMENT      = 0b0000_0100;
+        const SPRITE_PATTERN_ADDR     = 0b0000_1000;
+        const BACKROUND_PATTERN_ADDR  = 0b0001_0000;
+        const SPRITE_SIZE             = 0b0010_0000;
+        const MASTER_SLAVE_SELECT     = 0b0100_0000;
+        const GENERATE_NMI            = 0b1000_0000;
+    }
+}
+
+impl ControlRegister {
+    pub fn vram_addr_increment(&self) -> u16 {
+        if self.contains(ControlRegister::VRAM_ADD_INCREMENT) {
+            32
+        } else {
+            1
+        }
+    }
+
+    pub fn sprite_pattern_addr(&self) -> u16 {
+        if self.contains(ControlRegister::SPRITE_PATTERN_ADDR) {
+            0x1000
+        } else {
+            0
+        }
+    }
+
+    pub fn background_pattern_addr(&self) -> u16 {
+        if self.contains(ControlRegister::BACKROUND_PATTERN_ADDR) {
+            0x1000
+        } else {
+            0
+        }
+    }
+
+    pub fn sprite_height(&self) -> usize {
+        if self.contains(ControlRegister::SPRITE_SIZE) {
+            16
+        } else {
+            8
+        }
+    }
+}
+
+bitflags! {
+    /// # PPUMASK 0x2001 https://www.nesdev.org/wiki/PPU_registers#PPUMASK
+    ///
+    ///  7 6 5 4 3 2 1 0
+    ///  B G R s b M m G
+    ///  | | | | | | | +--- 灰度
+    ///  | | | | | | +----- 在屏幕最左边 8 个像素显示背景
+    ///  | | | | | +------- 在屏幕最左边 8 个像素显示精灵
+    ///  | | | | +--------- 显示背景
+    ///  | | | +----------- 显示精灵
+    ///  +-+-+------------- 颜色强调
+    ///
+    pub struct MaskRegister: u8 {
+        const GREYSCALE               = 0b0000_0001;
+        const LEFTMOST_8PXL_BACKGROUND = 0b0000_0010;
+        const LEFTMOST_8PXL_SPRITE    = 0b0000_0100;
+        const SHOW_BACKGROUND         = 0b0000_1000;
+        const SHOW_SPRITES            = 0b0001_0000;
+        const EMPHASISE_RED           = 0b0010_0000;
+        const EMPHASISE_GREEN         = 0b0100_0000;
+        const EMPHASISE_BLUE          = 0b1000_0000;
+    }
+}
+
+impl MaskRegister {
+    pub fn rendering_enabled(&self) -> bool {
+        self.intersects(MaskRegister::SHOW_BACKGROUND | MaskRegister::SHOW_SPRITES)
+    }
+}
+
+bitflags! {
+    /// # PPUSTATUS 0x2002 https://www.nesdev.org/wiki/PPU_registers#PPUSTATUS
+    ///
+    ///  7 6 5 4 3 2 1 0
+    ///  V S O . . . . .
+    ///  | | | +-+-+-+-+--- 没有使用, 读出来是 PPU 总线上最后一次的值
+    ///  | | +------------- 精灵溢出: 一条扫描线上超过 8 个精灵
+    ///  | +--------------- 精灵 0 碰撞: 精灵 0 的不透明像素和背景的不透明像素重叠
+    ///  +----------------- 处于 vblank
+    ///
+    pub struct StatusRegister: u8 {
+        const SPRITE_OVERFLOW = 0b0010_0000;
+        const SPRITE_ZERO_HIT = 0b0100_0000;
+        const VBLANK_STARTED  = 0b1000_0000;
+    }
+}
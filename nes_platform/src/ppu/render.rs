@@ -0,0 +1,176 @@
+use crate::mapper::Mapper;
+use crate::ppu::frame::Frame;
+use crate::ppu::palette::SYSTEM_PALETTE;
+use crate::ppu::registers::{MaskRegister, StatusRegister};
+use crate::ppu::NesPPU;
+
+/*
+OAM 中每个精灵占 4 个字节
+ 0 Y 坐标 (精灵实际显示在下一条扫描线)
+ 1 图块编号
+ 2 属性: 76543210
+         |||   ++- 调色板 (4-7)
+         ||+------ 优先级 (0: 在背景前面; 1: 在背景后面)
+         |+------- 水平翻转
+         +-------- 垂直翻转
+ 3 X 坐标
+ */
+const MAX_SPRITES_PER_SCANLINE: usize = 8;
+
+impl NesPPU {
+    /*
+    渲染当前扫描线, 在第 256 个周期调用, 使用此时 v 和 fine_x 中的滚动位置
+     */
+    pub(crate) fn render_scanline(&mut self, mapper: &mut dyn Mapper) {
+        let y = self.scanline as usize;
+        // 每个像素的背景颜色编号 (0-3), 0 表示透明
+        let mut background = [0u8; Frame::WIDTH];
+        let mut line = [self.palette_entry(0); Frame::WIDTH];
+
+        if self.mask.contains(MaskRegister::SHOW_BACKGROUND) {
+            self.render_background_line(mapper, &mut background, &mut line);
+        }
+        if self.mask.contains(MaskRegister::SHOW_SPRITES) {
+            self.render_sprite_line(mapper, &background, &mut line);
+        }
+
+        for (x, palette_index) in line.iter().enumerate() {
+            self.frame.set_pixel(x, y, SYSTEM_PALETTE[(*palette_index & 0x3F) as usize]);
+        }
+    }
+
+    fn palette_entry(&self, index: usize) -> u8 {
+        let data = self.palette_table[index];
+        if self.mask.contains(MaskRegister::GREYSCALE) {
+            data & 0x30
+        } else {
+            data
+        }
+    }
+
+    fn render_background_line(&self, mapper: &dyn Mapper, background: &mut [u8; Frame::WIDTH], line: &mut [u8; Frame::WIDTH]) {
+        let coarse_x = (self.v & 0x001F) as usize;
+        let coarse_y = ((self.v >> 5) & 0x001F) as usize;
+        let nametable_x = ((self.v >> 10) & 1) as usize;
+        let nametable_y = ((self.v >> 11) & 1) as usize;
+        let fine_y = (self.v >> 12) & 0b111;
+        let pattern_base = self.ctrl.background_pattern_addr();
+        let show_left = self.mask.contains(MaskRegister::LEFTMOST_8PXL_BACKGROUND);
+
+        // 从屏幕左边开始的水平位置 (两个命名表拼起来共 512 个像素宽)
+        let scroll_x = nametable_x * 256 + coarse_x * 8 + self.fine_x as usize;
+
+        let mut x = 0;
+        while x < Frame::WIDTH {
+            let position = (scroll_x + x) % 512;
+            let nametable = (nametable_y << 1) | (position / 256);
+            let tile_column = (position % 256) / 8;
+            let nametable_addr = 0x2000 + (nametable as u16) * 0x400;
+
+            let tile = self.read_vram(nametable_addr + (coarse_y * 32 + tile_column) as u16, mapper) as u16;
+            /*
+            属性表: 每个字节控制 4x4 个图块, 每 2 位控制其中 2x2 个图块的调色板
+             */
+            let attribute = self.read_vram(nametable_addr + 0x3C0 + ((coarse_y / 4) * 8 + tile_column / 4) as u16, mapper);
+            let shift = ((coarse_y % 4) / 2) * 4 + ((tile_column % 4) / 2) * 2;
+            let palette = (attribute >> shift) & 0b11;
+
+            // 每个图块 16 字节, 前 8 字节是颜色编号的低位, 后 8 字节是高位
+            let lo = mapper.ppu_read(pattern_base + tile * 16 + fine_y);
+            let hi = mapper.ppu_read(pattern_base + tile * 16 + fine_y + 8);
+
+            // 从当前像素画到这个图块的末尾
+            let mut fine = position % 8;
+            while fine < 8 && x < Frame::WIDTH {
+                let bit = 7 - fine;
+                let value = ((hi >> bit) & 1) << 1 | ((lo >> bit) & 1);
+                if x >= 8 || show_left {
+                    background[x] = value;
+                    if value != 0 {
+                        line[x] = self.palette_entry((palette as usize) * 4 + value as usize);
+                    }
+                }
+                fine += 1;
+                x += 1;
+            }
+        }
+    }
+
+    fn render_sprite_line(&mut self, mapper: &dyn Mapper, background: &[u8; Frame::WIDTH], line: &mut [u8; Frame::WIDTH]) {
+        let y = self.scanline as usize;
+        let height = self.ctrl.sprite_height();
+        let show_left = self.mask.contains(MaskRegister::LEFTMOST_8PXL_SPRITE);
+        let background_enabled = self.mask.contains(MaskRegister::SHOW_BACKGROUND);
+
+        // 找出这条扫描线上的精灵, 最多 8 个, OAM 中靠前的优先级高
+        let mut sprites = Vec::with_capacity(MAX_SPRITES_PER_SCANLINE);
+        for index in 0..64 {
+            let sprite_y = self.oam_data[index * 4] as usize + 1;
+            if y >= sprite_y && y < sprite_y + height {
+                if sprites.len() == MAX_SPRITES_PER_SCANLINE {
+                    self.status.insert(StatusRegister::SPRITE_OVERFLOW);
+                    break;
+                }
+                sprites.push(index);
+            }
+        }
+
+        // OAM 中靠前的精灵先画, 同一个像素只画一次
+        let mut drawn = [false; Frame::WIDTH];
+        for &index in sprites.iter() {
+            let sprite_y = self.oam_data[index * 4] as usize + 1;
+            let tile = self.oam_data[index * 4 + 1] as u16;
+            let attributes = self.oam_data[index * 4 + 2];
+            let sprite_x = self.oam_data[index * 4 + 3] as usize;
+
+            let flip_vertical = attributes & 0b1000_0000 != 0;
+            let flip_horizontal = attributes & 0b0100_0000 != 0;
+            let behind_background = attributes & 0b0010_0000 != 0;
+            let palette = (attributes & 0b11) as usize + 4;
+
+            let mut row = (y - sprite_y) as u16;
+            if flip_vertical {
+                row = height as u16 - 1 - row;
+            }
+            let tile_addr = if height == 16 {
+                // 8x16 精灵: 图块编号的位0选择图案表, 上半部分用偶数图块, 下半部分用下一个图块
+                let bank = (tile & 1) * 0x1000;
+                let tile = (tile & 0xFE) + row / 8;
+                bank + tile * 16 + row % 8
+            } else {
+                self.ctrl.sprite_pattern_addr() + tile * 16 + row
+            };
+            let lo = mapper.ppu_read(tile_addr);
+            let hi = mapper.ppu_read(tile_addr + 8);
+
+            for column in 0..8 {
+                let x = sprite_x + column;
+                if x >= Frame::WIDTH {
+                    break;
+                }
+                if x < 8 && !show_left {
+                    continue;
+                }
+                let bit = if flip_horizontal { column } else { 7 - column };
+                let value = ((hi >> bit) & 1) << 1 | ((lo >> bit) & 1);
+                if value == 0 {
+                    continue;
+                }
+
+                // 精灵 0 的不透明像素和背景的不透明像素重叠, 第 255 列永远不会触发
+                if index == 0 && background_enabled && background[x] != 0 && x != 255 {
+                    self.status.insert(StatusRegister::SPRITE_ZERO_HIT);
+                }
+
+                if drawn[x] {
+                    continue;
+                }
+                drawn[x] = true;
+                if behind_background && background[x] != 0 {
+                    continue;
+                }
+                line[x] = self.palette_entry(palette * 4 + value as usize);
+            }
+        }
+    }
+}
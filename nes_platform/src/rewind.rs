@@ -0,0 +1,247 @@
+use std::collections::VecDeque;
+
+use crate::cpu::CPU;
+use crate::savestate;
+
+// 连续这么多个 0 才结束一段原样保存的字节, 太短的 0 不值得单独编码
+const MIN_ZERO_RUN: usize = 3;
+
+/**
+倒带缓冲区: 定期保存机器的状态, 按住倒带键时一个一个往回恢复
+
+只保存最新的一个完整状态, 更早的状态保存为和后一个状态的差异 (异或之后压缩连续的 0),
+从最新的状态依次异或回去就能得到任意一个更早的状态. 一帧之内变化的内存很少, 差异通常只有几百字节
+超过内存预算时丢弃最早的差异, 不影响其余的状态
+ */
+pub struct Rewind {
+    budget_bytes: usize,
+    // 每隔多少帧保存一次
+    interval: u32,
+    frames: u32,
+    latest: Option<Vec<u8>>,
+    // 从旧到新, 第 i 个差异把第 i+1 个状态变回第 i 个状态
+    deltas: VecDeque<Vec<u8>>,
+    used_bytes: usize,
+}
+
+impl Rewind {
+    pub fn new(budget_bytes: usize, interval: u32) -> Self {
+        Rewind { budget_bytes, interval: interval.max(1), frames: 0, latest: None, deltas: VecDeque::new(), used_bytes: 0 }
+    }
+
+    // 可以往回恢复的状态数
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn memory_used(&self) -> usize {
+        self.used_bytes + self.latest.as_ref().map_or(0, |latest| latest.len())
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.used_bytes = 0;
+        self.frames = 0;
+    }
+
+    /*
+    每帧调用一次, 每 interval 帧保存一次状态
+     */
+    pub fn record_frame(&mut self, cpu: &CPU) {
+        self.frames += 1;
+        if self.frames >= self.interval {
+            self.frames = 0;
+            self.push(cpu);
+        }
+    }
+
+    pub fn push(&mut self, cpu: &CPU) {
+        let state = savestate::snapshot(cpu);
+        if let Some(latest) = self.latest.take() {
+            // 同一台机器的状态长度不变, 长度变了说明换了卡带, 之前的状态都不能用了
+            if latest.len() == state.len() {
+                let delta = encode_delta(&state, &latest);
+                self.used_bytes += delta.len();
+                self.deltas.push_back(delta);
+            } else {
+                self.deltas.clear();
+                self.used_bytes = 0;
+            }
+        }
+        self.latest = Some(state);
+        while self.memory_used() > self.budget_bytes {
+            let Some(oldest) = self.deltas.pop_front() else { break };
+            self.used_bytes -= oldest.len();
+        }
+    }
+
+    /*
+    恢复到上一个保存的状态, 没有更早的状态时返回 false, 机器保持不变
+     */
+    pub fn rewind(&mut self, cpu: &mut CPU) -> bool {
+        let (Some(latest), Some(delta)) = (self.latest.as_mut(), self.deltas.pop_back()) else {
+            return false;
+        };
+        self.used_bytes -= delta.len();
+        apply_delta(latest, &delta);
+        self.frames = 0;
+        savestate::restore(cpu, latest).is_ok()
+    }
+}
+
+/*
+差异的格式: 重复 [连续 0 的个数] [原样保存的字节数] [原样保存的字节], 个数使用 LEB128 变长编码
+ */
+fn encode_delta(from: &[u8], to: &[u8]) -> Vec<u8> {
+    let xor: Vec<u8> = from.iter().zip(to).map(|(a, b)| a ^ b).collect();
+    let mut delta = Vec::new();
+    let mut i = 0;
+    while i < xor.len() {
+        let zeros = xor[i..].iter().take_while(|&&b| b == 0).count();
+        i += zeros;
+        // 结尾的 0 不需要编码
+        if i == xor.len() {
+            break;
+        }
+        let literal_start = i;
+        while i < xor.len() {
+            let run = xor[i..].iter().take(MIN_ZERO_RUN).take_while(|&&b| b == 0).count();
+            if run == MIN_ZERO_RUN || i + run == xor.len() {
+                break;
+            }
+            i += run.max(1);
+        }
+        write_varint(&mut delta, zeros);
+        write_varint(&mut delta, i - literal_start);
+        delta.extend_from_slice(&xor[literal_start..i]);
+    }
+    delta
+}
+
+fn apply_delta(state: &mut [u8], delta: &[u8]) {
+    let mut position = 0;
+    let mut i = 0;
+    while i < delta.len() {
+        position += read_varint(delta, &mut i);
+        let literals = read_varint(delta, &mut i);
+        for (byte, xor) in state[position..position + literals].iter_mut().zip(&delta[i..i + literals]) {
+            *byte ^= xor;
+        }
+        position += literals;
+        i += literals;
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_varint(data: &[u8], i: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*i];
+        *i += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_delta_round_trip() {
+        let from: Vec<u8> = (0..1000).map(|i| (i % 251) as u8).collect();
+        let mut to = from.clone();
+        to[0] ^= 1;
+        to[10] = 0xFF;
+        to[12] = 0xFE;
+        to[500..700].iter_mut().for_each(|b| *b = b.wrapping_add(3));
+        to[999] = 0;
+        let delta = encode_delta(&to, &from);
+        assert!(delta.len() < 250);
+        let mut state = to.clone();
+        apply_delta(&mut state, &delta);
+        assert_eq!(state, from);
+
+        assert!(encode_delta(&from, &from).is_empty());
+    }
+
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 127, 128, 300, 65535, 1 << 20] {
+            let mut out = Vec::new();
+            write_varint(&mut out, value);
+            let mut i = 0;
+            assert_eq!(read_varint(&out, &mut i), value);
+            assert_eq!(i, out.len());
+        }
+    }
+
+    #[test]
+    fn test_rewind_restores_states_in_reverse() {
+        let mut cpu = CPU::new();
+        let mut rewind = Rewind::new(usize::MAX, 1);
+        assert!(!rewind.rewind(&mut cpu));
+        for frame in 0..10u8 {
+            cpu.memory_write(0x0200 + frame as u16, frame + 1);
+            cpu.register_a = frame;
+            rewind.record_frame(&cpu);
+        }
+        assert_eq!(rewind.len(), 9);
+        // 一帧只改了一个字节, 差异远小于 64KB 的完整状态
+        assert!(rewind.memory_used() < 0x10000 + 9 * 64);
+
+        for frame in (0..9u8).rev() {
+            assert!(rewind.rewind(&mut cpu));
+            assert_eq!(cpu.register_a, frame);
+            assert_eq!(cpu.memory_read(0x0200 + frame as u16), frame + 1);
+            assert_eq!(cpu.memory_read(0x0201 + frame as u16), 0);
+        }
+        assert!(!rewind.rewind(&mut cpu));
+        assert_eq!(cpu.register_a, 0);
+    }
+
+    #[test]
+    fn test_interval_and_budget() {
+        let mut cpu = CPU::new();
+        let mut rewind = Rewind::new(usize::MAX, 3);
+        for _ in 0..9 {
+            rewind.record_frame(&cpu);
+        }
+        assert_eq!(rewind.len(), 2);
+
+        let full = savestate::snapshot(&cpu).len();
+        let mut rewind = Rewind::new(full + 40, 1);
+        for frame in 0..20u8 {
+            // 每帧改变 4 个分开的字节, 每个差异大约 16 字节
+            for i in 0..4u16 {
+                cpu.memory_write(0x1000 * i, frame);
+            }
+            rewind.push(&cpu);
+        }
+        assert!(rewind.memory_used() <= full + 40);
+        assert!(!rewind.is_empty() && rewind.len() < 19);
+        // 丢掉的是最早的状态, 最近的状态仍然可以恢复
+        assert!(rewind.rewind(&mut cpu));
+        assert_eq!(cpu.memory_read(0), 18);
+    }
+}
@@ -0,0 +1,393 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::checksum::crc32;
+use crate::cpu::CPU;
+
+/*
+存档文件格式 (所有整数都是小端):
+ 0   4  "NSS\x1A"
+ 4   2  格式版本, 见 VERSION
+ 6   2  保留, 0
+ 8   4  数据的长度
+ 12  4  数据的 CRC-32
+ 16  .. 数据: 按 CPU, 总线, PPU, APU, 手柄, mapper 的顺序依次保存, 每一段以 4 字节的标签开头
+数据的布局发生变化时增加 VERSION, 旧版本的存档会被拒绝而不是读出错误的状态
+ */
+const MAGIC: [u8; 4] = *b"NSS\x1A";
+pub const VERSION: u16 = 1;
+const HEADER_SIZE: usize = 16;
+
+#[derive(Debug)]
+pub enum StateError {
+    Io(std::io::Error),
+    // 文件不是以 "NSS\x1A" 开头
+    InvalidTag,
+    // 其他版本的模拟器保存的存档
+    UnsupportedVersion(u16),
+    ChecksumMismatch { expected: u32, actual: u32 },
+    // 数据比布局要求的短
+    Truncated,
+    // 存档和当前的机器对不上, 比如是另一个卡带的存档
+    Mismatch(String),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::Io(err) => write!(f, "failed to access save state: {}", err),
+            StateError::InvalidTag => write!(f, "file is not a save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "save state version {} is not supported (expected {})", version, VERSION)
+            }
+            StateError::ChecksumMismatch { expected, actual } => {
+                write!(f, "save state is corrupted: checksum {:08X} does not match {:08X}", actual, expected)
+            }
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Mismatch(what) => write!(f, "save state does not match this machine: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for StateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StateError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for StateError {
+    fn from(err: std::io::Error) -> Self {
+        StateError::Io(err)
+    }
+}
+
+/**
+可以保存和恢复的状态. 只保存运行中会改变的部分, ROM 和采样率之类的配置由创建机器时决定
+ */
+pub trait SaveState {
+    fn save_state(&self, state: &mut StateWriter);
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError>;
+}
+
+#[derive(Debug, Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        StateWriter::default()
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+
+    // 每一段状态的开头写一个标签, 恢复时用来发现布局错位
+    pub fn write_tag(&mut self, tag: &[u8; 4]) {
+        self.data.extend_from_slice(tag);
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.write_u32(value.to_bits());
+    }
+
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_u64(value.to_bits());
+    }
+
+    // 长度固定的数组, 恢复时长度由类型决定
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    // 长度由卡带决定的存储器 (PRG-RAM, CHR-RAM), 先写长度
+    pub fn write_block(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.write_bytes(bytes);
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        StateReader { data, position: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == self.data.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        let end = self.position.checked_add(len).filter(|end| *end <= self.data.len()).ok_or(StateError::Truncated)?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub fn read_tag(&mut self, tag: &[u8; 4]) -> Result<(), StateError> {
+        let found = self.take(4)?;
+        if found != tag {
+            return Err(StateError::Mismatch(format!(
+                "expected section {:?} but found {:?}",
+                String::from_utf8_lossy(tag),
+                String::from_utf8_lossy(found)
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, StateError> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn read_f32(&mut self) -> Result<f32, StateError> {
+        Ok(f32::from_bits(self.read_u32()?))
+    }
+
+    pub fn read_f64(&mut self) -> Result<f64, StateError> {
+        Ok(f64::from_bits(self.read_u64()?))
+    }
+
+    pub fn read_bytes(&mut self, bytes: &mut [u8]) -> Result<(), StateError> {
+        bytes.copy_from_slice(self.take(bytes.len())?);
+        Ok(())
+    }
+
+    // 存档里的长度必须和当前卡带的存储器一致
+    pub fn read_block(&mut self, bytes: &mut [u8]) -> Result<(), StateError> {
+        let len = self.read_u32()? as usize;
+        if len != bytes.len() {
+            return Err(StateError::Mismatch(format!("memory block of {} bytes, expected {}", len, bytes.len())));
+        }
+        self.read_bytes(bytes)
+    }
+}
+
+/**
+整台机器的状态 (不带文件头), 倒带之类在内存中保存状态的功能直接使用它
+ */
+pub fn snapshot(cpu: &CPU) -> Vec<u8> {
+    let mut state = StateWriter::new();
+    cpu.save_state(&mut state);
+    state.into_inner()
+}
+
+/*
+恢复 snapshot 保存的状态. 出错时机器保持原来的状态, 不会只恢复了一半
+ */
+pub fn restore(cpu: &mut CPU, data: &[u8]) -> Result<(), StateError> {
+    let backup = snapshot(cpu);
+    let mut state = StateReader::new(data);
+    let result = cpu.load_state(&mut state).and_then(|_| {
+        if state.is_empty() {
+            Ok(())
+        } else {
+            Err(StateError::Mismatch("unexpected data after the end of the state".to_string()))
+        }
+    });
+    if result.is_err() {
+        cpu.load_state(&mut StateReader::new(&backup)).expect("restore the state that was just saved");
+    }
+    result
+}
+
+/**
+带文件头和校验和的存档, 可以直接写入文件
+ */
+pub fn encode(cpu: &CPU) -> Vec<u8> {
+    let data = snapshot(cpu);
+    let mut file = Vec::with_capacity(HEADER_SIZE + data.len());
+    file.extend_from_slice(&MAGIC);
+    file.extend_from_slice(&VERSION.to_le_bytes());
+    file.extend_from_slice(&0u16.to_le_bytes());
+    file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    file.extend_from_slice(&crc32(&data).to_le_bytes());
+    file.extend_from_slice(&data);
+    file
+}
+
+pub fn decode(cpu: &mut CPU, file: &[u8]) -> Result<(), StateError> {
+    if file.len() < HEADER_SIZE {
+        return Err(if file.starts_with(&MAGIC) { StateError::Truncated } else { StateError::InvalidTag });
+    }
+    let mut header = StateReader::new(&file[..HEADER_SIZE]);
+    header.read_tag(&MAGIC).map_err(|_| StateError::InvalidTag)?;
+    let version = header.read_u16()?;
+    if version != VERSION {
+        return Err(StateError::UnsupportedVersion(version));
+    }
+    header.read_u16()?;
+    let len = header.read_u32()? as usize;
+    let expected = header.read_u32()?;
+    let data = file.get(HEADER_SIZE..HEADER_SIZE + len).ok_or(StateError::Truncated)?;
+    let actual = crc32(data);
+    if actual != expected {
+        return Err(StateError::ChecksumMismatch { expected, actual });
+    }
+    restore(cpu, data)
+}
+
+pub fn save_file<P: AsRef<Path>>(cpu: &CPU, path: P) -> Result<(), StateError> {
+    fs::write(path, encode(cpu))?;
+    Ok(())
+}
+
+pub fn load_file<P: AsRef<Path>>(cpu: &mut CPU, path: P) -> Result<(), StateError> {
+    let file = fs::read(path)?;
+    decode(cpu, &file)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::NesBus;
+    use crate::cartridge::Cartridge;
+    use crate::mapper::test::numbered_cartridge;
+
+    // 在 NesBus 上运行一段会写内存, 访问 PPU 和 APU 的程序
+    fn nes_cpu(cartridge: Cartridge) -> CPU {
+        let mut cpu = CPU::with_bus(Box::new(NesBus::new(cartridge).unwrap()));
+        let program = crate::asm!(
+            "       .org $0300",
+            "loop:  INX",
+            "       STX $0200",
+            "       STX $2006",
+            "       STX $2007",
+            "       STX $4000",
+            "       LDA #$0F",
+            "       STA $4015",
+            "       JMP loop",
+        );
+        for (i, byte) in program.into_iter().enumerate() {
+            cpu.memory_write(0x0300 + i as u16, byte);
+        }
+        cpu.program_counter = 0x0300;
+        cpu
+    }
+
+    fn run(cpu: &mut CPU, steps: usize) {
+        for _ in 0..steps {
+            cpu.step().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_round_trip_flat_memory() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(crate::asm!("LDA #$42", "STA $10", "LDX #$07", "PHA", "BRK")).unwrap();
+        let file = encode(&cpu);
+
+        let mut restored = CPU::new();
+        decode(&mut restored, &file).unwrap();
+        assert_eq!(restored.register_a, 0x42);
+        assert_eq!(restored.register_x, 0x07);
+        assert_eq!(restored.stack_pointer, cpu.stack_pointer);
+        assert_eq!(restored.program_counter, cpu.program_counter);
+        assert_eq!(restored.cycles, cpu.cycles);
+        assert_eq!(restored.memory_read(0x10), 0x42);
+        assert_eq!(snapshot(&restored), snapshot(&cpu));
+    }
+
+    #[test]
+    fn test_restored_machine_runs_identically() {
+        let mut cpu = nes_cpu(numbered_cartridge(4, 8, 8));
+        run(&mut cpu, 5000);
+        let saved = encode(&cpu);
+        run(&mut cpu, 5000);
+        let expected = snapshot(&cpu);
+
+        let mut other = nes_cpu(numbered_cartridge(4, 8, 8));
+        decode(&mut other, &saved).unwrap();
+        run(&mut other, 5000);
+        assert_eq!(snapshot(&other), expected);
+    }
+
+    #[test]
+    fn test_rejects_bad_files() {
+        let mut cpu = nes_cpu(numbered_cartridge(0, 4, 8));
+        run(&mut cpu, 100);
+        let file = encode(&cpu);
+        let before = snapshot(&cpu);
+
+        assert!(matches!(decode(&mut cpu, b"not a state file"), Err(StateError::InvalidTag)));
+        assert!(matches!(decode(&mut cpu, &file[..HEADER_SIZE + 10]), Err(StateError::Truncated)));
+
+        let mut version = file.clone();
+        version[4] = 99;
+        assert!(matches!(decode(&mut cpu, &version), Err(StateError::UnsupportedVersion(99))));
+
+        let mut corrupted = file.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xFF;
+        assert!(matches!(decode(&mut cpu, &corrupted), Err(StateError::ChecksumMismatch { .. })));
+
+        // 其他卡带和平坦内存的存档都不能加载, 加载失败时状态不变
+        let mut other_cartridge = nes_cpu(numbered_cartridge(0, 2, 8));
+        assert!(matches!(decode(&mut other_cartridge, &file), Err(StateError::Mismatch(_))));
+        let mut other_mapper = nes_cpu(numbered_cartridge(2, 4, 8));
+        assert!(matches!(decode(&mut other_mapper, &file), Err(StateError::Mismatch(_))));
+        let mut flat = CPU::new();
+        flat.register_a = 0x33;
+        assert!(matches!(decode(&mut flat, &file), Err(StateError::Mismatch(_))));
+        assert_eq!(flat.register_a, 0x33);
+        assert_eq!(snapshot(&cpu), before);
+    }
+
+    #[test]
+    fn test_save_and_load_file() {
+        let path = std::env::temp_dir().join(format!("nes_platform_state_{}.state", std::process::id()));
+        let mut cpu = nes_cpu(numbered_cartridge(1, 8, 8));
+        run(&mut cpu, 1000);
+        save_file(&cpu, &path).unwrap();
+        let expected = snapshot(&cpu);
+        run(&mut cpu, 1000);
+        load_file(&mut cpu, &path).unwrap();
+        assert_eq!(snapshot(&cpu), expected);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::cpu::{CpuError, RunControl, CPU};
+use crate::headless::NTSC_CYCLES_PER_FRAME;
+use crate::joypad::JoypadButton;
+
+/*
+贪吃蛇, 为网页上的 6502 模拟器写的程序, 加载到 0x0600
+https://bugzmanov.github.io/nes_ebook/chapter_3_4.html
+ 内存 0xFE 是随机数, 0xFF 是最后按下的方向键的 ASCII 码, 0x0200-0x05FF 是 32x32 的屏幕
+ */
+pub const SNAKE_LOAD_ADDRESS: u16 = 0x0600;
+
+pub const SNAKE_GAME_CODE: [u8; 309] = [
+    0x20, 0x06, 0x06, 0x20, 0x38, 0x06, 0x20, 0x0d, 0x06, 0x20, 0x2a, 0x06, 0x60, 0xa9, 0x02,
+    0x85, 0x02, 0xa9, 0x04, 0x85, 0x03, 0xa9, 0x11, 0x85, 0x10, 0xa9, 0x10, 0x85, 0x12, 0xa9,
+    0x0f, 0x85, 0x14, 0xa9, 0x04, 0x85, 0x11, 0x85, 0x13, 0x85, 0x15, 0x60, 0xa5, 0xfe, 0x85,
+    0x00, 0xa5, 0xfe, 0x29, 0x03, 0x18, 0x69, 0x02, 0x85, 0x01, 0x60, 0x20, 0x4d, 0x06, 0x20,
+    0x8d, 0x06, 0x20, 0xc3, 0x06, 0x20, 0x19, 0x07, 0x20, 0x20, 0x07, 0x20, 0x2d, 0x07, 0x4c,
+    0x38, 0x06, 0xa5, 0xff, 0xc9, 0x77, 0xf0, 0x0d, 0xc9, 0x64, 0xf0, 0x14, 0xc9, 0x73, 0xf0,
+    0x1b, 0xc9, 0x61, 0xf0, 0x22, 0x60, 0xa9, 0x04, 0x24, 0x02, 0xd0, 0x26, 0xa9, 0x01, 0x85,
+    0x02, 0x60, 0xa9, 0x08, 0x24, 0x02, 0xd0, 0x1b, 0xa9, 0x02, 0x85, 0x02, 0x60, 0xa9, 0x01,
+    0x24, 0x02, 0xd0, 0x10, 0xa9, 0x04, 0x85, 0x02, 0x60, 0xa9, 0x02, 0x24, 0x02, 0xd0, 0x05,
+    0xa9, 0x08, 0x85, 0x02, 0x60, 0x60, 0x20, 0x94, 0x06, 0x20, 0xa8, 0x06, 0x60, 0xa5, 0x00,
+    0xc5, 0x10, 0xd0, 0x0d, 0xa5, 0x01, 0xc5, 0x11, 0xd0, 0x07, 0xe6, 0x03, 0xe6, 0x03, 0x20,
+    0x2a, 0x06, 0x60, 0xa2, 0x02, 0xb5, 0x10, 0xc5, 0x10, 0xd0, 0x06, 0xb5, 0x11, 0xc5, 0x11,
+    0xf0, 0x09, 0xe8, 0xe8, 0xe4, 0x03, 0xf0, 0x06, 0x4c, 0xaa, 0x06, 0x4c, 0x35, 0x07, 0x60,
+    0xa6, 0x03, 0xca, 0x8a, 0xb5, 0x10, 0x95, 0x12, 0xca, 0x10, 0xf9, 0xa5, 0x02, 0x4a, 0xb0,
+    0x09, 0x4a, 0xb0, 0x19, 0x4a, 0xb0, 0x1f, 0x4a, 0xb0, 0x2f, 0xa5, 0x10, 0x38, 0xe9, 0x20,
+    0x85, 0x10, 0x90, 0x01, 0x60, 0xc6, 0x11, 0xa9, 0x01, 0xc5, 0x11, 0xf0, 0x28, 0x60, 0xe6,
+    0x10, 0xa9, 0x1f, 0x24, 0x10, 0xf0, 0x1f, 0x60, 0xa5, 0x10, 0x18, 0x69, 0x20, 0x85, 0x10,
+    0xb0, 0x01, 0x60, 0xe6, 0x11, 0xa9, 0x06, 0xc5, 0x11, 0xf0, 0x0c, 0x60, 0xc6, 0x10, 0xa5,
+    0x10, 0x29, 0x1f, 0xc9, 0x1f, 0xf0, 0x01, 0x60, 0x4c, 0x35, 0x07, 0xa0, 0x00, 0xa5, 0xfe,
+    0x91, 0x00, 0x60, 0xa6, 0x03, 0xa9, 0x00, 0x81, 0x10, 0xa2, 0x00, 0xa9, 0x01, 0x81, 0x10,
+    0x60, 0xa6, 0xff, 0xea, 0xea, 0xca, 0xd0, 0xfb, 0x60,
+];
+
+// 贪吃蛇是为网页上的 6502 模拟器写的, 没有按帧同步, 按 NES 真实的时钟频率运行会快得没法玩
+pub const SNAKE_SLOWDOWN: u64 = 40;
+
+// 减速之后每秒仍然大约 60 帧, 每帧读一次输入
+pub const SNAKE_FRAME_CYCLES: u64 = NTSC_CYCLES_PER_FRAME / SNAKE_SLOWDOWN;
+
+/**
+按帧运行贪吃蛇, 随机数来自固定种子的生成器
+ 相同的种子和每帧相同的输入总是得到完全相同的运行过程, 可以用录像复现
+ */
+pub struct Snake {
+    pub cpu: CPU,
+    pub frames: u64,
+    // 是否因为 BRK 停机, 也就是游戏结束
+    pub halted: bool,
+    rng: StdRng,
+    frame_start_cycles: u64,
+}
+
+impl Snake {
+    pub fn new(seed: u64) -> Self {
+        let mut cpu = CPU::new();
+        // 游戏结束时执行 BRK, 这里把它当作停机
+        cpu.halt_on_brk = true;
+        cpu.memory_load_program(SNAKE_GAME_CODE.to_vec()).expect("snake fits in memory");
+        cpu.reset();
+        let frame_start_cycles = cpu.cycles;
+        Snake { cpu, frames: 0, halted: false, rng: StdRng::seed_from_u64(seed), frame_start_cycles }
+    }
+
+    /*
+    用玩家 1 的按键运行一帧, 每条指令执行之前调用一次回调函数 (比如调试器)
+    回调函数返回 RunControl::Stop 或者游戏结束时返回 RunControl::Stop
+     */
+    pub fn run_frame<F>(&mut self, buttons: JoypadButton, mut callback: F) -> Result<RunControl, CpuError>
+        where F: FnMut(&mut CPU) -> RunControl {
+        if let Some(key) = snake_key(buttons) {
+            self.cpu.memory_write(0xff, key);
+        }
+        while self.cpu.cycles - self.frame_start_cycles < SNAKE_FRAME_CYCLES {
+            if self.halted || callback(&mut self.cpu) == RunControl::Stop {
+                return Ok(RunControl::Stop);
+            }
+            let random = self.rng.gen_range(1, 16);
+            self.cpu.memory_write(0xfe, random);
+            self.halted = self.cpu.step()?.brk;
+        }
+        self.frame_start_cycles += SNAKE_FRAME_CYCLES;
+        self.frames += 1;
+        Ok(RunControl::Continue)
+    }
+
+    /*
+    读档或者倒带替换了 CPU 的状态之后调用, 周期数可能变小了, 下一帧从当前的周期数重新开始计算
+     */
+    pub fn state_restored(&mut self) {
+        self.frame_start_cycles = self.cpu.cycles;
+        self.halted = false;
+    }
+}
+
+/*
+贪吃蛇从 0xFF 读取最后按下的方向键的 ASCII 码 ('w', 's', 'a', 'd'),
+这里把手柄的方向键转换成贪吃蛇需要的值
+ */
+pub fn snake_key(buttons: JoypadButton) -> Option<u8> {
+    let directions = [
+        (JoypadButton::UP, 0x77),
+        (JoypadButton::DOWN, 0x73),
+        (JoypadButton::LEFT, 0x61),
+        (JoypadButton::RIGHT, 0x64),
+    ];
+    directions
+        .iter()
+        .find(|(button, _)| buttons.contains(*button))
+        .map(|(_, key)| *key)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_snake_key() {
+        assert_eq!(snake_key(JoypadButton::empty()), None);
+        assert_eq!(snake_key(JoypadButton::LEFT), Some(0x61));
+        assert_eq!(snake_key(JoypadButton::BUTTON_A), None);
+    }
+
+    #[test]
+    fn test_run_after_restoring_state() {
+        let mut snake = Snake::new(7);
+        for _ in 0..5 {
+            snake.run_frame(JoypadButton::DOWN, |_| RunControl::Continue).unwrap();
+        }
+        let state = crate::savestate::snapshot(&snake.cpu);
+        let cycles = snake.cpu.cycles;
+        for _ in 0..5 {
+            snake.run_frame(JoypadButton::DOWN, |_| RunControl::Continue).unwrap();
+        }
+        crate::savestate::restore(&mut snake.cpu, &state).unwrap();
+        snake.state_restored();
+        assert_eq!(snake.cpu.cycles, cycles);
+        snake.run_frame(JoypadButton::DOWN, |_| RunControl::Continue).unwrap();
+        assert!(snake.cpu.cycles - cycles >= SNAKE_FRAME_CYCLES);
+    }
+
+    #[test]
+    fn test_same_seed_same_game() {
+        let mut first = Snake::new(42);
+        let mut second = Snake::new(42);
+        for frame in 0..120 {
+            let buttons = if frame < 10 { JoypadButton::DOWN } else { JoypadButton::RIGHT };
+            first.run_frame(buttons, |_| RunControl::Continue).unwrap();
+            second.run_frame(buttons, |_| RunControl::Continue).unwrap();
+        }
+        assert!(first.frames > 10);
+        assert_eq!(first.frames, second.frames);
+        assert_eq!(first.halted, second.halted);
+        assert_eq!(first.cpu.cycles, second.cpu.cycles);
+        let screen = |snake: &mut Snake| (0x0000..0x0600).map(|addr| snake.cpu.memory_read(addr)).collect::<Vec<u8>>();
+        assert_eq!(screen(&mut first), screen(&mut second));
+    }
+}
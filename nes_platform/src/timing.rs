@@ -0,0 +1,64 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/**
+NES 的 CPU 时钟频率由主晶振分频得到, 不同制式的主机频率不同
+https://www.nesdev.org/wiki/Cycle_reference_chart
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    // 21.477272 MHz / 12
+    Ntsc,
+    // 26.601712 MHz / 16
+    Pal,
+}
+
+impl Region {
+    pub fn cpu_clock_hz(&self) -> f64 {
+        match self {
+            Region::Ntsc => 1_789_773.0,
+            Region::Pal => 1_662_607.0,
+        }
+    }
+}
+
+// 落后太多(比如被调试器暂停)时不再追赶, 而是重新以当前时间为基准
+const MAX_LAG: Duration = Duration::from_millis(100);
+// 领先不足这个时间就不睡眠, 避免频繁的系统调用
+const MIN_SLEEP: Duration = Duration::from_millis(1);
+
+/**
+根据 CPU 消耗的周期数控制模拟速度, 让模拟器按照给定的时钟频率运行
+ */
+pub struct Pacer {
+    clock_hz: f64,
+    start_time: Instant,
+    start_cycles: u64,
+}
+
+impl Pacer {
+    pub fn new(clock_hz: f64, cycles: u64) -> Self {
+        Pacer { clock_hz, start_time: Instant::now(), start_cycles: cycles }
+    }
+
+    pub fn for_region(region: Region, cycles: u64) -> Self {
+        Pacer::new(region.cpu_clock_hz(), cycles)
+    }
+
+    /*
+    cycles 是 CPU 当前累计的周期数, 如果模拟跑得比真实硬件快就睡眠等待
+     */
+    pub fn pace(&mut self, cycles: u64) {
+        let emulated = Duration::from_secs_f64(cycles.saturating_sub(self.start_cycles) as f64 / self.clock_hz);
+        let elapsed = self.start_time.elapsed();
+        if emulated > elapsed {
+            let ahead = emulated - elapsed;
+            if ahead >= MIN_SLEEP {
+                thread::sleep(ahead);
+            }
+        } else if elapsed - emulated > MAX_LAG {
+            self.start_time = Instant::now();
+            self.start_cycles = cycles;
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::cpu::{CpuVariant, CPU};
+use crate::instruction::addressing::AddressingMode;
+use crate::instruction::instruction_set;
+
+/**
+按 nestest.log 的格式输出即将执行的指令和执行前的寄存器状态, 用来和参考模拟器的日志逐行对比
+ C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD CYC:7
+ 地址  指令字节   反汇编 (带上操作数地址和内存中的值)  寄存器                    周期数
+https://www.qmtpro.com/~nes/misc/nestest.log
+所有的内存读取都通过 Bus::peek, 不会影响模拟器的状态
+ */
+pub fn trace(cpu: &mut CPU) -> String {
+    let pc = cpu.program_counter;
+    let code = cpu.bus.peek(pc);
+    let Some(builtin) = instruction_set(cpu.variant).get(&code) else {
+        return format!("{:04X}  {:02X}        ???", pc, code);
+    };
+    let op = &builtin.op;
+
+    let bytes: Vec<u8> = (0..op.len as u16).map(|i| cpu.bus.peek(pc.wrapping_add(i))).collect();
+    let hex = bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
+    let operand = format_operand(cpu, op.mnemonic, &op.mode, &bytes);
+
+    let mnemonic = if op.official { op.mnemonic.to_string() } else { format!("*{}", op.mnemonic) };
+    let asm = format!("{:04X}  {:8} {: >4} {}", pc, hex, mnemonic, operand);
+    format!(
+        "{:47} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+        asm.trim_end(),
+        cpu.register_a,
+        cpu.register_x,
+        cpu.register_y,
+        cpu.status.bits(),
+        cpu.stack_pointer,
+        cpu.cycles
+    )
+}
+
+fn format_operand(cpu: &mut CPU, mnemonic: &str, mode: &AddressingMode, bytes: &[u8]) -> String {
+    let pc = cpu.program_counter;
+    let byte = bytes.get(1).copied().unwrap_or(0);
+    let word = u16::from_le_bytes([byte, bytes.get(2).copied().unwrap_or(0)]);
+    let (x, y) = (cpu.register_x, cpu.register_y);
+
+    match mode {
+        AddressingMode::NoneAddressing => String::new(),
+        AddressingMode::Accumulator => "A".to_string(),
+        AddressingMode::Immediate => format!("#${:02X}", byte),
+        AddressingMode::Relative => {
+            let target = pc.wrapping_add(2).wrapping_add(byte as i8 as u16);
+            format!("${:04X}", target)
+        }
+        AddressingMode::ZeroPage => format!("${:02X} = {:02X}", byte, cpu.bus.peek(byte as u16)),
+        AddressingMode::ZeroPage_X => {
+            let addr = byte.wrapping_add(x);
+            format!("${:02X},X @ {:02X} = {:02X}", byte, addr, cpu.bus.peek(addr as u16))
+        }
+        AddressingMode::ZeroPage_Y => {
+            let addr = byte.wrapping_add(y);
+            format!("${:02X},Y @ {:02X} = {:02X}", byte, addr, cpu.bus.peek(addr as u16))
+        }
+        // 跳转指令的操作数是目标地址, 不显示内存中的值
+        AddressingMode::Absolute if mnemonic == "JMP" || mnemonic == "JSR" => format!("${:04X}", word),
+        AddressingMode::Absolute => format!("${:04X} = {:02X}", word, cpu.bus.peek(word)),
+        AddressingMode::Absolute_X => {
+            let addr = word.wrapping_add(x as u16);
+            format!("${:04X},X @ {:04X} = {:02X}", word, addr, cpu.bus.peek(addr))
+        }
+        AddressingMode::Absolute_Y => {
+            let addr = word.wrapping_add(y as u16);
+            format!("${:04X},Y @ {:04X} = {:02X}", word, addr, cpu.bus.peek(addr))
+        }
+        AddressingMode::Indirect => {
+            // 和 JMP 一样, 指针在页尾时高字节从同一页的开头读取, 65C02 没有这个 bug
+            let hi_addr = if cpu.variant == CpuVariant::Cmos65C02 {
+                word.wrapping_add(1)
+            } else {
+                (word & 0xFF00) | (word.wrapping_add(1) & 0x00FF)
+            };
+            let target = u16::from_le_bytes([cpu.bus.peek(word), cpu.bus.peek(hi_addr)]);
+            format!("(${:04X}) = {:04X}", word, target)
+        }
+        AddressingMode::Indirect_X => {
+            let ptr = byte.wrapping_add(x);
+            let addr = peek_zero_page_u16(cpu, ptr);
+            format!("(${:02X},X) @ {:02X} = {:04X} = {:02X}", byte, ptr, addr, cpu.bus.peek(addr))
+        }
+        AddressingMode::Indirect_Y => {
+            let base = peek_zero_page_u16(cpu, byte);
+            let addr = base.wrapping_add(y as u16);
+            format!("(${:02X}),Y = {:04X} @ {:04X} = {:02X}", byte, base, addr, cpu.bus.peek(addr))
+        }
+        AddressingMode::Indirect_ZeroPage => {
+            let addr = peek_zero_page_u16(cpu, byte);
+            format!("(${:02X}) = {:04X} = {:02X}", byte, addr, cpu.bus.peek(addr))
+        }
+        AddressingMode::Indirect_Absolute_X => {
+            let ptr = word.wrapping_add(x as u16);
+            let target = u16::from_le_bytes([cpu.bus.peek(ptr), cpu.bus.peek(ptr.wrapping_add(1))]);
+            format!("(${:04X},X) @ {:04X} = {:04X}", word, ptr, target)
+        }
+        AddressingMode::ZeroPage_Relative => {
+            let offset = bytes.get(2).copied().unwrap_or(0);
+            let target = pc.wrapping_add(3).wrapping_add(offset as i8 as u16);
+            format!("${:02X} = {:02X},${:04X}", byte, cpu.bus.peek(byte as u16), target)
+        }
+    }
+}
+
+// 零页上的指针, 高字节在 0xFF 之后回到 0x00
+fn peek_zero_page_u16(cpu: &mut CPU, ptr: u8) -> u16 {
+    u16::from_le_bytes([cpu.bus.peek(ptr as u16), cpu.bus.peek(ptr.wrapping_add(1) as u16)])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_trace() {
+        let mut cpu = CPU::new();
+        cpu.memory_write(100, 0xa2);
+        cpu.memory_write(101, 0x01);
+        cpu.memory_write(102, 0xca);
+        cpu.memory_write(103, 0x88);
+        cpu.memory_write(104, 0x00);
+        cpu.program_counter = 0x64;
+        cpu.register_a = 1;
+        cpu.register_x = 2;
+        cpu.register_y = 3;
+        cpu.halt_on_brk = true;
+        let mut result = vec![];
+        cpu.run_with_callback(|cpu| {
+            result.push(trace(cpu));
+            crate::cpu::RunControl::Continue
+        }).unwrap();
+        assert_eq!(
+            "0064  A2 01     LDX #$01                        A:01 X:02 Y:03 P:24 SP:FD CYC:0",
+            result[0]
+        );
+        assert_eq!(
+            "0066  CA        DEX                             A:01 X:01 Y:03 P:24 SP:FD CYC:2",
+            result[1]
+        );
+        assert_eq!(
+            "0067  88        DEY                             A:01 X:00 Y:03 P:26 SP:FD CYC:4",
+            result[2]
+        );
+    }
+
+    #[test]
+    fn test_format_memory_access() {
+        let mut cpu = CPU::new();
+        // ORA ($33), Y
+        cpu.memory_write(100, 0x11);
+        cpu.memory_write(101, 0x33);
+        cpu.memory_write(0x33, 0x00);
+        cpu.memory_write(0x34, 0x04);
+        cpu.memory_write(0x400, 0xAA);
+        cpu.program_counter = 0x64;
+        assert_eq!(
+            "0064  11 33     ORA ($33),Y = 0400 @ 0400 = AA  A:00 X:00 Y:00 P:24 SP:FD CYC:0",
+            trace(&mut cpu)
+        );
+
+        // JMP ($02FF) 的页尾 bug, LSR A
+        cpu.memory_write(100, 0x6c);
+        cpu.memory_write(101, 0xff);
+        cpu.memory_write(102, 0x02);
+        cpu.memory_write(0x02ff, 0x34);
+        cpu.memory_write(0x0200, 0x12);
+        assert!(trace(&mut cpu).starts_with("0064  6C FF 02  JMP ($02FF) = 1234 "));
+        cpu.memory_write(100, 0x4a);
+        assert!(trace(&mut cpu).starts_with("0064  4A        LSR A "));
+    }
+
+    #[cfg(feature = "unofficial-opcodes")]
+    #[test]
+    fn test_format_unofficial_opcode() {
+        let mut cpu = CPU::new();
+        // nestest.log 中非官方指令的助记符前面有一个 *
+        cpu.memory_write(100, 0x04);
+        cpu.memory_write(101, 0xa9);
+        cpu.program_counter = 0x64;
+        assert!(trace(&mut cpu).starts_with("0064  04 A9    *NOP $A9 = 00 "));
+    }
+}
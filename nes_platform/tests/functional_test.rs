@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::Path;
+
+use nes_platform::cpu::{CpuVariant, CPU};
+use nes_platform::memory::Memory;
+
+/*
+Klaus Dormann 的 6502 功能测试: https://github.com/Klaus2m5/6502_65C02_functional_tests
+用默认配置汇编出来的 6502_functional_test.bin 是完整的 64KB 内存映像, 从 $0400 开始执行
+每个测试失败时都会停在一个跳转到自己的死循环上 (JMP * 或者 BNE *), 全部通过时停在 SUCCESS_ADDRESS
+当前测试的编号保存在零页之后的 $0200
+ */
+const START_ADDRESS: u16 = 0x0400;
+// 默认配置 (带十进制测试) 汇编出来的二进制中 success 标签的地址, 换了配置重新汇编时要跟着改
+const SUCCESS_ADDRESS: u16 = 0x3469;
+const TEST_CASE_ADDRESS: u16 = 0x0200;
+// 整个测试大约执行三千万条指令, 超过这个数量说明程序跑飞了
+const MAX_INSTRUCTIONS: u64 = 100_000_000;
+
+/*
+运行一个 64KB 的内存映像直到遇到死循环, 停在 success_address 上返回 Ok, 否则返回带测试编号的错误信息
+ */
+fn run_functional_image(image: Vec<u8>, success_address: u16) -> Result<(), String> {
+    assert_eq!(image.len(), 0x10000, "functional test image should be a 64KB memory image");
+    let mut memory = Memory::default();
+    memory.load_program(0x0000, image).unwrap();
+    // 测试包含十进制模式, NES 的 2A03 没有这个功能, 用 NMOS 6502 来运行
+    let mut cpu = CPU::with_variant(Box::new(memory), CpuVariant::Nmos6502);
+    cpu.program_counter = START_ADDRESS;
+
+    for _ in 0..MAX_INSTRUCTIONS {
+        let step = match cpu.step() {
+            Ok(step) => step,
+            Err(e) => return Err(format!("test case {:#04X}: {}", cpu.memory_read(TEST_CASE_ADDRESS), e)),
+        };
+        if cpu.program_counter != step.program_counter {
+            continue;
+        }
+        if step.program_counter == success_address {
+            return Ok(());
+        }
+        let test_case = cpu.memory_read(TEST_CASE_ADDRESS);
+        return Err(format!(
+            "test case {:#04X} failed: trapped at {:04X} ({}) A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+            test_case,
+            step.program_counter,
+            step.mnemonic,
+            cpu.register_a,
+            cpu.register_x,
+            cpu.register_y,
+            cpu.status.bits(),
+            cpu.stack_pointer
+        ));
+    }
+    let test_case = cpu.memory_read(TEST_CASE_ADDRESS);
+    Err(format!("no trap after {} instructions, pc {:04X}, test case {:#04X}", MAX_INSTRUCTIONS, cpu.program_counter, test_case))
+}
+
+/*
+二进制文件是 GPL 授权的, 没有放进仓库, 需要手动下载之后运行:
+ curl -L -o tests/roms/6502_functional_test.bin \
+   https://github.com/Klaus2m5/6502_65C02_functional_tests/raw/master/bin_files/6502_functional_test.bin
+ cargo test --release --test functional_test -- --ignored
+ */
+#[test]
+#[ignore = "needs tests/roms/6502_functional_test.bin, see the comment above"]
+fn test_klaus_functional() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/roms/6502_functional_test.bin");
+    let image = fs::read(&path).unwrap_or_else(|e| panic!("{}: {}, see the comment above test_klaus_functional", path.display(), e));
+    if let Err(message) = run_functional_image(image, SUCCESS_ADDRESS) {
+        panic!("{}", message);
+    }
+}
+
+/*
+用手写的小映像检查死循环检测和测试编号的报告, 不需要真正的测试二进制
+ */
+#[test]
+fn test_trap_detection() {
+    let mut image = vec![0; 0x10000];
+    // LDA #$05; STA $0200; BEQ *+2; BNE * (失败的死循环)
+    image[0x0400..0x040a].copy_from_slice(&[0xa9, 0x05, 0x8d, 0x00, 0x02, 0xf0, 0x00, 0xd0, 0xfe, 0x00]);
+    let message = run_functional_image(image.clone(), SUCCESS_ADDRESS).unwrap_err();
+    assert!(message.starts_with("test case 0x05 failed: trapped at 0407 (BNE)"), "{}", message);
+
+    // JMP success; success: JMP success
+    image[0x0400..0x0403].copy_from_slice(&[0x4c, 0x69, 0x34]);
+    image[0x3469..0x346c].copy_from_slice(&[0x4c, 0x69, 0x34]);
+    assert_eq!(run_functional_image(image, SUCCESS_ADDRESS), Ok(()));
+}
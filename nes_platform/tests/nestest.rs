@@ -0,0 +1,42 @@
+// 日志的后半部分是非官方指令, 只在打开 unofficial-opcodes 特性时编译
+#![cfg(feature = "unofficial-opcodes")]
+
+use std::fs;
+use std::path::Path;
+
+use nes_platform::bus::NesBus;
+use nes_platform::cartridge::Cartridge;
+use nes_platform::cpu::CPU;
+use nes_platform::trace::trace;
+
+// 参考日志中还有 PPU 的扫描线和周期, 这里不比较
+fn strip_ppu_column(line: &str) -> String {
+    match (line.find(" PPU:"), line.find(" CYC:")) {
+        (Some(ppu), Some(cyc)) => format!("{}{}", &line[..ppu], &line[cyc..]),
+        _ => line.to_string(),
+    }
+}
+
+/*
+nestest.nes 的自动化模式: 从 0xC000 开始执行, 和 nestest.log 逐行对比
+ROM 和日志还没有放进仓库, 放进 tests/roms/ 之后去掉 #[ignore]:
+ curl -o tests/roms/nestest.nes https://www.qmtpro.com/~nes/misc/nestest.nes
+ curl -o tests/roms/nestest.log https://www.qmtpro.com/~nes/misc/nestest.log
+ cargo test --features unofficial-opcodes --test nestest -- --ignored
+ */
+#[test]
+#[ignore = "needs tests/roms/nestest.nes and nestest.log, see the comment above"]
+fn test_nestest_log() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/roms");
+    let (rom, log) = (dir.join("nestest.nes"), dir.join("nestest.log"));
+    assert!(rom.exists() && log.exists(), "{} or {} not found, see the comment above test_nestest_log", rom.display(), log.display());
+    let golden = fs::read_to_string(log).unwrap();
+    let mut cpu = CPU::with_bus(Box::new(NesBus::new(Cartridge::load(rom).unwrap()).unwrap()));
+    cpu.reset();
+    cpu.program_counter = 0xC000;
+
+    for (line_number, expected) in golden.lines().enumerate() {
+        assert_eq!(trace(&mut cpu), strip_ppu_column(expected), "nestest.log line {}", line_number + 1);
+        cpu.step().unwrap();
+    }
+}